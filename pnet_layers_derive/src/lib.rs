@@ -0,0 +1,703 @@
+//! Derive macro defining a `pnet_layers` layer from a `pnet_macros::packet` struct.
+//!
+//! The derive is placed below `#[packet]`, so `pnet_macros` generates the `<Name>Packet` and
+//! `Mutable<Name>Packet` types and this crate generates the `<Name>Layer` protocol which plugs
+//! the header into the registry of `pnet_layers` and the `<Name>Ref` and `<Name>Mut` layers. The attributes are documented at the
+//! re-export `pnet_layers::Layer`.
+
+#![warn(clippy::pedantic)]
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote};
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::{
+    Attribute, Data, DeriveInput, Error, Fields, Ident, LitInt, LitStr, Path, Result, Token, Type,
+    parse_macro_input,
+};
+
+/// Generates the `<Name>Layer` protocol and the `<Name>Ref` and `<Name>Mut` layers of a
+/// `#[packet]` struct
+#[proc_macro_derive(Layer, attributes(layer))]
+pub fn derive_layer(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+/// Field of a lower layer, named like the variants of `registry::Binding` in snake case
+#[derive(Clone, Copy)]
+enum BindingKind {
+    EtherType,
+    IpProto,
+    UdpPort,
+    TcpPort,
+}
+
+impl BindingKind {
+    fn parse(ident: &Ident) -> Result<Self> {
+        Ok(match ident.to_string().as_str() {
+            "ether_type" => Self::EtherType,
+            "ip_proto" => Self::IpProto,
+            "udp_port" => Self::UdpPort,
+            "tcp_port" => Self::TcpPort,
+            _ => {
+                return Err(Error::new(
+                    ident.span(),
+                    "expected `ether_type`, `ip_proto`, `udp_port` or `tcp_port`",
+                ));
+            }
+        })
+    }
+
+    fn variant(self) -> Ident {
+        format_ident!(
+            "{}",
+            match self {
+                Self::EtherType => "EtherType",
+                Self::IpProto => "IpProto",
+                Self::UdpPort => "UdpPort",
+                Self::TcpPort => "TcpPort",
+            }
+        )
+    }
+}
+
+/// Options given by `#[layer(..)]`
+#[derive(Default)]
+struct Options {
+    name: Option<LitStr>,
+    next: Option<(BindingKind, Ident)>,
+    length: Option<Ident>,
+    payload_length: Option<Ident>,
+    checksum: Option<Ident>,
+    build: Option<Path>,
+    bind: Vec<(BindingKind, LitInt)>,
+}
+
+impl Options {
+    fn parse(attrs: &[Attribute]) -> Result<Self> {
+        let mut options = Self::default();
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("layer")) {
+            attr.parse_nested_meta(|meta| {
+                let path = &meta.path;
+                if path.is_ident("name") {
+                    options.name = Some(meta.value()?.parse()?);
+                } else if path.is_ident("next") {
+                    let value = meta.value()?;
+                    let kind = BindingKind::parse(&value.parse()?)?;
+                    let content;
+                    syn::parenthesized!(content in value);
+                    options.next = Some((kind, content.parse()?));
+                } else if path.is_ident("length") {
+                    options.length = Some(meta.value()?.parse()?);
+                } else if path.is_ident("payload_length") {
+                    options.payload_length = Some(meta.value()?.parse()?);
+                } else if path.is_ident("checksum") {
+                    options.checksum = Some(meta.value()?.parse()?);
+                } else if path.is_ident("build") {
+                    options.build = Some(meta.value()?.parse()?);
+                } else if path.is_ident("bind") {
+                    meta.parse_nested_meta(|binding| {
+                        let kind = binding
+                            .path
+                            .get_ident()
+                            .ok_or_else(|| binding.error("expected a binding"))?;
+                        let kind = BindingKind::parse(kind)?;
+                        options.bind.push((kind, binding.value()?.parse()?));
+                        Ok(())
+                    })?;
+                } else {
+                    return Err(meta.error("unsupported layer attribute"));
+                }
+                Ok(())
+            })?;
+        }
+        Ok(options)
+    }
+}
+
+/// Kind of a header field like `pnet_layers::FieldKind`, `Hidden` fields can not be accessed
+/// by their name
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Int(usize),
+    Mac,
+    Ipv4,
+    Ipv6,
+    Bytes,
+    Hidden,
+}
+
+/// Field of the header before the payload
+struct HeaderField {
+    ident: Ident,
+    /// Offset in bits, `None` after a field with a variable length
+    offset: Option<usize>,
+    kind: Kind,
+}
+
+impl HeaderField {
+    /// Returns the `pnet_layers::Field` of the field if it can be accessed by its name
+    fn field(&self) -> Option<TokenStream2> {
+        let name = self.ident.to_string();
+        let offset = self.offset?;
+        let kind = match self.kind {
+            Kind::Int(bits) => {
+                return Some(quote!(::pnet_layers::Field::int(#name, #offset, #bits)));
+            }
+            Kind::Mac => quote!(Mac),
+            Kind::Ipv4 => quote!(Ipv4),
+            Kind::Ipv6 => quote!(Ipv6),
+            Kind::Bytes => quote!(Bytes),
+            Kind::Hidden => return None,
+        };
+        Some(quote!(::pnet_layers::Field::new(#name, #offset, ::pnet_layers::FieldKind::#kind)))
+    }
+}
+
+/// Returns the last identifier of the type path, e.g. `u16be` or `Vec`
+fn type_name(ty: &Type) -> Option<String> {
+    match ty {
+        Type::Path(path) => Some(path.path.segments.last()?.ident.to_string()),
+        _ => None,
+    }
+}
+
+/// Returns the number of bits of a `pnet_macros_support::types` primitive and whether it is
+/// read in network byte order
+fn primitive(ty: &Type) -> Option<(usize, bool)> {
+    let name = type_name(ty)?;
+    let digits = name.strip_prefix('u')?;
+    let end = digits
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(digits.len());
+    let bits: usize = digits[..end].parse().ok()?;
+    let big_endian = match &digits[end..] {
+        "" => bits <= 8,
+        "be" => true,
+        "le" | "he" => false,
+        _ => return None,
+    };
+    (1..=64).contains(&bits).then_some((bits, big_endian))
+}
+
+/// Returns the kind and size of a field with `#[construct_with(..)]`
+fn constructed(ty: &Type, attr: &Attribute) -> Result<(Kind, usize)> {
+    let args = attr.parse_args_with(Punctuated::<Type, Token![,]>::parse_terminated)?;
+    let mut bits = 0;
+    let mut big_endian = true;
+    for arg in &args {
+        let (size, order) =
+            primitive(arg).ok_or_else(|| Error::new(arg.span(), "expected a primitive type"))?;
+        bits += size;
+        big_endian &= order;
+    }
+    let kind = match (type_name(ty).as_deref(), bits) {
+        (Some("MacAddr"), 48) => Kind::Mac,
+        (Some("Ipv4Addr"), 32) => Kind::Ipv4,
+        (Some("Ipv6Addr"), 128) => Kind::Ipv6,
+        (_, ..=64) if big_endian => Kind::Int(bits),
+        _ => Kind::Hidden,
+    };
+    Ok((kind, bits))
+}
+
+/// Returns the fields of the header, which are all fields before the `#[payload]`
+fn header_fields(input: &DeriveInput) -> Result<Vec<HeaderField>> {
+    let Data::Struct(data) = &input.data else {
+        return Err(Error::new(input.span(), "only structs are supported"));
+    };
+    let Fields::Named(fields) = &data.fields else {
+        return Err(Error::new(
+            input.span(),
+            "only structs with named fields are supported",
+        ));
+    };
+    let mut offset = Some(0);
+    let mut payload = false;
+    let mut header = vec![];
+    for field in &fields.named {
+        let Some(ident) = field.ident.clone() else {
+            continue;
+        };
+        if field
+            .attrs
+            .iter()
+            .any(|attr| attr.path().is_ident("payload"))
+        {
+            payload = true;
+            continue;
+        }
+        if payload {
+            return Err(Error::new(
+                field.span(),
+                "fields after the payload are not supported",
+            ));
+        }
+        let construct_with = field
+            .attrs
+            .iter()
+            .find(|attr| attr.path().is_ident("construct_with"));
+        let (kind, bits) = if let Some(attr) = construct_with {
+            let (kind, bits) = constructed(&field.ty, attr)?;
+            (kind, Some(bits))
+        } else if type_name(&field.ty).as_deref() == Some("Vec") {
+            (Kind::Bytes, None)
+        } else if let Some((bits, big_endian)) = primitive(&field.ty) {
+            let kind = if big_endian {
+                Kind::Int(bits)
+            } else {
+                Kind::Hidden
+            };
+            (kind, Some(bits))
+        } else {
+            return Err(Error::new(field.ty.span(), "unsupported field type"));
+        };
+        header.push(HeaderField {
+            ident,
+            offset,
+            kind,
+        });
+        offset = offset.zip(bits).map(|(offset, bits)| offset + bits);
+    }
+    Ok(header)
+}
+
+/// Returns the `pnet_layers::Field` of the field named by an option. Fields set on build must
+/// be integers at a fixed offset.
+fn int_field(header: &[HeaderField], ident: &Ident) -> Result<(TokenStream2, usize, usize)> {
+    let field = header
+        .iter()
+        .find(|field| field.ident == *ident)
+        .ok_or_else(|| Error::new(ident.span(), "no header field with this name"))?;
+    match (field.kind, field.offset, field.field()) {
+        (Kind::Int(bits), Some(offset), Some(tokens)) => Ok((tokens, offset, bits)),
+        _ => Err(Error::new(
+            ident.span(),
+            "expected an integer field at a fixed offset",
+        )),
+    }
+}
+
+/// Generates the body of `Protocol::build` filling in the type of the upper layer, the lengths
+/// and the checksum in this order
+fn build_body(options: &Options, header: &[HeaderField]) -> Result<TokenStream2> {
+    let mut body = quote!();
+    if let Some((kind, ident)) = &options.next {
+        let (field, ..) = int_field(header, ident)?;
+        // upper layers do not announce a port, so only the type fields are set
+        let announced = match kind {
+            BindingKind::EtherType => Some(quote!(::pnet_layers::registry::ether_type)),
+            BindingKind::IpProto => Some(quote!(::pnet_layers::registry::ip_proto)),
+            BindingKind::UdpPort | BindingKind::TcpPort => None,
+        };
+        if let Some(announced) = announced {
+            body.extend(quote! {
+                if let Some(value) = upper_layer.and_then(#announced) {
+                    #field.set(header, &::pnet_layers::Value::Int(u64::from(value)))?;
+                }
+            });
+        }
+    }
+    for (ident, len) in [
+        (&options.length, quote!(header.len() + payload.len())),
+        (&options.payload_length, quote!(payload.len())),
+    ] {
+        let Some(ident) = ident else {
+            continue;
+        };
+        let (field, ..) = int_field(header, ident)?;
+        body.extend(quote! {
+            let length = u64::try_from(#len).unwrap_or(u64::MAX);
+            #field.set(header, &::pnet_layers::Value::Int(length))?;
+        });
+    }
+    if let Some(build) = &options.build {
+        body.extend(quote!(#build(header, upper_layer, payload)?;));
+    }
+    if let Some(ident) = &options.checksum {
+        let (field, offset, bits) = int_field(header, ident)?;
+        if bits != 16 || offset % 16 != 0 {
+            return Err(Error::new(
+                ident.span(),
+                "the checksum must be a 16 bit field at an even byte offset",
+            ));
+        }
+        let word = offset / 16;
+        body.extend(quote! {
+            #field.set(header, &::pnet_layers::Value::Int(0))?;
+            let mut data = header.clone();
+            data.extend_from_slice(payload);
+            let checksum = ::pnet_layers::__private::checksum(&data, #word);
+            #field.set(header, &::pnet_layers::Value::Int(u64::from(checksum)))?;
+        });
+    }
+    Ok(body)
+}
+
+/// Generates `Protocol::next` reading the binding of the payload from the header
+fn next_fn(options: &Options, header: &[HeaderField]) -> Result<TokenStream2> {
+    let Some((kind, ident)) = &options.next else {
+        return Ok(quote!());
+    };
+    let (field, ..) = int_field(header, ident)?;
+    let variant = kind.variant();
+    Ok(quote! {
+        fn next(&self, header: &[u8]) -> Option<::pnet_layers::registry::Binding> {
+            let ::pnet_layers::Value::Int(value) = #field.get(header)? else {
+                return None;
+            };
+            Some(::pnet_layers::registry::Binding::#variant(
+                ::core::convert::TryFrom::try_from(value).ok()?,
+            ))
+        }
+    })
+}
+
+/// Generates `Protocol::header_len`, the header ends after the fixed size fields and the
+/// variable length fields
+fn header_len_fn(packet: &Ident, header: &[HeaderField]) -> TokenStream2 {
+    let raw: Vec<Ident> = header
+        .iter()
+        .filter(|field| field.kind == Kind::Bytes)
+        .map(|field| format_ident!("get_{}_raw", field.ident))
+        .collect();
+    let len = if raw.is_empty() {
+        quote!(#packet::minimum_packet_size())
+    } else {
+        quote! {{
+            let packet = #packet::new(buf).ok_or_else(truncated)?;
+            #packet::minimum_packet_size() #(+ packet.#raw().len())*
+        }}
+    };
+    quote! {
+        fn header_len(&self, buf: &[u8]) -> ::pnet_layers::Result<usize> {
+            let truncated = || ::pnet_layers::Error::Truncated {
+                layer: ::pnet_layers::Layers::Custom,
+                offset: 0,
+            };
+            let len = #len;
+            if len > buf.len() {
+                return Err(truncated());
+            }
+            Ok(len)
+        }
+    }
+}
+
+/// Generates the `<Name>Ref` layer implementing `LayerImmutable` like the built-in layers
+fn immutable_layer(vis: &syn::Visibility, ident: &Ident, protocol: &Ident) -> TokenStream2 {
+    let immutable = format_ident!("{}Ref", ident);
+    let mutable = format_ident!("{}Mut", ident);
+    let packet = format_ident!("{}Packet", ident);
+    let mut_packet = format_ident!("Mutable{}Packet", ident);
+    let doc = format!("Immutable representation of a `{ident}` layer");
+
+    quote! {
+        #[doc = #doc]
+        #[derive(Debug, Clone, Copy)]
+        #vis struct #immutable<'a> {
+            buf: &'a [u8],
+        }
+
+        impl<'a> ::pnet_layers::LayerImmutable<'a> for #immutable<'a> {
+            type Packet = #packet<'a>;
+            type PacketMut = #mut_packet<'a>;
+            type LayerMutType = #mutable;
+            const LAYER: ::pnet_layers::Layers = ::pnet_layers::Layers::Custom;
+
+            fn new(buf: &'a [u8]) -> Self {
+                Self { buf }
+            }
+
+            fn as_mut(&self) -> ::pnet_layers::Result<#mutable> {
+                <#mutable as ::pnet_layers::LayerMutable>::from_buf(self.buf.to_vec())
+            }
+
+            fn get_buf(&self) -> &[u8] {
+                self.buf
+            }
+
+            fn as_pnet(&self) -> ::pnet_layers::Result<#packet<'a>> {
+                #packet::new(self.buf).ok_or(::pnet_layers::Error::Truncated {
+                    layer: Self::LAYER,
+                    offset: 0,
+                })
+            }
+
+            fn as_mut_pnet(&self) -> ::pnet_layers::Result<#mut_packet<'a>> {
+                #mut_packet::owned(self.buf.to_vec()).ok_or(::pnet_layers::Error::Truncated {
+                    layer: Self::LAYER,
+                    offset: 0,
+                })
+            }
+
+            fn get_layer(&'a self, layer: ::pnet_layers::Layers) -> Option<::pnet_layers::Layer<'a>> {
+                Self::get_layer_from_buf(self.buf, layer)
+            }
+
+            fn get_layer_from_buf(
+                buf: &[u8],
+                layer: ::pnet_layers::Layers,
+            ) -> Option<::pnet_layers::Layer<'_>> {
+                ::pnet_layers::LayerIter::with_protocol(buf, #protocol::protocol())
+                    .skip(1)
+                    .map(|span| span.layer)
+                    .find(|upper| upper.kind() == layer)
+            }
+
+            fn layers(&'a self) -> ::pnet_layers::LayerIter<'a> {
+                ::pnet_layers::LayerIter::with_protocol(self.buf, #protocol::protocol())
+            }
+        }
+    }
+}
+
+/// Generates the `<Name>Mut` layer implementing `LayerMutable` like the built-in layers. It
+/// wraps the `CustomMut` of the protocol, so it is stacked as `LayerMut::Custom`.
+fn mutable_layer(vis: &syn::Visibility, ident: &Ident, protocol: &Ident) -> TokenStream2 {
+    let mutable = format_ident!("{}Mut", ident);
+    let mut_packet = format_ident!("Mutable{}Packet", ident);
+    let doc = format!(
+        "Mutable representation of a `{ident}` layer, stacked on other layers as `LayerMut::Custom`"
+    );
+
+    quote! {
+        #[doc = #doc]
+        #[derive(Clone)]
+        #vis struct #mutable(::pnet_layers::CustomMut);
+
+        impl From<#mutable> for ::pnet_layers::LayerMut {
+            fn from(layer: #mutable) -> Self {
+                Self::Custom(layer.0)
+            }
+        }
+
+        impl TryFrom<::pnet_layers::LayerMut> for #mutable {
+            type Error = ::pnet_layers::LayerMut;
+
+            /// Returns the layer back if it is not of this protocol
+            fn try_from(layer: ::pnet_layers::LayerMut) -> Result<Self, Self::Error> {
+                match layer {
+                    ::pnet_layers::LayerMut::Custom(custom)
+                        if custom.protocol().name() == #protocol::NAME =>
+                    {
+                        Ok(Self(custom))
+                    }
+                    other => Err(other),
+                }
+            }
+        }
+
+        impl<'a> ::pnet_layers::LayerMutable<'a> for #mutable {
+            type PacketMut = #mut_packet<'a>;
+            const LAYER: ::pnet_layers::Layers = ::pnet_layers::Layers::Custom;
+            const FIELDS: &'static [::pnet_layers::Field] = #protocol::FIELDS;
+            const UPPER: &'static [::pnet_layers::Layers] =
+                <::pnet_layers::CustomMut as ::pnet_layers::LayerMutable<'static>>::UPPER;
+
+            fn new() -> Self {
+                Self(::pnet_layers::CustomMut::with_protocol(#protocol::protocol()))
+            }
+
+            fn from_buf(buf: Vec<u8>) -> ::pnet_layers::Result<Self> {
+                ::pnet_layers::CustomMut::parse(#protocol::protocol(), buf).map(Self)
+            }
+
+            fn upper_layer(&self) -> Option<&::pnet_layers::LayerMut> {
+                self.0.upper_layer.as_deref()
+            }
+
+            fn upper_layer_mut(&mut self) -> Option<&mut ::pnet_layers::LayerMut> {
+                self.0.upper_layer.as_deref_mut()
+            }
+
+            fn name(&self) -> &'static str {
+                #protocol::NAME
+            }
+
+            fn get_layer(
+                &'a mut self,
+                layer: &::pnet_layers::Layers,
+            ) -> Option<&'a mut ::pnet_layers::LayerMut> {
+                ::pnet_layers::LayerMutable::get_layer(&mut self.0, layer)
+            }
+
+            fn replace_upper_layer(
+                &mut self,
+                layer: Option<::pnet_layers::LayerMut>,
+            ) -> ::pnet_layers::Result<Option<::pnet_layers::LayerMut>> {
+                ::pnet_layers::LayerMutable::replace_upper_layer(&mut self.0, layer)
+            }
+
+            fn modify(&'a mut self) -> ::pnet_layers::Result<#mut_packet<'a>> {
+                #mut_packet::new(self.0.header_mut()).ok_or(::pnet_layers::Error::Truncated {
+                    layer: Self::LAYER,
+                    offset: 0,
+                })
+            }
+
+            /// Replaces the upper layers with the payload
+            fn set_payload(&'a mut self, payload: &[u8]) {
+                self.0.upper_layer = (!payload.is_empty()).then(|| {
+                    Box::new(::pnet_layers::LayerMut::Payload(::pnet_layers::PayloadMut {
+                        buf: payload.to_vec(),
+                    }))
+                });
+            }
+
+            fn add(&mut self, layer: ::pnet_layers::LayerMut) -> ::pnet_layers::Result<()> {
+                ::pnet_layers::LayerMutable::add(&mut self.0, layer)
+            }
+
+            fn switch_src_dst(&mut self) {
+                ::pnet_layers::LayerMutable::switch_src_dst(&mut self.0);
+            }
+
+            fn build(self) -> ::pnet_layers::Result<Vec<u8>> {
+                ::pnet_layers::LayerMutable::build(self.0)
+            }
+        }
+
+        impl ::std::fmt::Display for #mutable {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                ::std::fmt::Display::fmt(&self.0, f)
+            }
+        }
+
+        impl ::std::fmt::Debug for #mutable {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                ::std::fmt::Debug::fmt(&self.0, f)
+            }
+        }
+    }
+}
+
+fn expand(input: &DeriveInput) -> Result<TokenStream2> {
+    let options = Options::parse(&input.attrs)?;
+    let header = header_fields(input)?;
+    let vis = &input.vis;
+    let ident = &input.ident;
+    let protocol = format_ident!("{}Layer", ident);
+    let packet = format_ident!("{}Packet", ident);
+    let mut_packet = format_ident!("Mutable{}Packet", ident);
+    let name = options
+        .name
+        .clone()
+        .unwrap_or_else(|| LitStr::new(&ident.to_string(), Span::call_site()));
+    let fields = header.iter().filter_map(HeaderField::field);
+    let header_len = header_len_fn(&packet, &header);
+    let next = next_fn(&options, &header)?;
+    let build = build_body(&options, &header)?;
+    let bindings = options.bind.iter().map(|(kind, value)| {
+        let variant = kind.variant();
+        quote!(::pnet_layers::registry::Binding::#variant(#value))
+    });
+    let doc = format!("Protocol of the `{ident}` layer generated by `#[derive(Layer)]`");
+    let immutable = immutable_layer(vis, ident, &protocol);
+    let mutable = mutable_layer(vis, ident, &protocol);
+
+    Ok(quote! {
+        #[doc = #doc]
+        #[derive(Debug, Clone, Copy, Default)]
+        #vis struct #protocol;
+
+        impl #protocol {
+            /// Name of the layer
+            pub const NAME: &'static str = #name;
+            /// Fields of the header which can be accessed by their name
+            pub const FIELDS: &'static [::pnet_layers::Field] = &[#(#fields),*];
+
+            /// Binds the protocol to the lower layer fields given by `#[layer(bind(..))]` and
+            /// returns the bound protocol
+            pub fn register() -> ::std::sync::Arc<dyn ::pnet_layers::registry::Protocol> {
+                let protocol: ::std::sync::Arc<dyn ::pnet_layers::registry::Protocol> =
+                    ::std::sync::Arc::new(Self);
+                #(::pnet_layers::registry::bind(#bindings, protocol.clone());)*
+                protocol
+            }
+
+            /// Returns the protocol bound in the registry or an unbound protocol
+            #[must_use]
+            pub fn protocol() -> ::std::sync::Arc<dyn ::pnet_layers::registry::Protocol> {
+                ::pnet_layers::registry::by_name(Self::NAME)
+                    .unwrap_or_else(|| ::std::sync::Arc::new(Self))
+            }
+
+            /// Creates a layer with a zeroed header
+            #[must_use]
+            pub fn new_layer() -> ::pnet_layers::LayerMut {
+                ::pnet_layers::LayerMut::Custom(::pnet_layers::CustomMut::with_protocol(
+                    Self::protocol(),
+                ))
+            }
+
+            /// Parses the header and the upper layers announced by the header
+            ///
+            /// # Errors
+            /// Returns `Error::Truncated` if the buffer is shorter than the header or the
+            /// error of an upper layer
+            pub fn from_buf(buf: Vec<u8>) -> ::pnet_layers::Result<::pnet_layers::LayerMut> {
+                ::pnet_layers::CustomMut::parse(Self::protocol(), buf)
+                    .map(::pnet_layers::LayerMut::Custom)
+            }
+
+            /// Returns the header if the layer is of this protocol
+            #[must_use]
+            pub fn packet(layer: &::pnet_layers::LayerMut) -> Option<#packet<'_>> {
+                match layer {
+                    ::pnet_layers::LayerMut::Custom(_) if layer.name() == Self::NAME => {
+                        #packet::new(layer.header())
+                    }
+                    _ => None,
+                }
+            }
+
+            /// Returns the header for writing if the layer is of this protocol
+            pub fn modify(layer: &mut ::pnet_layers::LayerMut) -> Option<#mut_packet<'_>> {
+                match layer {
+                    ::pnet_layers::LayerMut::Custom(custom) if custom.protocol().name() == Self::NAME => {
+                        #mut_packet::new(custom.header_mut())
+                    }
+                    _ => None,
+                }
+            }
+        }
+
+        #immutable
+
+        #mutable
+
+        impl ::pnet_layers::registry::Protocol for #protocol {
+            fn name(&self) -> &'static str {
+                Self::NAME
+            }
+
+            fn fields(&self) -> &'static [::pnet_layers::Field] {
+                Self::FIELDS
+            }
+
+            fn new_header(&self) -> Vec<u8> {
+                vec![0; #packet::minimum_packet_size()]
+            }
+
+            #header_len
+
+            #next
+
+            #[allow(unused_variables)]
+            fn build(
+                &self,
+                header: &mut Vec<u8>,
+                upper_layer: Option<&::pnet_layers::LayerMut>,
+                payload: &[u8],
+            ) -> ::pnet_layers::Result<()> {
+                #build
+                Ok(())
+            }
+        }
+    })
+}
@@ -0,0 +1,498 @@
+//! Relation between requests and their responses.
+//!
+//! A response answers a request if:
+//! - TCP: the ports are swapped and the probe is acknowledged or reset
+//! - UDP: the ports are swapped
+//! - ICMP and `ICMPv6`: it is an echo, timestamp or neighbor discovery reply with the same
+//!   identifiers, or an error message quoting the request
+//! - ARP: it is an is-at for the address of the who-has
+//!
+//! The `hashret` of a response is equal to the `hashret` of the request it answers, so
+//! responses can be matched to a large number of requests with a hash map.
+
+use crate::traits::Answers;
+use crate::{LayerMut, Layers, Value};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// Borrowed header and upper layer of a layer, so the relation can be implemented once for
+/// `LayerMut` and the layer types
+#[derive(Clone, Copy)]
+pub(crate) struct View<'a> {
+    kind: Layers,
+    header: &'a [u8],
+    upper: Option<&'a LayerMut>,
+}
+
+impl<'a> View<'a> {
+    pub(crate) fn new(kind: Layers, header: &'a [u8], upper: Option<&'a LayerMut>) -> Self {
+        Self {
+            kind,
+            header,
+            upper,
+        }
+    }
+
+    fn of(layer: &'a LayerMut) -> Self {
+        Self::new(layer.kind(), layer.header(), layer.upper_layer())
+    }
+
+    fn upper(self) -> Option<Self> {
+        self.upper.map(Self::of)
+    }
+
+    fn field(self, name: &str) -> Option<Value> {
+        self.kind
+            .fields()
+            .iter()
+            .find(|field| field.name == name)?
+            .get(self.header)
+    }
+
+    fn int(self, name: &str) -> u64 {
+        match self.field(name) {
+            Some(Value::Int(value)) => value,
+            _ => 0,
+        }
+    }
+
+    fn addr(self, name: &str) -> Option<IpAddr> {
+        match self.field(name)? {
+            Value::Ipv4(addr) => Some(IpAddr::V4(addr)),
+            Value::Ipv6(addr) => Some(IpAddr::V6(addr)),
+            _ => None,
+        }
+    }
+
+    /// Returns the first upper layer which is no IPv6 extension header
+    fn transport(self) -> Option<Self> {
+        let upper = self.upper()?;
+        match upper.kind {
+            Layers::HopByHop
+            | Layers::Routing
+            | Layers::Fragment
+            | Layers::DestOpts
+            | Layers::Ah => upper.transport(),
+            _ => Some(upper),
+        }
+    }
+
+    /// Returns the ports of a transport header, also of one which is kept as `Payload` because
+    /// it was truncated by the quote of an ICMP error
+    fn ports(self) -> Option<[u64; 2]> {
+        match self.kind {
+            Layers::Udp | Layers::Tcp => Some([self.int("sport"), self.int("dport")]),
+            Layers::Payload => match *self.header.get(..4)? {
+                [s0, s1, d0, d1] => Some([
+                    u16::from_be_bytes([s0, s1]).into(),
+                    u16::from_be_bytes([d0, d1]).into(),
+                ]),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Returns the datagram quoted by an ICMP or `ICMPv6` error message of the IP layer
+    fn quoted_datagram(self) -> Option<Cow<'a, LayerMut>> {
+        let icmp = self.transport()?;
+        match (icmp.kind, icmp.int("type")) {
+            (Layers::Icmp, 3 | 4 | 5 | 11 | 12) => icmp.upper.map(Cow::Borrowed),
+            (Layers::Icmpv6, 1..=4) => {
+                LayerMut::from_buf(Layers::Ipv6, icmp.header.get(8..)?.to_vec())
+                    .ok()
+                    .map(Cow::Owned)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Returns true if a response from any address is expected
+fn is_group(addr: Option<IpAddr>) -> bool {
+    match addr {
+        Some(IpAddr::V4(addr)) => {
+            addr.is_multicast() || addr.is_broadcast() || addr == Ipv4Addr::UNSPECIFIED
+        }
+        Some(IpAddr::V6(addr)) => addr.is_multicast() || addr == Ipv6Addr::UNSPECIFIED,
+        None => true,
+    }
+}
+
+fn upper_answers(response: Option<View>, request: Option<View>) -> bool {
+    match (response, request) {
+        (_, None) => true,
+        (Some(response), Some(request)) => answers(response, request),
+        (None, Some(_)) => false,
+    }
+}
+
+/// Returns true if the quoted datagram of an ICMP error is the request
+fn quotes(quoted: View, request: View) -> bool {
+    let same = |name| quoted.field(name) == request.field(name);
+    if quoted.kind != request.kind || !same("src") || !same("dst") {
+        return false;
+    }
+    // compare the ports of the quoted transport header, which may be too short to be parsed
+    match (quoted.transport(), request.transport()) {
+        (Some(quoted), Some(request)) if quoted.kind == request.kind => {
+            !matches!(quoted.kind, Layers::Udp | Layers::Tcp) || quoted.ports() == request.ports()
+        }
+        (Some(quoted), Some(request))
+            if quoted.kind == Layers::Payload
+                && matches!(request.kind, Layers::Udp | Layers::Tcp) =>
+        {
+            quoted.ports() == request.ports()
+        }
+        (Some(_), Some(_)) => false,
+        _ => true,
+    }
+}
+
+fn tcp_answers(response: View, request: View) -> bool {
+    const FIN: u64 = 0x01;
+    const SYN: u64 = 0x02;
+    const RST: u64 = 0x04;
+    const ACK: u64 = 0x10;
+    if response.int("sport") != request.int("dport")
+        || response.int("dport") != request.int("sport")
+    {
+        return false;
+    }
+    let flags = response.int("flags");
+    if flags & ACK == 0 {
+        return flags & RST != 0;
+    }
+    let len = request.upper.map_or(0, |payload| payload.header().len()) as u64
+        + u64::from(request.int("flags") & (SYN | FIN) != 0);
+    let acknowledged = response.int("ack").wrapping_sub(request.int("seq")) & 0xffff_ffff;
+    acknowledged <= len
+}
+
+fn icmp_answers(response: View, request: View) -> bool {
+    let same = |name| response.int(name) == request.int(name);
+    matches!(
+        (response.int("type"), request.int("type")),
+        (0, 8) | (14, 13) | (16, 15) | (18, 17)
+    ) && same("id")
+        && same("seq")
+}
+
+fn icmpv6_answers(response: View, request: View) -> bool {
+    let same = |range: std::ops::Range<usize>| {
+        response.header.get(range.clone()) == request.header.get(range)
+    };
+    match (response.int("type"), request.int("type")) {
+        // echo reply with the same identifier and sequence number
+        (129, 128) => same(4..8),
+        // router advertisement
+        (134, 133) => true,
+        // neighbor advertisement for the target
+        (136, 135) => same(8..24),
+        _ => false,
+    }
+}
+
+/// Returns true if the `response` answers the `request`
+pub(crate) fn answers(response: View, request: View) -> bool {
+    match (response.kind, request.kind) {
+        (Layers::Ether, Layers::Ether) | (Layers::Vlan, Layers::Vlan) => {
+            upper_answers(response.upper(), request.upper())
+        }
+        (Layers::Ipv4, Layers::Ipv4) | (Layers::Ipv6, Layers::Ipv6) => {
+            let src = request.addr("src");
+            if !is_group(src) && response.addr("dst") != src {
+                return false;
+            }
+            if let Some(quoted) = response.quoted_datagram() {
+                return quotes(View::of(&quoted), request);
+            }
+            let dst = request.addr("dst");
+            (is_group(dst) || response.addr("src") == dst)
+                && upper_answers(response.transport(), request.transport())
+        }
+        (Layers::Arp, Layers::Arp) => {
+            response.int("op") == 2
+                && request.int("op") == 1
+                && response.field("psrc") == request.field("pdst")
+        }
+        (Layers::Tcp, Layers::Tcp) => tcp_answers(response, request),
+        (Layers::Udp, Layers::Udp) => {
+            response.int("sport") == request.int("dport")
+                && response.int("dport") == request.int("sport")
+        }
+        (Layers::Icmp, Layers::Icmp) => icmp_answers(response, request),
+        (Layers::Icmpv6, Layers::Icmpv6) => icmpv6_answers(response, request),
+        (Layers::Payload, Layers::Payload) => true,
+        _ => false,
+    }
+}
+
+/// Hashes the values which are equal in a request and its response, e.g. the sorted ports.
+/// The IP addresses are left out, because a request to a group address is answered from any
+/// address, they are checked by `answers`.
+fn hash_into(layer: View, hasher: &mut DefaultHasher) {
+    match layer.kind {
+        Layers::Ether | Layers::Vlan => {
+            if let Some(upper) = layer.upper() {
+                hash_into(upper, hasher);
+            }
+        }
+        Layers::Ipv4 | Layers::Ipv6 => {
+            // an error message is hashed like the quoted request
+            if let Some(quoted) = layer.quoted_datagram() {
+                let quoted = View::of(&quoted);
+                match quoted.transport() {
+                    // a truncated transport header is hashed like the request by its ports
+                    Some(transport) if transport.kind == Layers::Payload => {
+                        hash_ports(transport.ports(), hasher);
+                    }
+                    _ => hash_into(quoted, hasher),
+                }
+                return;
+            }
+            if let Some(upper) = layer.transport() {
+                hash_into(upper, hasher);
+            }
+        }
+        Layers::Tcp | Layers::Udp => hash_ports(layer.ports(), hasher),
+        Layers::Icmp => [layer.int("id"), layer.int("seq")].hash(hasher),
+        Layers::Icmpv6 => match layer.int("type") {
+            128 | 129 => layer.header.get(4..8).hash(hasher),
+            135 | 136 => layer.header.get(8..24).hash(hasher),
+            _ => {}
+        },
+        Layers::Arp => match layer.int("op") {
+            1 => layer.addr("pdst").hash(hasher),
+            _ => layer.addr("psrc").hash(hasher),
+        },
+        _ => {}
+    }
+}
+
+/// Hashes the sorted ports, so a request and its response have the same hash
+fn hash_ports(ports: Option<[u64; 2]>, hasher: &mut DefaultHasher) {
+    if let Some(mut ports) = ports {
+        ports.sort_unstable();
+        ports.hash(hasher);
+    }
+}
+
+/// Returns the hash key of the layer, see `Answers::hashret`
+pub(crate) fn hashret(layer: View) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    hash_into(layer, &mut hasher);
+    hasher.finish()
+}
+
+impl Answers for LayerMut {
+    fn answers(&self, request: &Self) -> bool {
+        answers(View::of(self), View::of(request))
+    }
+
+    fn hashret(&self) -> u64 {
+        hashret(View::of(self))
+    }
+}
+
+/// Pairs the responses with the requests they answer. Each request is paired with its first
+/// response. Returns the indices of the request and the response, ordered by the responses.
+#[must_use]
+pub fn match_responses<T: Answers>(requests: &[T], responses: &[T]) -> Vec<(usize, usize)> {
+    let mut by_hash: HashMap<u64, Vec<usize>> = HashMap::new();
+    for (i, request) in requests.iter().enumerate() {
+        by_hash.entry(request.hashret()).or_default().push(i);
+    }
+    let mut pairs = vec![];
+    for (j, response) in responses.iter().enumerate() {
+        let Some(candidates) = by_hash.get_mut(&response.hashret()) else {
+            continue;
+        };
+        if let Some(pos) = candidates
+            .iter()
+            .position(|&i| response.answers(&requests[i]))
+        {
+            pairs.push((candidates.remove(pos), j));
+        }
+    }
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pcap::{LinkType, PcapFormat, PcapReader, PcapWriter};
+    use crate::{EtherMut, LayerMutable, TcpMut};
+    use std::time::Duration;
+
+    fn parse(input: &str) -> LayerMut {
+        LayerMut::parse(input).expect("could not parse")
+    }
+
+    #[test]
+    fn test_answers() {
+        let syn = parse(
+            "Ether / Ipv4(src: 10.0.0.1, dst: 10.0.0.2) / Tcp(sport: 1000, dport: 80, seq: 10, flags: S)",
+        );
+        let syn_ack = parse(
+            "Ether / Ipv4(src: 10.0.0.2, dst: 10.0.0.1) / Tcp(sport: 80, dport: 1000, ack: 11, flags: SA)",
+        );
+        let rst = parse(
+            "Ether / Ipv4(src: 10.0.0.2, dst: 10.0.0.1) / Tcp(sport: 80, dport: 1000, flags: R)",
+        );
+        let wrong_ack = parse(
+            "Ether / Ipv4(src: 10.0.0.2, dst: 10.0.0.1) / Tcp(sport: 80, dport: 1000, ack: 12, flags: SA)",
+        );
+        let wrong_src = parse(
+            "Ether / Ipv4(src: 10.0.0.3, dst: 10.0.0.1) / Tcp(sport: 80, dport: 1000, ack: 11, flags: SA)",
+        );
+        assert!(syn_ack.answers(&syn));
+        assert!(rst.answers(&syn));
+        assert!(!wrong_ack.answers(&syn));
+        assert!(!wrong_src.answers(&syn));
+        assert!(!syn.answers(&syn));
+
+        let echo = parse("Ipv6(src: fe80::1, dst: ff02::1) / Icmpv6(type: 128, body: 0x00010002)");
+        let reply = parse("Ipv6(src: fe80::2, dst: fe80::1) / Icmpv6(type: 129, body: 0x00010002)");
+        assert!(reply.answers(&echo));
+
+        let who_has = parse("Ether / Arp(op: 1, pdst: 10.0.0.2)");
+        let is_at = parse("Ether / Arp(op: 2, psrc: 10.0.0.2)");
+        assert!(is_at.answers(&who_has));
+        assert!(!who_has.answers(&who_has));
+
+        // port unreachable quoting the probe
+        let probe = parse("Ether / Ipv4(src: 10.0.0.1, dst: 10.0.0.2) / Udp(sport: 5, dport: 53)");
+        let bytes = probe.clone().build().expect("could not build");
+        let unreachable = parse(&format!(
+            "Ether / Ipv4(src: 10.0.0.9, dst: 10.0.0.1) / Icmp(type: 3, code: 3) / Payload(load: 0x{})",
+            hex::encode(&bytes[14..])
+        ))
+        .build()
+        .and_then(EtherMut::from_buf)
+        .map(LayerMut::Ether)
+        .expect("could not parse");
+        assert!(unreachable.answers(&probe));
+        let other = parse("Ether / Ipv4(src: 10.0.0.1, dst: 10.0.0.2) / Udp(sport: 6, dport: 53)");
+        assert!(!unreachable.answers(&other));
+
+        // the response must be sent to the source of the request
+        let misdirected = parse(
+            "Ether / Ipv4(src: 10.0.0.2, dst: 10.0.0.3) / Tcp(sport: 80, dport: 1000, ack: 11, flags: SA)",
+        );
+        assert!(!misdirected.answers(&syn));
+    }
+
+    #[test]
+    fn test_answers_truncated_quote() {
+        // the error quotes only the first 8 bytes of the TCP header
+        let probe = parse(
+            "Ether / Ipv4(src: 10.0.0.1, dst: 10.0.0.2) / Tcp(sport: 1000, dport: 80, flags: S)",
+        );
+        let bytes = probe.clone().build().expect("could not build");
+        let unreachable = parse(&format!(
+            "Ether / Ipv4(src: 10.0.0.9, dst: 10.0.0.1) / Icmp(type: 3, code: 3) / Payload(load: 0x{})",
+            hex::encode(&bytes[14..14 + 28])
+        ))
+        .build()
+        .and_then(EtherMut::from_buf)
+        .map(LayerMut::Ether)
+        .expect("could not parse");
+        assert!(unreachable.answers(&probe));
+        assert_eq!(unreachable.hashret(), probe.hashret());
+        let other = parse(
+            "Ether / Ipv4(src: 10.0.0.1, dst: 10.0.0.2) / Tcp(sport: 1001, dport: 80, flags: S)",
+        );
+        assert!(!unreachable.answers(&other));
+        assert_ne!(unreachable.hashret(), other.hashret());
+    }
+
+    #[test]
+    fn test_hashret() {
+        let syn =
+            parse("Ipv4(src: 10.0.0.1, dst: 10.0.0.2) / Tcp(sport: 1000, dport: 80, flags: S)");
+        let syn_ack = parse(
+            "Ipv4(src: 10.0.0.2, dst: 10.0.0.1) / Tcp(sport: 80, dport: 1000, ack: 1, flags: SA)",
+        );
+        let other =
+            parse("Ipv4(src: 10.0.0.2, dst: 10.0.0.1) / Tcp(sport: 80, dport: 1001, flags: SA)");
+        assert_eq!(syn.hashret(), syn_ack.hashret());
+        assert_ne!(syn.hashret(), other.hashret());
+
+        // the layer types implement the relation as well
+        let (Some(LayerMut::Tcp(syn)), Some(LayerMut::Tcp(syn_ack))) =
+            (syn.upper_layer(), syn_ack.upper_layer())
+        else {
+            panic!("no tcp layer");
+        };
+        assert!(syn_ack.answers(syn));
+        assert!(!syn.answers(syn_ack));
+        assert_eq!(syn.hashret(), syn_ack.hashret());
+        assert!(!TcpMut::new().answers(&TcpMut::new()));
+    }
+
+    #[test]
+    fn test_match_responses() {
+        let requests: Vec<LayerMut> = (1..=100)
+            .map(|seq| {
+                parse(&format!(
+                    "Ether / Ipv4(src: 10.0.0.1, dst: 10.0.0.2) / Icmp(type: 8, id: 7, seq: {seq})"
+                ))
+            })
+            .collect();
+        // a capture of the responses in reverse order with unrelated traffic in between
+        let mut writer =
+            PcapWriter::new(vec![], PcapFormat::PcapNg, LinkType::Ethernet).expect("pcap");
+        for seq in (1..=100).rev().filter(|seq| seq % 10 != 0) {
+            let reply = parse(&format!(
+                "Ether / Ipv4(src: 10.0.0.2, dst: 10.0.0.1) / Icmp(type: 0, id: 7, seq: {seq})"
+            ));
+            writer
+                .write_layer(Duration::ZERO, reply)
+                .expect("could not write");
+            writer
+                .write_layer(Duration::ZERO, parse("Ether / Ipv4 / Udp"))
+                .expect("could not write");
+        }
+        let file = writer.into_inner().expect("could not write");
+        let responses: Vec<LayerMut> = PcapReader::new(&file[..])
+            .expect("could not read")
+            .map(|record| record.and_then(|record| record.layer_mut()))
+            .collect::<crate::Result<_>>()
+            .expect("could not read");
+
+        let pairs = match_responses(&requests, &responses);
+        assert_eq!(pairs.len(), 90);
+        let seq = |layer: &LayerMut| {
+            layer
+                .upper_layer()
+                .and_then(LayerMut::upper_layer)
+                .and_then(|icmp| icmp.get_field("seq"))
+        };
+        for (request, response) in pairs {
+            assert_eq!(seq(&requests[request]), seq(&responses[response]));
+        }
+        // each request is only paired once
+        let twice = [responses[0].clone(), responses[0].clone()];
+        assert_eq!(match_responses(&requests, &twice).len(), 1);
+
+        // requests to a group address are answered from the address of the responder
+        let requests = [
+            parse("Ipv6(src: fe80::1, dst: ff02::1) / Icmpv6(type: 128, body: 0x00010002)"),
+            parse("Ipv6(src: fe80::1, dst: ff02::2) / Icmpv6(type: 133)"),
+            parse("Ipv4(src: 10.0.0.1, dst: 255.255.255.255) / Icmp(type: 8, id: 1, seq: 1)"),
+        ];
+        let responses = [
+            parse("Ipv4(src: 10.0.0.7, dst: 10.0.0.1) / Icmp(type: 0, id: 1, seq: 1)"),
+            parse("Ipv6(src: fe80::2, dst: fe80::1) / Icmpv6(type: 134)"),
+            parse("Ipv6(src: fe80::2, dst: fe80::1) / Icmpv6(type: 129, body: 0x00010002)"),
+        ];
+        assert_eq!(
+            match_responses(&requests, &responses),
+            [(2, 0), (1, 1), (0, 2)]
+        );
+    }
+}
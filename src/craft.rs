@@ -1,102 +1,310 @@
-use crate::{Ipv4Mut, LayerMut, LayerMutable, VlanMut};
-use pnet::packet::vlan::ClassOfService;
-use std::collections::HashMap;
+use crate::overrides;
+use crate::registry;
+use crate::{CustomMut, Error, Field, FieldKind, LayerMut, Layers, Result, Value};
 
 impl LayerMut {
-    /// Parses the packet from a string
+    /// Parses the packet from a string.
+    ///
+    /// Layers are separated by `/` and written as `Name(field: value, ...)`, the brackets can
+    /// be omitted if no field is set. The layer names and fields are listed by
+    /// `Layers::fields`. Values are written like they are shown, flags can be given by their
+    /// names e.g. `flags: SA` and bytes either as hex `0x0102` or as quoted string with the
+    /// escapes `\"`, `\\`, `\n`, `\r`, `\t` and `\xNN`. A quoted string as layer is a `Payload`.
     /// ```rs
-    /// let pkt = LayerMut::parse("Vlan(id: 10) / IPv4(src: 10.10.10.10, dst: 1.1.1.1)");
+    /// let pkt = LayerMut::parse(r#"Ether / IPv4(src: 10.10.10.10, dst: 1.1.1.1) / Tcp(dport: 80, flags: S) / "GET /""#);
     /// ```
-    #[must_use]
-    pub fn parse(input: &str) -> Option<LayerMut> {
-        // Create a mutable vector to hold the layers
+    ///
+    /// # Errors
+    /// Returns `Error::Parse` with the column of the offending token if the string is not a
+    /// valid packet
+    pub fn parse(input: &str) -> Result<LayerMut> {
+        let mut parser = Parser {
+            input: input.chars().collect(),
+            pos: 0,
+        };
         let mut packet: Option<LayerMut> = None;
-
-        let layers = input.split('/');
-
-        for layer in layers {
-            // Match the Vlan layer
-            if let Some(pkt) = parse_vlan(layer) {
-                let pkt = LayerMut::Vlan(pkt);
-                if let Some(packet) = &mut packet {
-                    packet.add(pkt);
-                } else {
-                    packet = Some(pkt);
-                }
+        loop {
+            parser.skip_whitespace();
+            let column = parser.column();
+            let layer = parser.layer()?;
+            if let Some(packet) = &mut packet {
+                packet.add(layer).map_err(|err| Error::Parse {
+                    column,
+                    message: err.to_string(),
+                })?;
+            } else {
+                packet = Some(layer);
             }
-            // Match the IPv4 layer
-            else if let Some(pkt) = parse_ipv4(layer) {
-                let pkt = LayerMut::Ipv4(pkt);
-                if let Some(packet) = &mut packet {
-                    packet.add(pkt);
-                } else {
-                    packet = Some(pkt);
-                }
+            parser.skip_whitespace();
+            match parser.next() {
+                Some('/') => {}
+                None => break,
+                Some(c) => return Err(parser.error(&format!("expected `/` but found `{c}`"))),
             }
         }
-
-        packet
+        packet.ok_or(Error::Parse {
+            column: 1,
+            message: "no layer defined".to_string(),
+        })
     }
 }
 
-pub fn parse_options(options: &str) -> HashMap<&str, &str> {
-    let parts: Vec<&str> = options.split(',').collect();
-    let mut ordered_parts = HashMap::new();
-    for part in parts {
-        if let Some((key, value)) = part.split_once(':') {
-            ordered_parts.insert(key.trim(), value.trim());
+impl LayerMut {
+    /// Returns the complete textual form of the layer and all upper layers which is accepted
+    /// by `LayerMut::parse`. Every field of each layer is written in the order of
+    /// `Layers::fields`, so two packets can be compared by their text. Lengths and checksums
+    /// are left out because `build` calculates them anyway, so the text describes the packet
+    /// built from it even if the parsed packet had a wrong checksum. Only the datagram quoted
+    /// by an ICMP error keeps them, because it is built as it was quoted.
+    /// ```rs
+    /// // Ether(dst: ff:ff:ff:ff:ff:ff, src: 00:00:00:00:00:00, type: 2048) / Ipv4(version: 4, ...
+    /// let text = packet.canonical();
+    /// assert_eq!(LayerMut::parse(&text)?.canonical(), text);
+    /// ```
+    #[must_use]
+    pub fn canonical(&self) -> String {
+        let mut layers = vec![];
+        let mut trailers = vec![];
+        // a datagram quoted by an ICMP error is built as it is, so its checks are kept
+        let mut quoted = false;
+        let mut layer = Some(self);
+        while let Some(current) = layer {
+            layers.push(canonical_layer(
+                current.kind(),
+                current.name(),
+                current.fields(),
+                current.header(),
+                quoted,
+            ));
+            // the trailers are written from the outermost layer, see `LayerMut::add`
+            if let Some(trailer) = current.trailer() {
+                trailers.push(canonical_layer(
+                    Layers::Trailer,
+                    Layers::Trailer.name(),
+                    Layers::Trailer.fields(),
+                    &trailer.buf,
+                    false,
+                ));
+            }
+            quoted |= matches!(
+                (current, current.upper_layer()),
+                (LayerMut::Icmp(_), Some(LayerMut::Ipv4(_)))
+            );
+            layer = current.upper_layer();
         }
+        layers.append(&mut trailers);
+        layers.join(" / ")
     }
-    ordered_parts
 }
 
-// Helper function to parse Vlan layer
-pub fn parse_vlan(layer: &str) -> Option<VlanMut> {
-    if let Some(options) = layer
-        .strip_prefix("Vlan(")
-        .and_then(|s| s.strip_suffix(")"))
-    {
-        let parts = parse_options(options);
-        let mut vlan = VlanMut::new();
-        if let Some(mut vlan) = vlan.modify() {
-            if let Some(value) = parts.get("id")
-                && let Ok(parsed) = value.parse()
-            {
-                println!("parsed vlan id");
-                vlan.set_vlan_identifier(parsed);
-            }
-            if let Some(value) = parts.get("prio")
-                && let Ok(parsed) = value.parse()
-            {
-                vlan.set_priority_code_point(ClassOfService::new(parsed));
-            }
-        }
-        return Some(vlan);
+/// Returns the layer with all fields of the header, the ones calculated by `build` only if
+/// `checks` is set
+fn canonical_layer(
+    kind: Layers,
+    name: &str,
+    fields: &[Field],
+    header: &[u8],
+    checks: bool,
+) -> String {
+    let fields: Vec<String> = fields
+        .iter()
+        .filter(|field| checks || !overrides::is_check(kind, field.name))
+        .filter_map(|field| {
+            let value = field.get(header)?;
+            Some(format!("{}: {}", field.name, field.format(&value)))
+        })
+        .collect();
+    format!("{name}({})", fields.join(", "))
+}
+
+fn error_at(column: usize, message: &str) -> Error {
+    Error::Parse {
+        column,
+        message: message.to_string(),
     }
-    None
 }
 
-// Helper function to parse IPv4 layer
-pub fn parse_ipv4(layer: &str) -> Option<Ipv4Mut> {
-    if let Some(options) = layer
-        .strip_prefix("IPv4(")
-        .and_then(|s| s.strip_suffix(")"))
-    {
-        let parts = parse_options(options);
-        let mut vlan = Ipv4Mut::new();
-        if let Some(mut vlan) = vlan.modify() {
-            if let Some(value) = parts.get("src")
-                && let Ok(parsed) = value.parse()
-            {
-                vlan.set_source(parsed);
+/// Value of a field as written in the string
+enum Token {
+    /// Quoted string with the escapes resolved
+    Quoted(Vec<u8>),
+    /// Unquoted text
+    Text(String),
+}
+
+struct Parser {
+    input: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    /// Column of the current position starting at 1
+    fn column(&self) -> usize {
+        self.pos + 1
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        Some(c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    fn error(&self, message: &str) -> Error {
+        error_at(self.column(), message)
+    }
+
+    fn ident(&mut self) -> String {
+        let start = self.pos;
+        while self
+            .peek()
+            .is_some_and(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            self.pos += 1;
+        }
+        self.input[start..self.pos].iter().collect()
+    }
+
+    fn layer(&mut self) -> Result<LayerMut> {
+        let column = self.column();
+        if self.peek() == Some('"') {
+            let mut payload = LayerMut::new(Layers::Payload);
+            let load = self.quoted()?;
+            payload
+                .set_field("load", &Value::Bytes(load))
+                .map_err(|err| error_at(column, &err.to_string()))?;
+            return Ok(payload);
+        }
+
+        let name = self.ident();
+        if name.is_empty() {
+            return Err(match self.peek() {
+                Some(c) => self.error(&format!("expected a layer but found `{c}`")),
+                None => self.error("expected a layer but reached the end"),
+            });
+        }
+        let mut layer = Layers::from_name(&name)
+            .map(LayerMut::new)
+            .or_else(|| {
+                registry::by_name(&name).map(|p| LayerMut::Custom(CustomMut::with_protocol(p)))
+            })
+            .ok_or_else(|| error_at(column, &format!("unknown layer `{name}`")))?;
+
+        self.skip_whitespace();
+        if self.peek() != Some('(') {
+            return Ok(layer);
+        }
+        self.pos += 1;
+        self.skip_whitespace();
+        if self.peek() == Some(')') {
+            self.pos += 1;
+            return Ok(layer);
+        }
+        loop {
+            self.assignment(&mut layer)?;
+            self.skip_whitespace();
+            match self.next() {
+                Some(',') => {}
+                Some(')') => return Ok(layer),
+                Some(c) => {
+                    self.pos -= 1;
+                    return Err(self.error(&format!("expected `,` or `)` but found `{c}`")));
+                }
+                None => return Err(self.error("expected `)` but reached the end")),
             }
-            if let Some(value) = parts.get("dst")
-                && let Ok(parsed) = value.parse()
-            {
-                vlan.set_destination(parsed);
+        }
+    }
+
+    /// Parses `field: value` and sets the field of the layer
+    fn assignment(&mut self, layer: &mut LayerMut) -> Result<()> {
+        self.skip_whitespace();
+        let column = self.column();
+        let name = self.ident();
+        if name.is_empty() {
+            return Err(self.error("expected a field name"));
+        }
+        let field = layer
+            .fields()
+            .iter()
+            .find(|field| field.name == name)
+            .ok_or_else(|| error_at(column, &format!("{} has no field `{name}`", layer.name())))?;
+
+        self.skip_whitespace();
+        if !matches!(self.peek(), Some(':' | '=')) {
+            return Err(self.error("expected `:` or `=` after the field name"));
+        }
+        self.pos += 1;
+        self.skip_whitespace();
+        let column = self.column();
+        match (self.value()?, field.kind) {
+            (Token::Quoted(bytes), FieldKind::Bytes) => Ok(Value::Bytes(bytes)),
+            (Token::Quoted(bytes), _) => field.parse(&String::from_utf8_lossy(&bytes)),
+            (Token::Text(text), _) => field.parse(&text),
+        }
+        .and_then(|value| layer.set_field(field.name, &value))
+        .map_err(|err| error_at(column, &err.to_string()))
+    }
+
+    /// Parses a quoted string or the text until the next `,` or `)`
+    fn value(&mut self) -> Result<Token> {
+        if self.peek() == Some('"') {
+            return Ok(Token::Quoted(self.quoted()?));
+        }
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c != ',' && c != ')') {
+            self.pos += 1;
+        }
+        let text: String = self.input[start..self.pos].iter().collect();
+        let text = text.trim_end();
+        if text.is_empty() {
+            return Err(error_at(start + 1, "expected a value"));
+        }
+        Ok(Token::Text(text.to_string()))
+    }
+
+    fn quoted(&mut self) -> Result<Vec<u8>> {
+        let column = self.column();
+        self.pos += 1;
+        let mut bytes = vec![];
+        loop {
+            let c = self
+                .next()
+                .ok_or_else(|| error_at(column, "unterminated string"))?;
+            match c {
+                '"' => return Ok(bytes),
+                '\\' => {
+                    let escape = self.column();
+                    let byte = match self.next() {
+                        Some('"') => b'"',
+                        Some('\\') => b'\\',
+                        Some('n') => b'\n',
+                        Some('r') => b'\r',
+                        Some('t') => b'\t',
+                        Some('x') => {
+                            let hex: String = self.input.iter().skip(self.pos).take(2).collect();
+                            self.pos += 2;
+                            u8::from_str_radix(&hex, 16).map_err(|_| {
+                                error_at(escape, &format!("invalid escape `\\x{hex}`"))
+                            })?
+                        }
+                        _ => return Err(error_at(escape, "invalid escape")),
+                    };
+                    bytes.push(byte);
+                }
+                c => {
+                    let mut buf = [0; 4];
+                    bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                }
             }
         }
-        return Some(vlan);
     }
-    None
 }
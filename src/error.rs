@@ -0,0 +1,121 @@
+use crate::Layers;
+use std::fmt::Display;
+
+/// Result type used by all fallible functions of `pnet_layers`
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Errors returned when parsing, crafting or building packets
+#[derive(Debug)]
+pub enum Error {
+    /// The buffer is too short to contain the layer
+    Truncated {
+        /// The layer which could not be parsed
+        layer: Layers,
+        /// Offset of the layer from the start of the parsed buffer
+        offset: usize,
+    },
+    /// The layer announces a next protocol which is not supported
+    UnsupportedNextProtocol {
+        /// The layer announcing the next protocol
+        layer: Layers,
+        /// The protocol number e.g. the `EtherType` or the IP protocol number
+        protocol: u16,
+    },
+    /// The layer is not allowed on top of the lower layer
+    IllegalStacking {
+        /// The layer which should be stacked
+        layer: Layers,
+        /// The layer the `layer` should be stacked on
+        lower: Layers,
+    },
+    /// The packet has no layer of the requested type
+    MissingLayer {
+        /// The requested layer
+        layer: Layers,
+    },
+    /// The length of the layer can not be represented by its length field
+    LengthOverflow {
+        /// The layer with the length field
+        layer: Layers,
+        /// The length which does not fit into the length field
+        length: usize,
+    },
+    /// The value can not be written into the field
+    InvalidValue {
+        /// Name of the field
+        field: &'static str,
+        /// Description of the problem
+        message: String,
+    },
+    /// Reading or writing a file failed
+    Io(std::io::Error),
+    /// The capture file is malformed
+    Capture {
+        /// Offset of the malformed block or record from the start of the file
+        offset: u64,
+        /// Description of the problem
+        message: String,
+    },
+    /// The provided string could not be parsed into a packet
+    Parse {
+        /// Column (starting at 1) of the offending token
+        column: usize,
+        /// Description of the problem
+        message: String,
+    },
+}
+
+impl Error {
+    /// Moves the offset of a truncated layer by `offset` bytes. This is used by the lower
+    /// layers to make the offset relative to the start of their own buffer.
+    pub(crate) fn offset_by(self, offset: usize) -> Self {
+        match self {
+            Self::Truncated { layer, offset: at } => Self::Truncated {
+                layer,
+                offset: at + offset,
+            },
+            other => other,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Truncated { layer, offset } => {
+                write!(f, "buffer too short for {layer:?} at offset {offset}")
+            }
+            Self::UnsupportedNextProtocol { layer, protocol } => {
+                write!(f, "{layer:?} next protocol {protocol:#06x} not supported")
+            }
+            Self::IllegalStacking { layer, lower } => {
+                write!(f, "{layer:?} can not be stacked on {lower:?}")
+            }
+            Self::MissingLayer { layer } => write!(f, "no {layer:?} layer found"),
+            Self::LengthOverflow { layer, length } => {
+                write!(f, "{layer:?} length {length} exceeds length field")
+            }
+            Self::InvalidValue { field, message } => write!(f, "field `{field}`: {message}"),
+            Self::Io(err) => write!(f, "io error: {err}"),
+            Self::Capture { offset, message } => {
+                write!(f, "invalid capture at offset {offset}: {message}")
+            }
+            Self::Parse { column, message } => write!(f, "column {column}: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
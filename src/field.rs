@@ -0,0 +1,249 @@
+use crate::{Error, Result};
+use pnet::util::MacAddr;
+use std::fmt::Write;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+/// Type of a header field which defines how the bits are interpreted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldKind {
+    /// Unsigned big endian integer with the given number of bits
+    Int(usize),
+    /// Flags, each character names one bit starting with the least significant bit
+    Flags(&'static str),
+    /// Mac address
+    Mac,
+    /// Ipv4 address
+    Ipv4,
+    /// Ipv6 address
+    Ipv6,
+    /// Bytes from the offset until the end of the header e.g. options
+    Bytes,
+}
+
+/// Value of a header field
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    /// Value of an `Int` or `Flags` field
+    Int(u64),
+    /// Value of a `Mac` field
+    Mac(MacAddr),
+    /// Value of an `Ipv4` field
+    Ipv4(Ipv4Addr),
+    /// Value of an `Ipv6` field
+    Ipv6(Ipv6Addr),
+    /// Value of a `Bytes` field
+    Bytes(Vec<u8>),
+}
+
+/// Describes a field of a layer header so it can be read and written by its name
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Field {
+    /// Name of the field e.g. `ttl`
+    pub name: &'static str,
+    /// How the field is interpreted
+    pub kind: FieldKind,
+    /// Offset of the field from the start of the header in bits
+    pub offset: usize,
+}
+
+fn read_bits(buf: &[u8], offset: usize, bits: usize) -> Option<u64> {
+    if offset + bits > buf.len() * 8 {
+        return None;
+    }
+    Some((offset..offset + bits).fold(0, |value, i| {
+        (value << 1) | u64::from((buf[i / 8] >> (7 - i % 8)) & 1)
+    }))
+}
+
+fn write_bits(buf: &mut [u8], offset: usize, bits: usize, value: u64) {
+    for (n, i) in (offset..offset + bits).enumerate() {
+        let mask = 1 << (7 - i % 8);
+        if (value >> (bits - n - 1)) & 1 == 1 {
+            buf[i / 8] |= mask;
+        } else {
+            buf[i / 8] &= !mask;
+        }
+    }
+}
+
+fn parse_int(text: &str) -> Option<u64> {
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+impl Field {
+    /// Creates an integer field
+    #[must_use]
+    pub const fn int(name: &'static str, offset: usize, bits: usize) -> Self {
+        Self {
+            name,
+            kind: FieldKind::Int(bits),
+            offset,
+        }
+    }
+
+    /// Creates a field of the given kind
+    #[must_use]
+    pub const fn new(name: &'static str, offset: usize, kind: FieldKind) -> Self {
+        Self { name, kind, offset }
+    }
+
+    /// Returns the number of bits of the field or `None` for `Bytes`
+    #[must_use]
+    pub fn bits(&self) -> Option<usize> {
+        match self.kind {
+            FieldKind::Int(bits) => Some(bits),
+            FieldKind::Flags(names) => Some(names.len()),
+            FieldKind::Mac => Some(48),
+            FieldKind::Ipv4 => Some(32),
+            FieldKind::Ipv6 => Some(128),
+            FieldKind::Bytes => None,
+        }
+    }
+
+    /// Reads the field from the header, returns `None` if the header is too short
+    #[must_use]
+    pub fn get(&self, buf: &[u8]) -> Option<Value> {
+        let start = self.offset / 8;
+        Some(match self.kind {
+            FieldKind::Int(bits) => Value::Int(read_bits(buf, self.offset, bits)?),
+            FieldKind::Flags(names) => Value::Int(read_bits(buf, self.offset, names.len())?),
+            FieldKind::Mac => {
+                let b = buf.get(start..start + 6)?;
+                Value::Mac(MacAddr::new(b[0], b[1], b[2], b[3], b[4], b[5]))
+            }
+            FieldKind::Ipv4 => {
+                let b: [u8; 4] = buf.get(start..start + 4)?.try_into().ok()?;
+                Value::Ipv4(Ipv4Addr::from(b))
+            }
+            FieldKind::Ipv6 => {
+                let b: [u8; 16] = buf.get(start..start + 16)?.try_into().ok()?;
+                Value::Ipv6(Ipv6Addr::from(b))
+            }
+            FieldKind::Bytes => Value::Bytes(buf.get(start..)?.to_vec()),
+        })
+    }
+
+    /// Writes the value into the header. A `Bytes` field replaces everything from its offset
+    /// until the end of the header.
+    ///
+    /// # Errors
+    /// Returns `Error::InvalidValue` if the value does not match the field or the header is
+    /// too short
+    pub fn set(&self, buf: &mut Vec<u8>, value: &Value) -> Result<()> {
+        let start = self.offset / 8;
+        let too_short = || self.invalid("header too short");
+        match (self.kind, value) {
+            (FieldKind::Int(_) | FieldKind::Flags(_), Value::Int(value)) => {
+                let bits = self.bits().unwrap_or_default();
+                if bits < 64 && *value >> bits != 0 {
+                    return Err(self.invalid(&format!("{value} does not fit into {bits} bits")));
+                }
+                if self.offset + bits > buf.len() * 8 {
+                    return Err(too_short());
+                }
+                write_bits(buf, self.offset, bits, *value);
+            }
+            (FieldKind::Mac, Value::Mac(mac)) => buf
+                .get_mut(start..start + 6)
+                .ok_or_else(too_short)?
+                .copy_from_slice(&mac.octets()),
+            (FieldKind::Ipv4, Value::Ipv4(addr)) => buf
+                .get_mut(start..start + 4)
+                .ok_or_else(too_short)?
+                .copy_from_slice(&addr.octets()),
+            (FieldKind::Ipv6, Value::Ipv6(addr)) => buf
+                .get_mut(start..start + 16)
+                .ok_or_else(too_short)?
+                .copy_from_slice(&addr.octets()),
+            (FieldKind::Bytes, Value::Bytes(bytes)) => {
+                if buf.len() < start {
+                    return Err(too_short());
+                }
+                buf.truncate(start);
+                buf.extend_from_slice(bytes);
+            }
+            _ => return Err(self.invalid(&format!("{value:?} has the wrong type"))),
+        }
+        Ok(())
+    }
+
+    /// Parses the text representation of a value of this field.
+    /// Integers can be decimal or hexadecimal with a `0x` prefix, flags can also be given by
+    /// their names e.g. `SA` and bytes are given as hex with a `0x` prefix.
+    ///
+    /// # Errors
+    /// Returns `Error::InvalidValue` if the text is not a valid value for the field
+    pub fn parse(&self, text: &str) -> Result<Value> {
+        let invalid = || self.invalid(&format!("invalid value `{text}`"));
+        Ok(match self.kind {
+            FieldKind::Int(_) => Value::Int(parse_int(text).ok_or_else(invalid)?),
+            FieldKind::Flags(names) => match parse_int(text) {
+                Some(value) => Value::Int(value),
+                None => Value::Int(text.chars().try_fold(0, |value, c| {
+                    names
+                        .find(c)
+                        .map(|bit| value | (1 << bit))
+                        .ok_or_else(invalid)
+                })?),
+            },
+            FieldKind::Mac => Value::Mac(MacAddr::from_str(text).map_err(|_| invalid())?),
+            FieldKind::Ipv4 => Value::Ipv4(text.parse().map_err(|_| invalid())?),
+            FieldKind::Ipv6 => Value::Ipv6(text.parse().map_err(|_| invalid())?),
+            FieldKind::Bytes => {
+                let hex = text.strip_prefix("0x").ok_or_else(invalid)?;
+                Value::Bytes(hex::decode(hex).map_err(|_| invalid())?)
+            }
+        })
+    }
+
+    /// Returns the text representation of a value of this field which can be parsed again.
+    /// Flags are written by their names, bytes as quoted string if they are printable and
+    /// as hex otherwise.
+    #[must_use]
+    pub fn format(&self, value: &Value) -> String {
+        match (self.kind, value) {
+            (FieldKind::Flags(names), Value::Int(value)) if *value != 0 => names
+                .chars()
+                .enumerate()
+                .filter(|(bit, _)| (value >> bit) & 1 == 1)
+                .map(|(_, c)| c)
+                .collect(),
+            (_, Value::Int(value)) => value.to_string(),
+            (_, Value::Mac(mac)) => mac.to_string(),
+            (_, Value::Ipv4(addr)) => addr.to_string(),
+            (_, Value::Ipv6(addr)) => addr.to_string(),
+            (_, Value::Bytes(bytes)) => format_bytes(bytes),
+        }
+    }
+
+    fn invalid(&self, message: &str) -> Error {
+        Error::InvalidValue {
+            field: self.name,
+            message: message.to_string(),
+        }
+    }
+}
+
+/// Formats bytes as quoted string if all bytes are printable and as hex otherwise
+pub(crate) fn format_bytes(bytes: &[u8]) -> String {
+    if bytes.iter().all(|b| b.is_ascii_graphic() || *b == b' ') {
+        let mut text = String::from('"');
+        for c in bytes.iter().map(|b| char::from(*b)) {
+            if c == '"' || c == '\\' {
+                text.push('\\');
+            }
+            text.push(c);
+        }
+        text.push('"');
+        text
+    } else {
+        bytes.iter().fold(String::from("0x"), |mut text, b| {
+            let _ = write!(text, "{b:02x}");
+            text
+        })
+    }
+}
@@ -0,0 +1,333 @@
+//! Field-aware fuzzing of layer stacks.
+//!
+//! A `Fuzzer` randomizes the header fields of an `EtherMut` stack by their name, so no pnet
+//! setters are needed. Values respect the width of the fields, payloads are changed by
+//! byte-level mutations. The fuzzer is seeded, so a failing test case can be reproduced from
+//! its seed.
+//!
+//! ```
+//! use pnet_layers::fuzz::{Checks, Fuzzer};
+//! use pnet_layers::{LayerMut, Layers};
+//!
+//! let LayerMut::Ether(ether) = LayerMut::parse(r#"Ether / Ipv4 / Udp(dport: 53) / "query""#)? else {
+//!     unreachable!()
+//! };
+//! let mut fuzzer = Fuzzer::new(42);
+//! fuzzer.select(Layers::Ipv4, "ttl")?;
+//! fuzzer.select(Layers::Payload, "load")?;
+//! fuzzer.set_checks(Checks::Broken);
+//! for _ in 0..10 {
+//!     let _packet = fuzzer.fuzz(&ether)?;
+//! }
+//! # Ok::<(), pnet_layers::Error>(())
+//! ```
+
+use crate::overrides::{Overrides, is_check};
+use crate::{
+    Error, EtherMut, Field, FieldKind, LayerMut, LayerMutable, Layers, PayloadMut, Result, Value,
+};
+use pnet::util::MacAddr;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+/// How the length and checksum fields of a fuzzed packet are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Checks {
+    /// The values are calculated by `build`
+    #[default]
+    Valid,
+    /// The calculated values are replaced by wrong ones, see `Overrides::broken_checks`
+    Broken,
+}
+
+/// Returns the mask of an integer field with the given number of bits
+fn mask(bits: usize) -> u64 {
+    1_u64
+        .checked_shl(u32::try_from(bits).unwrap_or(u32::MAX))
+        .unwrap_or(0)
+        .wrapping_sub(1)
+}
+
+/// Byte values which often trigger edge cases
+const INTERESTING_BYTES: [u8; 5] = [0x00, 0x01, 0x7f, 0x80, 0xff];
+
+/// Maximum number of byte-level mutations applied at once
+const MAX_MUTATIONS: usize = 4;
+
+/// Seeded fuzzer for layer stacks, see the module documentation
+#[derive(Debug, Clone)]
+pub struct Fuzzer {
+    rng: StdRng,
+    fields: Vec<(Layers, &'static str)>,
+    checks: Checks,
+}
+
+impl Fuzzer {
+    /// Creates a fuzzer which randomizes all fields except lengths and checksums until fields
+    /// are selected. Header fields holding raw bytes, e.g. options, are only mutated if they
+    /// are selected, payloads are always mutated.
+    #[must_use]
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+            fields: vec![],
+            checks: Checks::Valid,
+        }
+    }
+
+    /// Restricts the fuzzing to the selected fields. Every layer of the given type is fuzzed.
+    ///
+    /// # Errors
+    /// Returns `Error::InvalidValue` if the layer has no such field
+    pub fn select(&mut self, layer: Layers, field: &str) -> Result<()> {
+        let field = layer
+            .fields()
+            .iter()
+            .find(|f| f.name == field)
+            .ok_or_else(|| Error::InvalidValue {
+                field: "",
+                message: format!("{layer:?} has no field `{field}`"),
+            })?;
+        if !self.fields.contains(&(layer, field.name)) {
+            self.fields.push((layer, field.name));
+        }
+        Ok(())
+    }
+
+    /// Sets how the lengths and checksums of the packets returned by `fuzz` are written
+    pub fn set_checks(&mut self, checks: Checks) {
+        self.checks = checks;
+    }
+
+    fn is_selected(&self, layer: Layers, field: &Field) -> bool {
+        if self.fields.is_empty() {
+            !is_check(layer, field.name)
+                && (field.kind != FieldKind::Bytes || layer == Layers::Payload)
+        } else {
+            self.fields.contains(&(layer, field.name))
+        }
+    }
+
+    /// Returns a random value for the field, bytes are mutated starting from `current`
+    fn random_value(&mut self, field: &Field, current: Value) -> Value {
+        match (field.kind, current) {
+            (FieldKind::Mac, _) => {
+                let b: [u8; 6] = self.rng.random();
+                Value::Mac(MacAddr::new(b[0], b[1], b[2], b[3], b[4], b[5]))
+            }
+            (FieldKind::Ipv4, _) => Value::Ipv4(Ipv4Addr::from(self.rng.random::<u32>())),
+            (FieldKind::Ipv6, _) => Value::Ipv6(Ipv6Addr::from(self.rng.random::<u128>())),
+            (FieldKind::Bytes, Value::Bytes(mut bytes)) => {
+                self.mutate_bytes(&mut bytes);
+                Value::Bytes(bytes)
+            }
+            _ => Value::Int(self.rng.random::<u64>() & mask(field.bits().unwrap_or_default())),
+        }
+    }
+
+    /// Randomizes the selected fields of a single header. Fields beyond the end of the header
+    /// are skipped, so the header is not extended.
+    fn mutate_header(&mut self, layer: Layers, buf: &mut Vec<u8>) {
+        for field in layer.fields() {
+            if !self.is_selected(layer, field) {
+                continue;
+            }
+            let Some(current) = field.get(buf) else {
+                continue;
+            };
+            let value = self.random_value(field, current);
+            // the value matches the kind and width of the field
+            let _ = field.set(buf, &value);
+        }
+    }
+
+    /// Randomizes the selected fields of every layer of the stack in place
+    pub fn mutate(&mut self, ether: &mut EtherMut) {
+        self.mutate_header(Layers::Ether, &mut ether.buf);
+        let mut layer = ether.upper_layer.as_deref_mut();
+        while let Some(current) = layer {
+            self.mutate_header(current.kind(), current.header_mut());
+            layer = current.upper_layer_mut();
+        }
+    }
+
+    /// Fuzzes a copy of the stack and builds it. Values which `build` would choose randomly,
+    /// e.g. an IPv4 identification of 0, are drawn from the seed, so the packets are
+    /// reproducible.
+    ///
+    /// # Errors
+    /// Returns the error of `build`, e.g. if fuzzed options are too long
+    pub fn fuzz(&mut self, ether: &EtherMut) -> Result<Vec<u8>> {
+        let mut ether = ether.clone();
+        self.mutate(&mut ether);
+        let mut layer = ether.upper_layer.as_deref_mut();
+        while let Some(current) = layer {
+            if current.kind() == Layers::Ipv4 && current.get_field("id") == Some(Value::Int(0)) {
+                let id = self.rng.random_range(1..=u64::from(u16::MAX));
+                current.set_field("id", &Value::Int(id))?;
+            }
+            layer = current.upper_layer_mut();
+        }
+        match self.checks {
+            Checks::Valid => ether.build(),
+            Checks::Broken => LayerMut::Ether(ether).build_with(&Overrides::broken_checks()),
+        }
+    }
+
+    /// Applies one to four random byte-level mutations: flipping a bit, replacing a byte by
+    /// a random or an interesting value, inserting or removing a byte, duplicating a chunk or
+    /// truncating the bytes
+    pub fn mutate_bytes(&mut self, bytes: &mut Vec<u8>) {
+        for _ in 0..self.rng.random_range(1..=MAX_MUTATIONS) {
+            if bytes.is_empty() {
+                bytes.push(self.rng.random());
+                continue;
+            }
+            let pos = self.rng.random_range(0..bytes.len());
+            match self.rng.random_range(0..7) {
+                0 => bytes[pos] ^= 1 << self.rng.random_range(0..8),
+                1 => bytes[pos] = self.rng.random(),
+                2 => {
+                    bytes[pos] =
+                        INTERESTING_BYTES[self.rng.random_range(0..INTERESTING_BYTES.len())];
+                }
+                3 => bytes.insert(pos, self.rng.random()),
+                4 => {
+                    bytes.remove(pos);
+                }
+                5 => {
+                    let end = self.rng.random_range(pos..bytes.len()) + 1;
+                    let chunk = bytes[pos..end].to_vec();
+                    bytes.splice(end..end, chunk);
+                }
+                _ => bytes.truncate(pos),
+            }
+        }
+    }
+
+    /// Applies random byte-level mutations to the payload, see `mutate_bytes`
+    pub fn mutate_payload(&mut self, payload: &mut PayloadMut) {
+        self.mutate_bytes(&mut payload.buf);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pnet::packet::ip::IpNextHeaderProtocols::Udp;
+    use pnet::packet::util;
+
+    fn ether(input: &str) -> EtherMut {
+        match LayerMut::parse(input).expect("could not parse") {
+            LayerMut::Ether(ether) => ether,
+            _ => panic!("no ether layer"),
+        }
+    }
+
+    fn fuzz(seed: u64, ether: &EtherMut, checks: Checks) -> Vec<Vec<u8>> {
+        let mut fuzzer = Fuzzer::new(seed);
+        fuzzer.set_checks(checks);
+        (0..20)
+            .map(|_| fuzzer.fuzz(ether).expect("could not fuzz"))
+            .collect()
+    }
+
+    #[test]
+    fn test_fuzz_reproducible() {
+        let ether = ether(r#"Ether / Vlan / Ipv4 / Tcp(flags: S) / "GET / HTTP/1.1""#);
+        let packets = fuzz(7, &ether, Checks::Valid);
+        assert_eq!(packets, fuzz(7, &ether, Checks::Valid));
+        assert_ne!(packets, fuzz(8, &ether, Checks::Valid));
+        // the headers can be parsed again as the lengths and checksums are valid
+        for packet in packets {
+            let parsed = EtherMut::from_buf(packet).expect("could not parse");
+            assert!(matches!(
+                parsed.upper_layer.as_deref(),
+                Some(LayerMut::Vlan(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_fuzz_selected() {
+        let ether = ether(r#"Ether / Ipv4(id: 1) / Udp(dport: 53) / "query""#);
+        let mut fuzzer = Fuzzer::new(1);
+        assert!(fuzzer.select(Layers::Udp, "foo").is_err());
+        fuzzer
+            .select(Layers::Udp, "sport")
+            .expect("could not select");
+        let valid = ether.clone().build().expect("could not build");
+        let mut sports = vec![];
+        for _ in 0..20 {
+            let packet = fuzzer.fuzz(&ether).expect("could not fuzz");
+            // only the source port and the UDP checksum differ
+            assert_eq!(packet[..34], valid[..34]);
+            assert_eq!(packet[36..40], valid[36..40]);
+            assert_eq!(packet[42..], valid[42..]);
+            sports.push(u16::from_be_bytes([packet[34], packet[35]]));
+        }
+        sports.dedup();
+        assert!(sports.len() > 1);
+    }
+
+    #[test]
+    fn test_fuzz_broken_checks() {
+        let ether = ether(r#"Ether / Ipv4(id: 1) / Udp / "query""#);
+        let packets: Vec<Vec<u8>> = [Checks::Valid, Checks::Broken]
+            .into_iter()
+            .map(|checks| {
+                let mut fuzzer = Fuzzer::new(3);
+                fuzzer
+                    .select(Layers::Payload, "load")
+                    .expect("could not select");
+                fuzzer.set_checks(checks);
+                fuzzer.fuzz(&ether).expect("could not fuzz")
+            })
+            .collect();
+        // the packets are fuzzed the same way, only lengths and checksums differ
+        let (valid, broken) = (&packets[0], &packets[1]);
+        assert_eq!(valid.len(), broken.len());
+        for range in [14..15, 16..18, 38..40] {
+            assert_ne!(valid[range.clone()], broken[range]);
+        }
+        assert_eq!(valid[18..24], broken[18..24]);
+        assert_eq!(valid[26..38], broken[26..38]);
+        assert_eq!(valid[42..], broken[42..]);
+        // the checksums do not match the broken headers
+        let sum = |bytes: &[u8], at: usize| u16::from_be_bytes([bytes[at], bytes[at + 1]]);
+        assert_ne!(sum(broken, 24), util::checksum(&broken[14..34], 5));
+        let end = 14 + usize::from(sum(valid, 16));
+        let addr =
+            |at: usize| Ipv4Addr::new(broken[at], broken[at + 1], broken[at + 2], broken[at + 3]);
+        let (src, dst) = (addr(26), addr(30));
+        let udp = util::ipv4_checksum(&broken[34..end], 3, &[], &src, &dst, Udp);
+        assert_ne!(sum(broken, 40), udp);
+    }
+
+    #[test]
+    fn test_mutate_payload() {
+        let mutations = |seed| {
+            let mut fuzzer = Fuzzer::new(seed);
+            let mut payload = PayloadMut {
+                buf: b"payload".to_vec(),
+            };
+            (0..100)
+                .map(|_| {
+                    fuzzer.mutate_payload(&mut payload);
+                    payload.buf.clone()
+                })
+                .collect::<Vec<_>>()
+        };
+        let payloads = mutations(0);
+        assert_eq!(payloads, mutations(0));
+        assert!(payloads.windows(2).any(|w| w[0].len() < w[1].len()));
+        assert!(
+            payloads
+                .windows(2)
+                .any(|w| w[0].len() == w[1].len() && w[0] != w[1])
+        );
+        assert_eq!(mask(3), 0b111);
+        assert_eq!(mask(64), u64::MAX);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{ArpMut, EtherMut, LayerMut, LayerMutable, VlanMut, helper::tcp::TcpPacket};
+use crate::{ArpMut, EtherMut, LayerMut, LayerMutable, Result, VlanMut, helper::tcp::TcpPacket};
 use pnet::{
     packet::arp::{ArpHardwareTypes, ArpOperations},
     util::MacAddr,
@@ -27,8 +27,10 @@ impl ArpPacket {
         }
     }
     /// craft an Arp Replay packet
-    #[must_use]
-    pub fn reply(&self) -> Option<EtherMut> {
+    ///
+    /// # Errors
+    /// Returns an error if one of the layers could not be crafted
+    pub fn reply(&self) -> Result<EtherMut> {
         let mut ether = EtherMut::new();
         {
             let mut eth = ether.modify()?;
@@ -42,7 +44,7 @@ impl ArpPacket {
                 let mut pkt = vlan.modify()?;
                 pkt.set_vlan_identifier(vlan_id);
             }
-            ether.add(LayerMut::Vlan(vlan));
+            ether.add(LayerMut::Vlan(vlan))?;
         }
 
         let mut arp = ArpMut::new();
@@ -58,7 +60,7 @@ impl ArpPacket {
             pkt.set_target_proto_addr(self.ipv4_dst);
             pkt.set_target_hw_addr(self.eth_dst);
         }
-        ether.add(LayerMut::Arp(arp));
-        Some(ether)
+        ether.add(LayerMut::Arp(arp))?;
+        Ok(ether)
     }
 }
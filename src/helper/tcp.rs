@@ -1,5 +1,6 @@
 use crate::{
-    EtherMut, Ipv4Mut, LayerMut, LayerMutable, Layers, TcpMut, VlanMut, helper::arp::ArpPacket,
+    EtherMut, Ipv4Mut, LayerMut, LayerMutable, Layers, Result, TcpMut, VlanMut,
+    helper::arp::ArpPacket,
 };
 use pnet::util::MacAddr;
 use serde::{Deserialize, Serialize};
@@ -27,8 +28,10 @@ pub struct TcpPacket {
 impl TcpPacket {
     /// creates a basic TCP packet setting all the parameters
     /// but without any TCP flags set
-    #[must_use]
-    pub fn basic(&self) -> Option<EtherMut> {
+    ///
+    /// # Errors
+    /// Returns an error if one of the layers could not be crafted
+    pub fn basic(&self) -> Result<EtherMut> {
         let mut ether = EtherMut::new();
         {
             let mut eth = ether.modify()?;
@@ -42,7 +45,7 @@ impl TcpPacket {
                 let mut pkt = vlan.modify()?;
                 pkt.set_vlan_identifier(vlan_id);
             }
-            ether.add(LayerMut::Vlan(vlan));
+            ether.add(LayerMut::Vlan(vlan))?;
         }
 
         let mut ipv4 = Ipv4Mut::new();
@@ -52,7 +55,7 @@ impl TcpPacket {
             pkt.set_destination(self.ipv4_dst);
         }
 
-        ether.add(LayerMut::Ipv4(ipv4));
+        ether.add(LayerMut::Ipv4(ipv4))?;
 
         let mut tcp = TcpMut::new();
         {
@@ -61,13 +64,15 @@ impl TcpPacket {
             pkt.set_source(self.sport);
         }
 
-        ether.add(LayerMut::Tcp(tcp));
-        Some(ether)
+        ether.add(LayerMut::Tcp(tcp))?;
+        Ok(ether)
     }
 
     /// Crafts an TCP Syn packet
-    #[must_use]
-    pub fn syn(&self, seq: u32) -> Option<EtherMut> {
+    ///
+    /// # Errors
+    /// Returns an error if one of the layers could not be crafted
+    pub fn syn(&self, seq: u32) -> Result<EtherMut> {
         let mut ether = self.basic()?;
         if let Some(LayerMut::Tcp(tcp)) = ether.get_layer(&Layers::Tcp) {
             let mut pkt = tcp.modify()?;
@@ -76,12 +81,14 @@ impl TcpPacket {
             pkt.set_acknowledgement(0);
             pkt.set_sequence(seq);
         }
-        Some(ether)
+        Ok(ether)
     }
 
     /// Create an ARP packet so the target of the SYN can find the sender
-    #[must_use]
-    pub fn arp(&self) -> Option<EtherMut> {
+    ///
+    /// # Errors
+    /// Returns an error if one of the layers could not be crafted
+    pub fn arp(&self) -> Result<EtherMut> {
         ArpPacket::from_tcp(self).reply()
     }
 }
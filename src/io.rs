@@ -0,0 +1,266 @@
+//! Sending and receiving packets.
+//!
+//! The `PacketIo` trait abstracts the backend, so code sending and receiving layer stacks can
+//! be tested with a `Loopback` pair or a `PcapReplay` instead of a network interface.
+//!
+//! ```
+//! use pnet_layers::io::{Loopback, PacketIo};
+//! use pnet_layers::LayerMut;
+//! use std::time::Duration;
+//!
+//! let (mut a, mut b) = Loopback::pair();
+//! a.send(LayerMut::parse("Ether / Ipv4 / Udp(dport: 53)")?)?;
+//! let received = b.recv(Some(Duration::from_secs(1)))?;
+//! assert!(matches!(received, Some(LayerMut::Ether(_))));
+//! # Ok::<(), pnet_layers::Error>(())
+//! ```
+
+use crate::pcap::{LinkType, PcapReader, Record};
+use crate::{Error, LayerMut, Result};
+use pnet::datalink::{self, Channel, Config, DataLinkReceiver, DataLinkSender};
+use std::io::{ErrorKind, Read};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Backend to send and receive packets
+pub trait PacketIo {
+    /// Sends a built packet
+    ///
+    /// # Errors
+    /// Returns `Error::Io` if the packet could not be sent
+    fn send_bytes(&mut self, packet: &[u8]) -> Result<()>;
+
+    /// Receives the next packet. Waits at most `timeout` or until a packet is received if no
+    /// timeout is given. Returns `None` if the timeout expired or no more packets can be
+    /// received.
+    ///
+    /// # Errors
+    /// Returns `Error::Io` if receiving failed
+    fn recv_record(&mut self, timeout: Option<Duration>) -> Result<Option<Record>>;
+
+    /// Builds and sends the layer stack
+    ///
+    /// # Errors
+    /// Returns the error of `LayerMut::build` or `Error::Io` if the packet could not be sent
+    fn send(&mut self, layer: LayerMut) -> Result<()> {
+        self.send_bytes(&layer.build()?)
+    }
+
+    /// Receives the next packet and parses it into mutable layers, see `recv_record`
+    ///
+    /// # Errors
+    /// Returns `Error::Io` if receiving failed or the error of `Record::layer_mut` if the
+    /// packet could not be parsed
+    fn recv(&mut self, timeout: Option<Duration>) -> Result<Option<LayerMut>> {
+        self.recv_record(timeout)?
+            .map(|record| record.layer_mut())
+            .transpose()
+    }
+}
+
+/// Creates the record of a packet received now
+fn ethernet_record(data: Vec<u8>) -> Record {
+    Record {
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default(),
+        interface: 0,
+        link_type: LinkType::Ethernet,
+        original_len: u32::try_from(data.len()).unwrap_or(u32::MAX),
+        data,
+        comments: vec![],
+    }
+}
+
+/// One end of an in-memory Ethernet link. Packets sent on one end are received by the other.
+#[derive(Debug)]
+pub struct Loopback {
+    tx: Sender<Vec<u8>>,
+    rx: Receiver<Vec<u8>>,
+}
+
+impl Loopback {
+    /// Creates both ends of a link
+    #[must_use]
+    pub fn pair() -> (Self, Self) {
+        let (tx_a, rx_b) = mpsc::channel();
+        let (tx_b, rx_a) = mpsc::channel();
+        (Self { tx: tx_a, rx: rx_a }, Self { tx: tx_b, rx: rx_b })
+    }
+}
+
+impl PacketIo for Loopback {
+    /// Sending to a dropped end is not an error, the packet is lost like on a real link
+    fn send_bytes(&mut self, packet: &[u8]) -> Result<()> {
+        let _ = self.tx.send(packet.to_vec());
+        Ok(())
+    }
+
+    /// Returns `None` if the other end was dropped and all packets are received
+    fn recv_record(&mut self, timeout: Option<Duration>) -> Result<Option<Record>> {
+        let data = match timeout {
+            Some(timeout) => self.rx.recv_timeout(timeout).ok(),
+            None => self.rx.recv().ok(),
+        };
+        Ok(data.map(ethernet_record))
+    }
+}
+
+/// Replays the packets of a capture file as received packets. Sent packets are kept and can
+/// be inspected with `sent`.
+#[derive(Debug)]
+pub struct PcapReplay<R> {
+    reader: PcapReader<R>,
+    sent: Vec<Vec<u8>>,
+}
+
+impl<R: Read> PcapReplay<R> {
+    /// Creates a replay of the records of the reader
+    #[must_use]
+    pub fn new(reader: PcapReader<R>) -> Self {
+        Self {
+            reader,
+            sent: vec![],
+        }
+    }
+
+    /// Returns the packets sent so far
+    #[must_use]
+    pub fn sent(&self) -> &[Vec<u8>] {
+        &self.sent
+    }
+}
+
+impl<R: Read> PacketIo for PcapReplay<R> {
+    fn send_bytes(&mut self, packet: &[u8]) -> Result<()> {
+        self.sent.push(packet.to_vec());
+        Ok(())
+    }
+
+    /// The records are returned without a delay, the timeout is ignored. Returns `None` at
+    /// the end of the file.
+    fn recv_record(&mut self, _timeout: Option<Duration>) -> Result<Option<Record>> {
+        self.reader.next().transpose()
+    }
+}
+
+/// Sends and receives Ethernet frames on a network interface using a pnet datalink channel.
+/// Opening the channel usually requires root privileges.
+pub struct DatalinkIo {
+    tx: Box<dyn DataLinkSender>,
+    rx: Box<dyn DataLinkReceiver>,
+}
+
+impl DatalinkIo {
+    /// How long a read blocks before the timeout of `recv_record` is checked
+    const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+    /// Opens a channel on the interface with the given name
+    ///
+    /// # Errors
+    /// Returns `Error::Io` if the interface does not exist or the channel could not be opened
+    pub fn open(interface: &str) -> Result<Self> {
+        let iface = datalink::interfaces()
+            .into_iter()
+            .find(|iface| iface.name == interface)
+            .ok_or_else(|| {
+                Error::Io(std::io::Error::new(
+                    ErrorKind::NotFound,
+                    format!("interface `{interface}` not found"),
+                ))
+            })?;
+        let config = Config {
+            read_timeout: Some(Self::POLL_INTERVAL),
+            ..Config::default()
+        };
+        match datalink::channel(&iface, config)? {
+            Channel::Ethernet(tx, rx) => Ok(Self { tx, rx }),
+            _ => Err(Error::Io(std::io::Error::new(
+                ErrorKind::Unsupported,
+                "channel type not supported",
+            ))),
+        }
+    }
+}
+
+impl PacketIo for DatalinkIo {
+    fn send_bytes(&mut self, packet: &[u8]) -> Result<()> {
+        match self.tx.send_to(packet, None) {
+            Some(result) => Ok(result?),
+            None => Err(Error::Io(std::io::Error::new(
+                ErrorKind::WriteZero,
+                "packet could not be sent",
+            ))),
+        }
+    }
+
+    fn recv_record(&mut self, timeout: Option<Duration>) -> Result<Option<Record>> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        loop {
+            match self.rx.next() {
+                Ok(data) => return Ok(Some(ethernet_record(data.to_vec()))),
+                Err(err) if matches!(err.kind(), ErrorKind::TimedOut | ErrorKind::WouldBlock) => {
+                    if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                        return Ok(None);
+                    }
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pcap::{PcapFormat, PcapWriter};
+
+    #[test]
+    fn test_loopback() {
+        let (mut a, mut b) = Loopback::pair();
+        let packet = LayerMut::parse("Ether / Ipv4(id: 1) / Udp(dport: 53)").expect("parse");
+        let bytes = packet.clone().build().expect("could not build");
+        a.send(packet).expect("could not send");
+        b.send_bytes(&[0; 4]).expect("could not send");
+
+        let received = b
+            .recv(Some(Duration::from_secs(1)))
+            .expect("could not recv")
+            .expect("no packet");
+        assert_eq!(received.build().expect("could not build"), bytes);
+        assert!(matches!(
+            b.recv_record(Some(Duration::from_millis(10))),
+            Ok(None)
+        ));
+        // packets which can not be parsed are returned as error by `recv`
+        assert!(a.recv(None).is_err());
+
+        drop(b);
+        a.send_bytes(&[0; 4]).expect("could not send");
+        assert!(matches!(a.recv_record(None), Ok(None)));
+    }
+
+    #[test]
+    fn test_pcap_replay() {
+        let packet = LayerMut::parse("Ether / Ipv6 / Udp")
+            .and_then(LayerMut::build)
+            .expect("could not build");
+        let mut writer =
+            PcapWriter::new(vec![], PcapFormat::PcapNg, LinkType::Ethernet).expect("pcap");
+        writer
+            .write(Duration::from_secs(5), &packet)
+            .expect("could not write");
+        let file = writer.into_inner().expect("could not write");
+
+        let mut replay = PcapReplay::new(PcapReader::new(&file[..]).expect("could not read"));
+        replay.send_bytes(&[1, 2, 3]).expect("could not send");
+        assert_eq!(replay.sent(), [vec![1, 2, 3]]);
+        let record = replay
+            .recv_record(None)
+            .expect("could not recv")
+            .expect("no packet");
+        assert_eq!(record.timestamp, Duration::from_secs(5));
+        assert_eq!(record.data, packet);
+        assert!(matches!(replay.recv(None), Ok(None)));
+    }
+}
@@ -1,23 +1,41 @@
 pub(crate) mod arp;
+pub(crate) mod custom;
 pub(crate) mod ether;
 pub(crate) mod icmp;
+pub(crate) mod icmpv6;
 pub(crate) mod ipv4;
 pub(crate) mod ipv6;
+pub(crate) mod ipv6_ext;
+pub(crate) mod iter;
+pub(crate) mod options;
 pub(crate) mod payload;
+pub(crate) mod raw;
 pub(crate) mod tcp;
+pub(crate) mod trailer;
 pub(crate) mod udp;
 pub(crate) mod vlan;
 
 use std::fmt::Display;
 
+use crate::error::{Error, Result};
+use crate::field::{Field, Value};
 use crate::layers;
 use crate::layers::arp::{Arp, ArpMut};
+use crate::layers::custom::{Custom, CustomMut};
 use crate::layers::ether::{Ether, EtherMut};
 use crate::layers::icmp::{Icmp, IcmpMut};
+use crate::layers::icmpv6::{Icmpv6, Icmpv6Mut};
 use crate::layers::ipv4::{Ipv4, Ipv4Mut};
 use crate::layers::ipv6::{Ipv6, Ipv6Mut};
+use crate::layers::ipv6_ext::{
+    Ah, AhMut, DestOpts, DestOptsMut, Fragment, FragmentMut, HopByHop, HopByHopMut, Routing,
+    RoutingMut,
+};
+use crate::layers::iter::LayerIter;
 use crate::layers::payload::{Payload, PayloadMut};
+use crate::layers::raw::{Raw, RawMut};
 use crate::layers::tcp::{Tcp, TcpMut};
+use crate::layers::trailer::{Trailer, TrailerMut};
 use crate::layers::udp::{Udp, UdpMut};
 use crate::layers::vlan::{Vlan, VlanMut};
 
@@ -27,10 +45,19 @@ layers!(
     Arp => ArpMut,
     Ipv4 => Ipv4Mut,
     Ipv6 => Ipv6Mut,
+    HopByHop => HopByHopMut,
+    Routing => RoutingMut,
+    Fragment => FragmentMut,
+    DestOpts => DestOptsMut,
+    Ah => AhMut,
     Icmp => IcmpMut,
+    Icmpv6 => Icmpv6Mut,
     Udp => UdpMut,
     Tcp => TcpMut,
-    Payload => PayloadMut
+    Payload => PayloadMut,
+    Raw => RawMut,
+    Custom => CustomMut,
+    Trailer => TrailerMut
 );
 
 /// Implements functions for immutable layer representation
@@ -41,20 +68,35 @@ pub trait LayerImmutable<'a> {
     type PacketMut;
     ///  Packet specific`LayerMut` type used to convert the immutable to a mutable packet
     type LayerMutType;
+    /// The layer type used to identify the layer e.g. in errors
+    const LAYER: Layers;
     /// Creates a new immutable representation of the packet
     fn new(buf: &'a [u8]) -> Self;
     /// Returns a mutable packet to modification this will cause a copy of payload
-    fn as_mut(&self) -> Option<Self::LayerMutType>;
+    ///
+    /// # Errors
+    /// Returns an error if the buffer could not be parsed into the mutable layers
+    fn as_mut(&self) -> Result<Self::LayerMutType>;
     /// Retunrs a immutable reference to the raw packet
     fn get_buf(&self) -> &[u8];
     /// returns immutable pnet representation
-    fn as_pnet(&self) -> Option<Self::Packet>;
+    ///
+    /// # Errors
+    /// Returns `Error::Truncated` if the buffer is too short for the pnet packet
+    fn as_pnet(&self) -> Result<Self::Packet>;
     /// returns mutable pnet representation this will cause a copy of payload
-    fn as_mut_pnet(&self) -> Option<Self::PacketMut>;
+    ///
+    /// # Errors
+    /// Returns `Error::Truncated` if the buffer is too short for the pnet packet
+    fn as_mut_pnet(&self) -> Result<Self::PacketMut>;
     /// Searches in the self buffer for the layer provided and if exists returns it
     fn get_layer(&'a self, layer: Layers) -> Option<Layer<'a>>;
     /// Searches in the buffer for the layer provided and if exists returns it
     fn get_layer_from_buf(buf: &'_ [u8], layer: Layers) -> Option<Layer<'_>>;
+    /// Returns an iterator over this and all upper layers in a single pass
+    fn layers(&'a self) -> LayerIter<'a> {
+        LayerIter::new(self.get_buf(), Self::LAYER)
+    }
 }
 
 /// Implements functions for packet manipulation
@@ -62,28 +104,244 @@ pub trait LayerMutable<'a>: Sized {
     /// Mutable packet type of the pnet lib
     type PacketMut;
 
+    /// The layer type used to identify the layer e.g. in errors
+    const LAYER: Layers;
+
+    /// Fields of the header which can be read and written by their name
+    const FIELDS: &'static [Field] = &[];
+
+    /// Layer types allowed directly on top of this layer
+    const UPPER: &'static [Layers] = &[];
+
     /// Creates a new mut layer instance
     fn new() -> Self;
 
     /// Creates a new mut layer instance base on the provided buffer
-    fn from_buf(buf: Vec<u8>) -> Option<Self>;
+    ///
+    /// # Errors
+    /// Returns `Error::Truncated` with the offset of the layer if the buffer is too short for
+    /// one of the layers
+    fn from_buf(buf: Vec<u8>) -> Result<Self>;
+
+    /// Returns the upper layer if there is one
+    fn upper_layer(&self) -> Option<&LayerMut> {
+        None
+    }
+
+    /// Returns a mutable reference to the upper layer if there is one
+    fn upper_layer_mut(&mut self) -> Option<&mut LayerMut> {
+        None
+    }
+
+    /// Returns the trailer following all upper layers if there is one
+    fn trailer(&self) -> Option<&TrailerMut> {
+        None
+    }
+
+    /// Returns the name of the layer, which differs from the layer type for custom layers
+    fn name(&self) -> &'static str {
+        Self::LAYER.name()
+    }
+
+    /// Returns the fields of the header, which are the `FIELDS` except for custom layers
+    fn fields(&self) -> &'static [Field] {
+        Self::FIELDS
+    }
+
+    /// Updates the header after the field was set by its name, e.g. sizes an ICMP header by
+    /// its message type or updates the header length of IPv4 to the options
+    fn update_header(&mut self, _field: &Field) {}
 
     /// Returns a mutable reference to the requested layer
     fn get_layer(&'a mut self, _layer: &Layers) -> Option<&'a mut LayerMut> {
         None
     }
 
+    /// Returns a mutable reference to the `n`th upper layer of the requested type counting from
+    /// 0, e.g. the inner tag of a double tagged frame with `get_layer_nth(&Layers::Vlan, 1)`
+    fn get_layer_nth(&mut self, layer: &Layers, n: usize) -> Option<&mut LayerMut> {
+        nth_layer(self.upper_layer_mut(), *layer, n)
+    }
+
+    /// Returns an iterator over all upper layers from the lowest to the highest
+    fn layers(&self) -> impl Iterator<Item = &LayerMut> {
+        std::iter::successors(self.upper_layer(), |layer| layer.upper_layer())
+    }
+
+    /// Returns a cursor visiting all upper layers from the lowest to the highest for
+    /// modifying them
+    fn layers_mut(&mut self) -> LayersMut<'_> {
+        LayersMut::new(self.upper_layer_mut())
+    }
+
+    /// Returns the number of upper layers of the requested type
+    fn count(&self, layer: &Layers) -> usize {
+        self.layers().filter(|upper| upper.kind() == *layer).count()
+    }
+
+    /// Returns true if the layer type is allowed directly on top of this layer
+    fn accepts(&self, layer: &Layers) -> bool {
+        Self::UPPER.contains(layer)
+    }
+
+    /// Replaces the upper layer together with all layers above it and returns the previous
+    /// upper layer, `None` removes all upper layers
+    ///
+    /// # Errors
+    /// Returns `Error::IllegalStacking` if the layer is not allowed on top of this layer
+    fn replace_upper_layer(&mut self, layer: Option<LayerMut>) -> Result<Option<LayerMut>> {
+        match layer {
+            Some(layer) => Err(Error::IllegalStacking {
+                layer: layer.kind(),
+                lower: Self::LAYER,
+            }),
+            None => Ok(None),
+        }
+    }
+
+    /// Inserts the layer directly on top of this layer, the previous upper layers are stacked
+    /// on top of the inserted layer
+    ///
+    /// # Errors
+    /// Returns `Error::IllegalStacking` if the layer is not allowed on this layer or the
+    /// previous upper layer not on the inserted layer, the packet is left unchanged then
+    fn insert(&mut self, mut layer: LayerMut) -> Result<()> {
+        let above = self.upper_layer().map(LayerMut::kind);
+        check_insert(Self::LAYER, self.accepts(&layer.kind()), &layer, above)?;
+        if let Some(above) = self.replace_upper_layer(None)? {
+            stack_on_top(&mut layer, above)?;
+        }
+        self.replace_upper_layer(Some(layer)).map(drop)
+    }
+
+    /// Removes the first upper layer of the requested type and returns it without its upper
+    /// layers, which are stacked on the layer below instead. E.g. `remove(&Layers::Vlan)`
+    /// strips the outer tag of a VLAN tagged frame.
+    ///
+    /// # Errors
+    /// Returns `Error::MissingLayer` if there is no upper layer of the type or
+    /// `Error::IllegalStacking` if the layers above are not allowed on the layer below, the
+    /// packet is left unchanged then
+    fn remove(&mut self, layer: &Layers) -> Result<LayerMut> {
+        let upper = self
+            .upper_layer_mut()
+            .ok_or(Error::MissingLayer { layer: *layer })?;
+        if upper.kind() != *layer {
+            return upper.remove(layer);
+        }
+        if let Some(above) = upper.upper_layer().map(LayerMut::kind)
+            && !self.accepts(&above)
+        {
+            return Err(Error::IllegalStacking {
+                layer: above,
+                lower: Self::LAYER,
+            });
+        }
+        let Some(mut removed) = self.replace_upper_layer(None)? else {
+            unreachable!("the upper layer was checked before")
+        };
+        let above = removed.replace_upper_layer(None)?;
+        self.replace_upper_layer(above)?;
+        Ok(removed)
+    }
+
+    /// Inserts the layer after the first upper layer of the requested type, the layers above
+    /// it are stacked on top of the inserted layer
+    ///
+    /// # Errors
+    /// Returns `Error::MissingLayer` if there is no upper layer of the type or
+    /// `Error::IllegalStacking` if the layers can not be stacked like this, the packet is left
+    /// unchanged then
+    fn insert_after(&mut self, layer: &Layers, new: LayerMut) -> Result<()> {
+        let upper = self
+            .upper_layer_mut()
+            .ok_or(Error::MissingLayer { layer: *layer })?;
+        if upper.kind() == *layer {
+            upper.insert(new)
+        } else {
+            upper.insert_after(layer, new)
+        }
+    }
+
+    /// Inserts the layer before the first upper layer of the requested type, e.g. adds a VLAN
+    /// tag with `insert_before(&Layers::Ipv4, vlan)`
+    ///
+    /// # Errors
+    /// Returns `Error::MissingLayer` if there is no upper layer of the type or
+    /// `Error::IllegalStacking` if the layers can not be stacked like this, the packet is left
+    /// unchanged then
+    fn insert_before(&mut self, layer: &Layers, new: LayerMut) -> Result<()> {
+        let upper = self
+            .upper_layer_mut()
+            .ok_or(Error::MissingLayer { layer: *layer })?;
+        if upper.kind() == *layer {
+            self.insert(new)
+        } else {
+            upper.insert_before(layer, new)
+        }
+    }
+
+    /// Replaces the first upper layer of the requested type and returns it without its upper
+    /// layers, which are stacked on top of the new layer instead
+    ///
+    /// # Errors
+    /// Returns `Error::MissingLayer` if there is no upper layer of the type or
+    /// `Error::IllegalStacking` if the layers can not be stacked like this, the packet is left
+    /// unchanged then
+    fn replace(&mut self, layer: &Layers, mut new: LayerMut) -> Result<LayerMut> {
+        let upper = self
+            .upper_layer_mut()
+            .ok_or(Error::MissingLayer { layer: *layer })?;
+        if upper.kind() != *layer {
+            return upper.replace(layer, new);
+        }
+        let above = upper.upper_layer().map(LayerMut::kind);
+        check_insert(Self::LAYER, self.accepts(&new.kind()), &new, above)?;
+        let Some(mut old) = self.replace_upper_layer(None)? else {
+            unreachable!("the upper layer was checked before")
+        };
+        if let Some(above) = old.replace_upper_layer(None)? {
+            stack_on_top(&mut new, above)?;
+        }
+        self.replace_upper_layer(Some(new))?;
+        Ok(old)
+    }
+
+    /// Removes all layers above the first upper layer of the requested type and returns them
+    ///
+    /// # Errors
+    /// Returns `Error::MissingLayer` if there is no upper layer of the type
+    fn truncate_after(&mut self, layer: &Layers) -> Result<Option<LayerMut>> {
+        let upper = self
+            .upper_layer_mut()
+            .ok_or(Error::MissingLayer { layer: *layer })?;
+        if upper.kind() == *layer {
+            upper.replace_upper_layer(None)
+        } else {
+            upper.truncate_after(layer)
+        }
+    }
+
     /// This returns the a mutable reference to the pnet type to modify properties of the packet.
     /// If you want to modify the payload YOU must use `set_payload` from the `LayerMutable` trait
-    fn modify(&'a mut self) -> Option<Self::PacketMut>;
+    ///
+    /// # Errors
+    /// Returns `Error::Truncated` if the buffer is too short for the pnet packet
+    fn modify(&'a mut self) -> Result<Self::PacketMut>;
 
     /// Modifies the payload of the packet
     fn set_payload(&'a mut self, payload: &[u8]);
 
-    /// Add upper layer as payload. Returns false if it fails e.g layer is not allowed as payload
+    /// Add upper layer as payload.
     /// If already a upper layer is defined this will be propagated to this upper layer
-    fn add(&mut self, _layer: LayerMut) -> bool {
-        false
+    ///
+    /// # Errors
+    /// Returns `Error::IllegalStacking` if the layer is not allowed as payload
+    fn add(&mut self, layer: LayerMut) -> Result<()> {
+        Err(Error::IllegalStacking {
+            layer: layer.kind(),
+            lower: Self::LAYER,
+        })
     }
 
     /// If the layer contains a src and a dest this will be switch and propagated to the last
@@ -96,5 +354,125 @@ pub trait LayerMutable<'a>: Sized {
     ///     already set
     ///     - Payload types will be set
     ///     - Checksum will be calculated
-    fn build(self) -> Option<Vec<u8>>;
+    ///
+    /// # Errors
+    /// Returns `Error::IllegalStacking` if an upper layer is not allowed on its lower layer or
+    /// `Error::LengthOverflow` if a layer gets too large for its length field
+    fn build(self) -> Result<Vec<u8>>;
+}
+
+/// Returns the `n`th layer of the requested type in the chain starting with `current`
+fn nth_layer(
+    mut current: Option<&mut LayerMut>,
+    layer: Layers,
+    mut n: usize,
+) -> Option<&mut LayerMut> {
+    while let Some(candidate) = current {
+        if candidate.kind() == layer {
+            if n == 0 {
+                return Some(candidate);
+            }
+            n -= 1;
+        }
+        current = candidate.upper_layer_mut();
+    }
+    None
+}
+
+/// Checks that `layer` is allowed on the `lower` layer type and the layer type `above` on the
+/// highest layer of `layer`
+fn check_insert(
+    lower: Layers,
+    accepted: bool,
+    layer: &LayerMut,
+    above: Option<Layers>,
+) -> Result<()> {
+    if !accepted {
+        return Err(Error::IllegalStacking {
+            layer: layer.kind(),
+            lower,
+        });
+    }
+    let top = layer.layers().last().unwrap_or(layer);
+    match above {
+        Some(above) if !top.accepts(&above) => Err(Error::IllegalStacking {
+            layer: above,
+            lower: top.kind(),
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Stacks `above` on the highest layer of `layer` without the reordering done by `add`
+fn stack_on_top(layer: &mut LayerMut, above: LayerMut) -> Result<()> {
+    match layer.upper_layer_mut() {
+        Some(upper) => stack_on_top(upper, above),
+        None => layer.replace_upper_layer(Some(above)).map(drop),
+    }
+}
+
+/// Cursor over a chain of layers returned by `layers_mut`. A layer owns its upper layers, so
+/// the layers are visited one after another with `next` instead of an `Iterator`.
+///
+/// ```
+/// use pnet_layers::{LayerMut, Layers, Value};
+///
+/// let mut packet = LayerMut::parse("Ether / Vlan / Vlan / Ipv4")?;
+/// let mut layers = packet.layers_mut();
+/// while let Some(layer) = layers.next() {
+///     if layer.kind() == Layers::Vlan {
+///         layer.set_field("id", &Value::Int(100))?;
+///     }
+/// }
+/// assert_eq!(packet.count(&Layers::Vlan), 2);
+/// # Ok::<(), pnet_layers::Error>(())
+/// ```
+pub struct LayersMut<'a> {
+    current: Option<&'a mut LayerMut>,
+    started: bool,
+}
+
+impl<'a> LayersMut<'a> {
+    fn new(first: Option<&'a mut LayerMut>) -> Self {
+        Self {
+            current: first,
+            started: false,
+        }
+    }
+
+    /// Advances to the next layer and returns it
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<&mut LayerMut> {
+        if self.started {
+            self.current = self.current.take().and_then(LayerMut::upper_layer_mut);
+        }
+        self.started = true;
+        self.current.as_deref_mut()
+    }
+}
+
+impl LayerMut {
+    /// Returns a mutable reference to the `n`th upper layer of the requested type counting from
+    /// 0 like `LayerMutable::get_layer_nth`
+    pub fn get_layer_nth(&mut self, layer: &Layers, n: usize) -> Option<&mut LayerMut> {
+        nth_layer(self.upper_layer_mut(), *layer, n)
+    }
+
+    /// Returns an iterator over all upper layers from the lowest to the highest like
+    /// `LayerMutable::layers`
+    pub fn layers(&self) -> impl Iterator<Item = &LayerMut> {
+        std::iter::successors(self.upper_layer(), |layer| layer.upper_layer())
+    }
+
+    /// Returns a cursor visiting all upper layers for modifying them like
+    /// `LayerMutable::layers_mut`
+    pub fn layers_mut(&mut self) -> LayersMut<'_> {
+        LayersMut::new(self.upper_layer_mut())
+    }
+
+    /// Returns the number of upper layers of the requested type like `LayerMutable::count`
+    #[must_use]
+    pub fn count(&self, layer: &Layers) -> usize {
+        self.layers().filter(|upper| upper.kind() == *layer).count()
+    }
 }
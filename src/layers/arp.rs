@@ -1,6 +1,6 @@
 use crate::{
-    Layer, LayerImmutable, LayerMutable, Layers, create_default_immutable, create_modify,
-    create_set_payload,
+    Field, FieldKind, Layer, LayerImmutable, LayerMutable, Layers, Result,
+    create_default_immutable, create_modify, create_set_payload,
 };
 use pnet::packet::arp::{ArpPacket, MutableArpPacket};
 use std::fmt::Display;
@@ -14,11 +14,23 @@ pub struct Arp<'a> {
 #[derive(Debug, Clone)]
 /// Mutable representation of an arp packet
 pub struct ArpMut {
-    buf: Vec<u8>,
+    pub(crate) buf: Vec<u8>,
 }
 
 impl<'a> LayerMutable<'a> for ArpMut {
     type PacketMut = MutableArpPacket<'a>;
+    const LAYER: Layers = Layers::Arp;
+    const FIELDS: &'static [Field] = &[
+        Field::int("hwtype", 0, 16),
+        Field::int("ptype", 16, 16),
+        Field::int("hwlen", 32, 8),
+        Field::int("plen", 40, 8),
+        Field::int("op", 48, 16),
+        Field::new("hwsrc", 64, FieldKind::Mac),
+        Field::new("psrc", 112, FieldKind::Ipv4),
+        Field::new("hwdst", 144, FieldKind::Mac),
+        Field::new("pdst", 192, FieldKind::Ipv4),
+    ];
     fn new() -> Self {
         Self {
             buf: vec![0; ArpPacket::minimum_packet_size()],
@@ -27,12 +39,12 @@ impl<'a> LayerMutable<'a> for ArpMut {
     create_modify!();
     create_set_payload!();
 
-    fn from_buf(buf: Vec<u8>) -> Option<Self> {
-        Some(Self { buf })
+    fn from_buf(buf: Vec<u8>) -> Result<Self> {
+        Ok(Self { buf })
     }
 
-    fn build(self) -> Option<Vec<u8>> {
-        Some(self.buf)
+    fn build(self) -> Result<Vec<u8>> {
+        Ok(self.buf)
     }
 }
 
@@ -40,6 +52,7 @@ impl<'a> LayerImmutable<'a> for Arp<'a> {
     type Packet = ArpPacket<'a>;
     type PacketMut = MutableArpPacket<'a>;
     type LayerMutType = ArpMut;
+    const LAYER: Layers = Layers::Arp;
 
     create_default_immutable!();
     fn get_layer_from_buf(_buf: &'_ [u8], _layer: Layers) -> Option<Layer<'_>> {
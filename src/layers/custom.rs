@@ -0,0 +1,180 @@
+use crate::layers::payload::{MutablePayloadDummyPacket, PayloadDummyPacket};
+use crate::registry::{self, Protocol};
+use crate::{
+    Error, Field, Layer, LayerImmutable, LayerMut, LayerMutable, Layers, Result, create_add_layer,
+    create_default_immutable, create_get_layer, create_modify, create_set_payload,
+    create_switch_layer, create_upper_layer,
+};
+use std::fmt::{Debug, Display};
+use std::sync::Arc;
+
+/// Immutable representation of a layer of a custom protocol
+#[derive(Debug)]
+pub struct Custom<'a> {
+    /// buf
+    pub buf: &'a [u8],
+}
+
+#[derive(Clone)]
+/// Mutable representation of a layer of a custom protocol bound in the `registry`
+pub struct CustomMut {
+    pub(crate) buf: Vec<u8>,
+    /// the upper layer
+    pub upper_layer: Option<Box<LayerMut>>,
+    protocol: Arc<dyn Protocol>,
+}
+
+/// Protocol of a custom layer created without a protocol, the whole buffer is the header
+struct Opaque;
+
+impl Protocol for Opaque {
+    fn name(&self) -> &'static str {
+        "Custom"
+    }
+
+    fn new_header(&self) -> Vec<u8> {
+        vec![]
+    }
+
+    fn header_len(&self, buf: &[u8]) -> Result<usize> {
+        Ok(buf.len())
+    }
+}
+
+impl CustomMut {
+    /// Creates a layer of the protocol with the default header of the protocol
+    #[must_use]
+    pub fn with_protocol(protocol: Arc<dyn Protocol>) -> Self {
+        Self {
+            buf: protocol.new_header(),
+            upper_layer: None,
+            protocol,
+        }
+    }
+
+    /// Parses the header of the protocol and the upper layers announced by the header
+    ///
+    /// # Errors
+    /// Returns `Error::Truncated` if the buffer is shorter than the header or the error of an
+    /// upper layer
+    pub fn parse(protocol: Arc<dyn Protocol>, mut buf: Vec<u8>) -> Result<Self> {
+        let header_len = protocol.header_len(&buf)?;
+        if header_len > buf.len() {
+            return Err(Error::Truncated {
+                layer: Layers::Custom,
+                offset: 0,
+            });
+        }
+        let payload = buf.split_off(header_len);
+        let upper_layer = registry::parse_next(protocol.next(&buf), payload, Self::UPPER)
+            .map_err(|e| e.offset_by(header_len))?
+            .map(Box::new);
+        Ok(Self {
+            buf,
+            upper_layer,
+            protocol,
+        })
+    }
+
+    /// Returns the protocol of the layer
+    #[must_use]
+    pub fn protocol(&self) -> &Arc<dyn Protocol> {
+        &self.protocol
+    }
+
+    /// Returns the header for writing, e.g. through the mutable packet of a derived layer
+    pub fn header_mut(&mut self) -> &mut [u8] {
+        &mut self.buf
+    }
+}
+
+impl<'a> LayerMutable<'a> for CustomMut {
+    type PacketMut = MutablePayloadDummyPacket<'a>;
+    const LAYER: Layers = Layers::Custom;
+
+    fn new() -> Self {
+        Self::with_protocol(Arc::new(Opaque))
+    }
+
+    fn name(&self) -> &'static str {
+        self.protocol.name()
+    }
+
+    fn fields(&self) -> &'static [Field] {
+        self.protocol.fields()
+    }
+
+    create_modify!();
+    create_upper_layer!();
+    create_set_payload!();
+    create_switch_layer!(upper);
+    create_add_layer!(
+        Vlan, Arp, Ipv4, Ipv6, HopByHop, Routing, Fragment, DestOpts, Ah, Icmp, Icmpv6, Udp, Tcp,
+        Payload, Raw, Custom; {}
+    );
+    create_get_layer!(
+        Vlan, Arp, Ipv4, Ipv6, Icmp, Icmpv6, Udp, Tcp, Payload, Raw, Custom
+    );
+
+    fn from_buf(buf: Vec<u8>) -> Result<Self> {
+        Self::parse(Arc::new(Opaque), buf)
+    }
+
+    fn build(mut self) -> Result<Vec<u8>> {
+        let payload = match self.upper_layer.clone() {
+            Some(upper) => upper.build()?,
+            None => vec![],
+        };
+        self.protocol
+            .build(&mut self.buf, self.upper_layer.as_deref(), &payload)?;
+        self.buf.extend_from_slice(&payload);
+        Ok(self.buf)
+    }
+}
+
+impl<'a> LayerImmutable<'a> for Custom<'a> {
+    type Packet = PayloadDummyPacket<'a>;
+    type PacketMut = MutablePayloadDummyPacket<'a>;
+    type LayerMutType = CustomMut;
+    const LAYER: Layers = Layers::Custom;
+
+    create_default_immutable!();
+    fn get_layer_from_buf(_buf: &'_ [u8], _layer: Layers) -> Option<Layer<'_>> {
+        None
+    }
+}
+
+impl Display for CustomMut {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.protocol.name())?;
+        let fields: Vec<String> = self
+            .protocol
+            .fields()
+            .iter()
+            .filter_map(|field| {
+                Some(format!(
+                    "{}: {}",
+                    field.name,
+                    field.format(&field.get(&self.buf)?)
+                ))
+            })
+            .collect();
+        if !fields.is_empty() {
+            write!(f, " ({})", fields.join(", "))?;
+        }
+        if let Some(upper) = &self.upper_layer {
+            write!(f, " > {upper}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Debug for CustomMut {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({:02x?})", self.protocol.name(), self.buf)?;
+        if let Some(upper) = &self.upper_layer {
+            write!(f, " > {upper:?}")?;
+        }
+        Ok(())
+    }
+}
@@ -1,10 +1,10 @@
-use crate::layers::{Arp, ArpMut, Ipv4, Ipv4Mut, Ipv6, Ipv6Mut, LayerImmutable, Vlan, VlanMut};
+use crate::layers::{Arp, Ipv4, Ipv6, LayerImmutable, Raw, Trailer, TrailerMut, Vlan};
+use crate::registry;
 use crate::{
-    Layer, LayerMut, LayerMutable, Layers, create_default_immutable, create_set_payload,
-    create_switch_layer,
+    Error, Field, FieldKind, Layer, LayerMut, LayerMutable, Layers, Result,
+    create_default_immutable, create_set_payload, create_switch_layer,
 };
-use crate::{create_add_layer, create_from_buf, create_get_layer, create_modify};
-use pnet::packet::Packet;
+use crate::{create_add_layer, create_get_layer, create_modify, create_upper_layer};
 use pnet::packet::ethernet::MutableEthernetPacket;
 use pnet::packet::ethernet::{EtherType, EtherTypes, EthernetPacket};
 use std::fmt::{Debug, Display};
@@ -18,54 +18,122 @@ pub struct Ether<'a> {
 #[derive(Clone)]
 /// Mutable representation of an Ethernet packet
 pub struct EtherMut {
-    buf: Vec<u8>,
+    pub(crate) buf: Vec<u8>,
     /// The upper layer of the ethernet
     pub upper_layer: Option<Box<LayerMut>>,
+    /// The bytes following the upper layers, e.g. the padding of a short frame
+    pub trailer: Option<TrailerMut>,
+    /// Pads the frame to `MIN_LEN` on build
+    pad: bool,
+}
+
+impl EtherMut {
+    /// Minimum length of an Ethernet frame without the frame check sequence
+    pub const MIN_LEN: usize = 60;
+
+    /// Pads frames shorter than `MIN_LEN` with zeros after the trailer on build. The padding
+    /// is disabled by default, so the built frame contains only the given layers.
+    pub fn set_padding(&mut self, pad: bool) {
+        self.pad = pad;
+    }
+}
+
+/// Returns the length of the frame up to the end of the network layer, which is given by the
+/// IP length fields or the ARP address lengths. Bytes after it belong to the trailer.
+pub(super) fn frame_len(buf: &[u8]) -> Option<usize> {
+    let be16 = |offset: usize| -> Option<usize> {
+        let bytes = buf.get(offset..offset + 2)?;
+        Some(usize::from(u16::from_be_bytes([bytes[0], bytes[1]])))
+    };
+    let mut offset = EthernetPacket::minimum_packet_size();
+    let mut ethertype = EtherType(u16::try_from(be16(offset - 2)?).ok()?);
+    while ethertype == EtherTypes::Vlan {
+        ethertype = EtherType(u16::try_from(be16(offset + 2)?).ok()?);
+        offset += 4;
+    }
+    let len = match ethertype {
+        // a total length of zero is used by captures with segmentation offloading
+        EtherTypes::Ipv4 => Some(be16(offset + 2)?).filter(|len| *len >= 20)?,
+        // a payload length of zero is used by jumbograms
+        EtherTypes::Ipv6 => 40 + Some(be16(offset + 4)?).filter(|len| *len > 0)?,
+        // the address lengths of a crafted ARP packet may be left at zero
+        EtherTypes::Arp => {
+            let addrs_len = usize::from(*buf.get(offset + 4)?) + usize::from(*buf.get(offset + 5)?);
+            8 + 2 * Some(addrs_len).filter(|len| *len > 0)?
+        }
+        _ => return None,
+    };
+    Some(offset + len)
 }
 
 impl<'a> LayerMutable<'a> for EtherMut {
     type PacketMut = MutableEthernetPacket<'a>;
+    const LAYER: Layers = Layers::Ether;
+    const FIELDS: &'static [Field] = &[
+        Field::new("dst", 0, FieldKind::Mac),
+        Field::new("src", 48, FieldKind::Mac),
+        Field::int("type", 96, 16),
+    ];
 
     fn new() -> Self {
         Self {
             buf: vec![0; EthernetPacket::minimum_packet_size()],
             upper_layer: None,
+            trailer: None,
+            pad: false,
         }
     }
 
     create_modify!();
+    create_upper_layer!();
     create_set_payload!();
     create_switch_layer!();
-    create_add_layer!(Vlan, Ipv4, Ipv6, Arp; { Ipv4 => Vlan, Arp => Vlan, Ipv6 => Vlan});
-    create_get_layer!(Vlan, Ipv4, Ipv6, Arp);
-    create_from_buf!(
-        EthernetPacket,
-        get_ethertype,
-        EtherTypes,
-        Vlan => VlanMut,
-        Ipv4 => Ipv4Mut,
-        Ipv6 => Ipv6Mut,
-        Arp => ArpMut
-    );
-
-    fn build(mut self) -> Option<Vec<u8>> {
-        if self.upper_layer.is_some() {
-            #[allow(clippy::unwrap_used)]
-            if matches!(**self.upper_layer.as_ref().unwrap(), LayerMut::Ipv4(_)) {
-                self.modify().unwrap().set_ethertype(EtherTypes::Ipv4);
-            }
-            #[allow(clippy::unwrap_used)]
-            if matches!(**self.upper_layer.as_ref().unwrap(), LayerMut::Ipv6(_)) {
-                self.modify().unwrap().set_ethertype(EtherTypes::Ipv6);
-            }
-            #[allow(clippy::unwrap_used)]
-            if matches!(**self.upper_layer.as_ref().unwrap(), LayerMut::Vlan(_)) {
-                self.modify().unwrap().set_ethertype(EtherTypes::Vlan);
-            }
-            #[allow(clippy::unwrap_used)]
-            if matches!(**self.upper_layer.as_ref().unwrap(), LayerMut::Arp(_)) {
-                self.modify().unwrap().set_ethertype(EtherTypes::Arp);
-            }
+    create_add_layer!(Vlan, Ipv4, Ipv6, Arp, Raw, Custom; { Ipv4 => Vlan, Arp => Vlan, Ipv6 => Vlan}; trailer);
+    create_get_layer!(Vlan, Ipv4, Ipv6, Arp, Raw, Custom);
+
+    fn trailer(&self) -> Option<&TrailerMut> {
+        self.trailer.as_ref()
+    }
+
+    /// Bytes after the end of the network layer, e.g. the padding of short frames, are kept
+    /// as `trailer` and written again on build.
+    fn from_buf(mut buf: Vec<u8>) -> Result<Self> {
+        let ethertype = EthernetPacket::new(&buf)
+            .ok_or(Error::Truncated {
+                layer: Self::LAYER,
+                offset: 0,
+            })?
+            .get_ethertype();
+        let trailer = frame_len(&buf)
+            .filter(|len| *len < buf.len())
+            .map(|len| TrailerMut {
+                buf: buf.split_off(len),
+            });
+
+        let header_len = EthernetPacket::minimum_packet_size();
+        let payload = buf.split_off(header_len);
+        // a protocol bound in the registry is preferred over the built-in layers and the
+        // payload of an unsupported protocol is kept as `Raw`
+        let upper_layer = registry::parse_next(Some(ethertype.into()), payload, Self::UPPER)
+            .map_err(|e| e.offset_by(header_len))?
+            .map(Box::new);
+
+        Ok(Self {
+            buf,
+            upper_layer,
+            trailer,
+            pad: false,
+        })
+    }
+
+    fn build(mut self) -> Result<Vec<u8>> {
+        let ethertype = self
+            .upper_layer
+            .as_deref()
+            .and_then(registry::ether_type)
+            .map(EtherType);
+        if let Some(ethertype) = ethertype {
+            self.modify()?.set_ethertype(ethertype);
         }
         let payload = match self.upper_layer {
             Some(child) => match *child {
@@ -73,12 +141,25 @@ impl<'a> LayerMutable<'a> for EtherMut {
                 LayerMut::Ipv6(ipv6) => ipv6.build()?,
                 LayerMut::Vlan(vlan) => vlan.build()?,
                 LayerMut::Arp(arp) => arp.build()?,
-                _ => panic!("child not possible"),
+                LayerMut::Raw(raw) => raw.build()?,
+                LayerMut::Custom(custom) => custom.build()?,
+                other => {
+                    return Err(Error::IllegalStacking {
+                        layer: other.kind(),
+                        lower: Self::LAYER,
+                    });
+                }
             },
             None => vec![],
         };
         self.buf.extend_from_slice(&payload);
-        Some(self.buf)
+        if let Some(trailer) = self.trailer {
+            self.buf.extend_from_slice(&trailer.buf);
+        }
+        if self.pad && self.buf.len() < Self::MIN_LEN {
+            self.buf.resize(Self::MIN_LEN, 0);
+        }
+        Ok(self.buf)
     }
 }
 
@@ -86,10 +167,15 @@ impl<'a> LayerImmutable<'a> for Ether<'a> {
     type Packet = EthernetPacket<'a>;
     type PacketMut = MutableEthernetPacket<'a>;
     type LayerMutType = EtherMut;
+    const LAYER: Layers = Layers::Ether;
 
     create_default_immutable!();
 
     fn get_layer_from_buf(buf: &'_ [u8], layer: Layers) -> Option<Layer<'_>> {
+        if layer == Layers::Trailer {
+            let len = frame_len(buf).filter(|len| *len < buf.len())?;
+            return Some(Layer::Trailer(Trailer::new(&buf[len..])));
+        }
         let eth = EthernetPacket::new(buf)?;
         let eth_size = EthernetPacket::minimum_packet_size();
         let buf = &buf[eth_size..buf.len()];
@@ -138,17 +224,8 @@ pub(super) fn get_layer_with_ether_type(
                 // Vlan::get_layer_from_buf(buf, layer)?
             }
         }
-        EtherTypes::Ptp => {
-            // log::debug!("Ignoring PTP");
-            return None;
-            // if matches!(layer, Layers::Ptp) {
-            //     Layer::Ptp(buf.to_vec())
-            // } else {
-            //     log::debug!("Ptp is ignored");
-            //     return None;
-            //     // Vlan::get_layer_from_buf(buf, layer)?
-            // }
-        }
+        EtherTypes::Ptp if layer != Layers::Raw => return None,
+        _ if layer == Layers::Raw => Layer::Raw(Raw::new(buf)),
         _ => {
             if format!("{ether_type}") == "unknown" {
                 log::debug!("Unknown EtherType: {ether_type}");
@@ -172,6 +249,9 @@ impl Display for EtherMut {
             if let Some(upper) = &self.upper_layer {
                 write!(f, " > {upper}")?;
             }
+            if let Some(trailer) = &self.trailer {
+                write!(f, " > {trailer}")?;
+            }
         }
         Ok(())
     }
@@ -193,6 +273,9 @@ impl Debug for EtherMut {
             if let Some(upper) = &self.upper_layer {
                 write!(f, " > {upper:?}")?;
             }
+            if let Some(trailer) = &self.trailer {
+                write!(f, " > {trailer}")?;
+            }
         }
         Ok(())
     }
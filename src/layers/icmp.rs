@@ -1,10 +1,17 @@
 use crate::{
-    Layer, LayerImmutable, LayerMutable, Layers, create_default_immutable, create_modify,
-    create_set_payload,
+    Error, Field, Ipv4, Ipv4Mut, Layer, LayerImmutable, LayerMut, LayerMutable, Layers, Payload,
+    PayloadMut, Result, create_add_layer, create_default_immutable, create_get_layer,
+    create_modify, create_set_payload, create_upper_layer,
 };
 use pnet::packet::icmp::{IcmpPacket, MutableIcmpPacket, checksum};
+use pnet::packet::ipv4::Ipv4Packet;
 use std::fmt::Display;
 
+mod message;
+pub use message::IcmpMessage;
+pub(super) use message::header_len;
+use message::is_error;
+
 /// Immutable representation of an icmp packet
 #[derive(Debug)]
 pub struct Icmp<'a> {
@@ -12,38 +19,163 @@ pub struct Icmp<'a> {
 }
 
 #[derive(Debug, Clone)]
-/// Mutable representation of an icmp packet
+/// Mutable representation of an icmp packet.
+/// The datagram quoted by an error message is parsed as `Ipv4Mut` upper layer, the data of
+/// all other messages is kept as `Payload`.
 pub struct IcmpMut {
-    buf: Vec<u8>,
+    /// the header as bytes, 20 bytes for timestamp messages and 8 bytes for all others
+    pub buf: Vec<u8>,
+    /// the upper layer
+    pub upper_layer: Option<Box<LayerMut>>,
+}
+
+impl IcmpMut {
+    /// Returns the typed message of the header
+    #[must_use]
+    pub fn message(&self) -> IcmpMessage {
+        let mut buf = self.buf.clone();
+        let len = header_len(buf.first().copied().unwrap_or_default());
+        if buf.len() < len {
+            buf.resize(len, 0);
+        }
+        IcmpMessage::parse(&buf)
+    }
+
+    /// Replaces the header with the message, the upper layer is kept
+    pub fn set_message(&mut self, message: &IcmpMessage) {
+        self.buf = message.write();
+    }
+}
+
+/// Returns true if the data of the message is a datagram quoted by an error message which is
+/// parsed as `Ipv4Mut`, i.e. at least its IPv4 header is complete
+pub(super) fn quotes_ipv4(icmp_type: u8, data: &[u8]) -> bool {
+    is_error(icmp_type)
+        && Ipv4Packet::new(data).is_some_and(|ipv4| super::ipv4::header_len(&ipv4) <= data.len())
+}
+
+/// Returns the quoted datagram as it is without updating lengths or checksums, because the
+/// quoted datagram is usually truncated after the first 8 bytes of its payload.
+fn quoted_bytes(layer: &LayerMut) -> Result<Vec<u8>> {
+    let (buf, upper_layer) = match layer {
+        LayerMut::Ipv4(ipv4) => (&ipv4.buf, &ipv4.upper_layer),
+        LayerMut::Udp(udp) => (&udp.buf, &udp.upper_layer),
+        LayerMut::Tcp(tcp) => (&tcp.buf, &tcp.upper_layer),
+        LayerMut::Icmp(icmp) => (&icmp.buf, &icmp.upper_layer),
+        LayerMut::Payload(payload) => return Ok(payload.buf.clone()),
+        LayerMut::Raw(raw) => return Ok(raw.buf.clone()),
+        other => {
+            return Err(Error::IllegalStacking {
+                layer: other.kind(),
+                lower: Layers::Icmp,
+            });
+        }
+    };
+    let mut buf = buf.clone();
+    if let Some(upper) = upper_layer {
+        buf.extend_from_slice(&quoted_bytes(upper)?);
+    }
+    Ok(buf)
 }
 
 impl<'a> LayerMutable<'a> for IcmpMut {
     type PacketMut = MutableIcmpPacket<'a>;
+    const LAYER: Layers = Layers::Icmp;
+    const FIELDS: &'static [Field] = &[
+        Field::int("type", 0, 8),
+        Field::int("code", 8, 8),
+        Field::int("chksum", 16, 16),
+        Field::int("id", 32, 16),
+        Field::int("seq", 48, 16),
+        Field::int("ts_ori", 64, 32),
+        Field::int("ts_rx", 96, 32),
+        Field::int("ts_tx", 128, 32),
+    ];
     fn new() -> Self {
         Self {
-            buf: vec![0; IcmpPacket::minimum_packet_size()],
+            buf: vec![0; 8],
+            upper_layer: None,
         }
     }
     create_modify!();
+    create_upper_layer!();
     create_set_payload!();
+    create_add_layer!(Payload, Ipv4; {});
+    create_get_layer!(Payload, Ipv4);
 
-    fn from_buf(buf: Vec<u8>) -> Option<Self> {
-        Some(Self { buf })
+    fn update_header(&mut self, field: &Field) {
+        if field.name != "type" {
+            return;
+        }
+        self.buf
+            .resize(header_len(self.buf.first().copied().unwrap_or_default()), 0);
     }
 
-    fn build(mut self) -> Option<Vec<u8>> {
-        let mut chksum = None;
-        if let Some(packet) = IcmpPacket::new(&self.buf) {
-            chksum = Some(checksum(&packet));
+    fn from_buf(mut buf: Vec<u8>) -> Result<Self> {
+        let icmp_type = *buf.first().ok_or(Error::Truncated {
+            layer: Self::LAYER,
+            offset: 0,
+        })?;
+        let len = header_len(icmp_type);
+        if buf.len() < len {
+            return Err(Error::Truncated {
+                layer: Self::LAYER,
+                offset: 0,
+            });
         }
-        if let Some(checksum) = chksum
-            && let Some(mut pkt) = self.modify()
-        {
-            pkt.set_checksum(checksum);
-            return Some(self.buf);
+        let data = buf.split_off(len);
+
+        let upper_layer = if data.is_empty() {
+            None
+        } else if quotes_ipv4(icmp_type, &data) {
+            log::debug!("Icmp quotes a datagram");
+            Some(Box::new(LayerMut::Ipv4(Ipv4Mut::from_quoted(data)?)))
+        } else {
+            Some(Box::new(LayerMut::Payload(PayloadMut::from_buf(data)?)))
+        };
+
+        Ok(Self { buf, upper_layer })
+    }
+
+    /// A quoted `Ipv4Mut` with a total length is written as it is, only a newly crafted
+    /// datagram without a total length is built.
+    fn build(mut self) -> Result<Vec<u8>> {
+        let len = header_len(self.buf.first().copied().unwrap_or_default());
+        if self.buf.len() < len {
+            self.buf.resize(len, 0);
         }
-        None
-        // Some(self.buf)
+        let data = match self.upper_layer {
+            Some(child) => match *child {
+                LayerMut::Payload(payload) => payload.build()?,
+                LayerMut::Ipv4(ipv4)
+                    if Ipv4Packet::new(&ipv4.buf).is_none_or(|p| p.get_total_length() == 0) =>
+                {
+                    ipv4.build()?
+                }
+                quoted @ LayerMut::Ipv4(_) => quoted_bytes(&quoted)?,
+                other => {
+                    return Err(Error::IllegalStacking {
+                        layer: other.kind(),
+                        lower: Self::LAYER,
+                    });
+                }
+            },
+            None => vec![],
+        };
+        self.buf.extend_from_slice(&data);
+
+        let packet = IcmpPacket::new(&self.buf).ok_or(Error::Truncated {
+            layer: Self::LAYER,
+            offset: 0,
+        })?;
+        let checksum = checksum(&packet);
+        Self::PacketMut::new(self.buf.as_mut())
+            .ok_or(Error::Truncated {
+                layer: Self::LAYER,
+                offset: 0,
+            })?
+            .set_checksum(checksum);
+        Ok(self.buf)
     }
 }
 
@@ -51,10 +183,23 @@ impl<'a> LayerImmutable<'a> for Icmp<'a> {
     type Packet = IcmpPacket<'a>;
     type PacketMut = MutableIcmpPacket<'a>;
     type LayerMutType = IcmpMut;
+    const LAYER: Layers = Layers::Icmp;
 
     create_default_immutable!();
-    fn get_layer_from_buf(_buf: &'_ [u8], _layer: Layers) -> Option<Layer<'_>> {
-        None
+    fn get_layer_from_buf(buf: &'_ [u8], layer: Layers) -> Option<Layer<'_>> {
+        let icmp_type = *buf.first()?;
+        let buf = buf.get(header_len(icmp_type)..).filter(|b| !b.is_empty())?;
+        if quotes_ipv4(icmp_type, buf) {
+            if matches!(layer, Layers::Ipv4) {
+                Some(Layer::Ipv4(Ipv4::new(buf)))
+            } else {
+                Ipv4::get_layer_from_buf(buf, layer)
+            }
+        } else if matches!(layer, Layers::Payload) {
+            Some(Layer::Payload(Payload::new(buf)))
+        } else {
+            None
+        }
     }
 }
 
@@ -62,7 +207,152 @@ impl Display for IcmpMut {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         if let Some(eth) = IcmpPacket::new(&self.buf) {
             write!(f, "Icmp (type: {:?})", eth.get_icmp_type())?;
+            if let Some(upper) = &self.upper_layer {
+                write!(f, " > {upper}")?;
+            }
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Ether, EtherMut};
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn test_icmp_quoted() {
+        // Ether > Ipv4 > Icmp port unreachable > quoted Ipv4 (total length 60) > Udp (length 40)
+        let packet = hex::decode(concat!(
+            "3cce333333333cce3333333408004500003812340000400100000a0000020a000001",
+            "0303fc4300000000",
+            "4500003c43210000011100000a0000010a000002",
+            "d43100350028abcd"
+        ))
+        .expect("failed");
+
+        let mut ether = EtherMut::from_buf(packet.clone()).expect("could not parse");
+        let Some(LayerMut::Icmp(icmp)) = ether.get_layer(&Layers::Icmp) else {
+            panic!("no icmp layer");
+        };
+        assert_eq!(
+            icmp.message(),
+            IcmpMessage::DestinationUnreachable {
+                code: 3,
+                unused: [0; 2],
+                next_hop_mtu: 0
+            }
+        );
+        let Some(LayerMut::Ipv4(quoted)) = icmp.get_layer(&Layers::Ipv4) else {
+            panic!("no quoted ipv4 layer");
+        };
+        let pkt = quoted.modify().expect("ipv4");
+        assert_eq!(pkt.get_ttl(), 1);
+        assert_eq!(pkt.get_source(), Ipv4Addr::new(10, 0, 0, 1));
+        assert!(matches!(
+            ether.get_layer(&Layers::Udp),
+            Some(LayerMut::Udp(udp)) if udp.buf[..2] == [0xd4, 0x31]
+        ));
+
+        // the quoted datagram is written as it is
+        let build = ether.build().expect("could not build");
+        assert_eq!(&build[42..], &packet[42..]);
+        assert_eq!(
+            IcmpPacket::new(&build[34..]).map(|p| p.get_checksum()),
+            IcmpPacket::new(&build[34..]).map(|p| checksum(&p))
+        );
+
+        assert!(matches!(
+            Ether::get_layer_from_buf(&packet, Layers::Udp),
+            Some(Layer::Udp(udp)) if udp.get_buf() == &packet[62..]
+        ));
+    }
+
+    #[test]
+    fn test_icmp_quoted_truncated() {
+        // the quote of a TCP probe ends after the ports and the sequence number
+        let probe = LayerMut::parse("Ipv4(src: 10.0.0.1, dst: 10.0.0.2) / Tcp(sport: 1, dport: 2)")
+            .and_then(LayerMut::build)
+            .expect("could not build");
+        let mut icmp = IcmpMut::new();
+        icmp.set_message(&IcmpMessage::DestinationUnreachable {
+            code: 3,
+            unused: [0; 2],
+            next_hop_mtu: 0,
+        });
+        icmp.add(LayerMut::Payload(PayloadMut {
+            buf: probe[..28].to_vec(),
+        }))
+        .expect("could not add");
+        let bytes = icmp.build().expect("could not build");
+
+        let mut parsed = IcmpMut::from_buf(bytes.clone()).expect("could not parse");
+        let Some(LayerMut::Ipv4(quoted)) = parsed.get_layer(&Layers::Ipv4) else {
+            panic!("no quoted ipv4 layer");
+        };
+        assert!(matches!(
+            quoted.upper_layer.as_deref(),
+            Some(LayerMut::Payload(payload)) if payload.buf == probe[20..28]
+        ));
+
+        // the parsed chain can be crafted again
+        assert!(
+            Ipv4Mut::new()
+                .add(LayerMut::Payload(PayloadMut {
+                    buf: probe[20..28].to_vec(),
+                }))
+                .is_ok()
+        );
+        assert_eq!(parsed.build().expect("could not build"), bytes);
+    }
+
+    #[test]
+    fn test_icmp_messages() {
+        let mut icmp = IcmpMut::new();
+        let timestamp = IcmpMessage::Timestamp {
+            code: 0,
+            identifier: 1,
+            sequence: 2,
+            originate: 3,
+            receive: 0,
+            transmit: 0,
+        };
+        icmp.set_message(&timestamp);
+        let build = icmp.build().expect("could not build");
+        assert_eq!(build.len(), 20);
+
+        let icmp = IcmpMut::from_buf(build).expect("could not parse");
+        assert_eq!(icmp.message(), timestamp);
+        assert!(icmp.upper_layer.is_none());
+
+        let icmp = IcmpMut::from_buf(hex::decode("0800f7fc00010002abcd").expect("failed"))
+            .expect("could not parse");
+        assert_eq!(
+            icmp.message(),
+            IcmpMessage::EchoRequest {
+                code: 0,
+                identifier: 1,
+                sequence: 2
+            }
+        );
+
+        // codes and unused bytes which are not zero are kept
+        for header in [
+            "0801000000010002",
+            "0b00000000000001",
+            "0303000012340000",
+            "0c00000004010203",
+        ] {
+            let mut icmp =
+                IcmpMut::from_buf(hex::decode(header).expect("failed")).expect("could not parse");
+            let buf = icmp.buf.clone();
+            icmp.set_message(&icmp.message());
+            assert_eq!(icmp.buf, buf, "{header}");
+        }
+        assert!(matches!(
+            icmp.upper_layer.as_deref(),
+            Some(LayerMut::Payload(payload)) if payload.buf == [0xab, 0xcd]
+        ));
+    }
+}
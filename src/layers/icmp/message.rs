@@ -0,0 +1,265 @@
+use std::net::Ipv4Addr;
+
+/// Typed representation of the ICMP header.
+/// The data of an echo message or the quoted datagram of an error message are not part of the
+/// message, they are the upper layer of the `IcmpMut`. The checksum is calculated when the
+/// packet is built. Codes and unused bytes are kept even if they are not zero, so writing a
+/// parsed message does not change the header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IcmpMessage {
+    /// Echo reply (0)
+    EchoReply {
+        /// The message code, 0 in a valid message
+        code: u8,
+        /// Identifier of the request
+        identifier: u16,
+        /// Sequence number of the request
+        sequence: u16,
+    },
+    /// Destination unreachable (3)
+    DestinationUnreachable {
+        /// Reason why the destination is unreachable e.g. 3 for port unreachable
+        code: u8,
+        /// The unused bytes in front of the MTU, zero in a valid message
+        unused: [u8; 2],
+        /// MTU of the next-hop network if the code is 4 (fragmentation needed)
+        next_hop_mtu: u16,
+    },
+    /// Redirect (5)
+    Redirect {
+        /// 0: network, 1: host, 2: type of service and network, 3: type of service and host
+        code: u8,
+        /// The gateway the traffic should be sent to
+        gateway: Ipv4Addr,
+    },
+    /// Echo request (8)
+    EchoRequest {
+        /// The message code, 0 in a valid message
+        code: u8,
+        /// Identifier to match the reply
+        identifier: u16,
+        /// Sequence number to match the reply
+        sequence: u16,
+    },
+    /// Time exceeded (11)
+    TimeExceeded {
+        /// 0: TTL exceeded in transit, 1: fragment reassembly time exceeded
+        code: u8,
+        /// The unused bytes, zero in a valid message
+        unused: [u8; 4],
+    },
+    /// Parameter problem (12)
+    ParameterProblem {
+        /// 0: pointer indicates the error, 1: missing option, 2: bad length
+        code: u8,
+        /// Offset of the error in the quoted datagram
+        pointer: u8,
+        /// The unused bytes after the pointer, zero in a valid message
+        unused: [u8; 3],
+    },
+    /// Timestamp (13)
+    Timestamp {
+        /// The message code, 0 in a valid message
+        code: u8,
+        /// Identifier to match the reply
+        identifier: u16,
+        /// Sequence number to match the reply
+        sequence: u16,
+        /// Time the sender last touched the message in milliseconds since midnight UT
+        originate: u32,
+        /// Time the echoer first touched the message
+        receive: u32,
+        /// Time the echoer last touched the message
+        transmit: u32,
+    },
+    /// Timestamp reply (14)
+    TimestampReply {
+        /// The message code, 0 in a valid message
+        code: u8,
+        /// Identifier of the request
+        identifier: u16,
+        /// Sequence number of the request
+        sequence: u16,
+        /// Originate timestamp of the request
+        originate: u32,
+        /// Time the echoer first touched the message
+        receive: u32,
+        /// Time the echoer last touched the message
+        transmit: u32,
+    },
+    /// Message with a type not known by `pnet_layers`
+    Unknown {
+        /// The message type
+        icmp_type: u8,
+        /// The message code
+        code: u8,
+        /// The four bytes following the checksum
+        rest: [u8; 4],
+    },
+}
+
+/// Length of the ICMP header of the message type
+pub(crate) fn header_len(icmp_type: u8) -> usize {
+    match icmp_type {
+        13 | 14 => 20,
+        _ => 8,
+    }
+}
+
+/// Returns true if the message type quotes the datagram which caused the error
+pub(crate) fn is_error(icmp_type: u8) -> bool {
+    matches!(icmp_type, 3 | 4 | 5 | 11 | 12)
+}
+
+impl IcmpMessage {
+    /// Returns the message type
+    #[must_use]
+    pub fn icmp_type(&self) -> u8 {
+        match self {
+            Self::EchoReply { .. } => 0,
+            Self::DestinationUnreachable { .. } => 3,
+            Self::Redirect { .. } => 5,
+            Self::EchoRequest { .. } => 8,
+            Self::TimeExceeded { .. } => 11,
+            Self::ParameterProblem { .. } => 12,
+            Self::Timestamp { .. } => 13,
+            Self::TimestampReply { .. } => 14,
+            Self::Unknown { icmp_type, .. } => *icmp_type,
+        }
+    }
+
+    /// Returns the message code
+    #[must_use]
+    pub fn code(&self) -> u8 {
+        match self {
+            Self::EchoReply { code, .. }
+            | Self::DestinationUnreachable { code, .. }
+            | Self::Redirect { code, .. }
+            | Self::EchoRequest { code, .. }
+            | Self::TimeExceeded { code, .. }
+            | Self::ParameterProblem { code, .. }
+            | Self::Timestamp { code, .. }
+            | Self::TimestampReply { code, .. }
+            | Self::Unknown { code, .. } => *code,
+        }
+    }
+
+    /// Returns the header in its wire format with a zero checksum
+    pub(crate) fn write(&self) -> Vec<u8> {
+        let mut buf = vec![self.icmp_type(), self.code(), 0, 0];
+        match self {
+            Self::EchoReply {
+                identifier,
+                sequence,
+                ..
+            }
+            | Self::EchoRequest {
+                identifier,
+                sequence,
+                ..
+            } => {
+                buf.extend_from_slice(&identifier.to_be_bytes());
+                buf.extend_from_slice(&sequence.to_be_bytes());
+            }
+            Self::DestinationUnreachable {
+                unused,
+                next_hop_mtu,
+                ..
+            } => {
+                buf.extend_from_slice(unused);
+                buf.extend_from_slice(&next_hop_mtu.to_be_bytes());
+            }
+            Self::Redirect { gateway, .. } => buf.extend_from_slice(&gateway.octets()),
+            Self::TimeExceeded { unused, .. } => buf.extend_from_slice(unused),
+            Self::ParameterProblem {
+                pointer, unused, ..
+            } => {
+                buf.push(*pointer);
+                buf.extend_from_slice(unused);
+            }
+            Self::Timestamp {
+                identifier,
+                sequence,
+                originate,
+                receive,
+                transmit,
+                ..
+            }
+            | Self::TimestampReply {
+                identifier,
+                sequence,
+                originate,
+                receive,
+                transmit,
+                ..
+            } => {
+                buf.extend_from_slice(&identifier.to_be_bytes());
+                buf.extend_from_slice(&sequence.to_be_bytes());
+                buf.extend_from_slice(&originate.to_be_bytes());
+                buf.extend_from_slice(&receive.to_be_bytes());
+                buf.extend_from_slice(&transmit.to_be_bytes());
+            }
+            Self::Unknown { rest, .. } => buf.extend_from_slice(rest),
+        }
+        buf
+    }
+
+    /// Parses the message from the ICMP header. The header must be at least as long as
+    /// `header_len` returns for its type.
+    pub(crate) fn parse(buf: &[u8]) -> Self {
+        let u16_at = |i: usize| u16::from_be_bytes([buf[i], buf[i + 1]]);
+        let u32_at = |i: usize| u32::from_be_bytes([buf[i], buf[i + 1], buf[i + 2], buf[i + 3]]);
+        let (icmp_type, code) = (buf[0], buf[1]);
+        match icmp_type {
+            0 => Self::EchoReply {
+                code,
+                identifier: u16_at(4),
+                sequence: u16_at(6),
+            },
+            3 => Self::DestinationUnreachable {
+                code,
+                unused: [buf[4], buf[5]],
+                next_hop_mtu: u16_at(6),
+            },
+            5 => Self::Redirect {
+                code,
+                gateway: Ipv4Addr::new(buf[4], buf[5], buf[6], buf[7]),
+            },
+            8 => Self::EchoRequest {
+                code,
+                identifier: u16_at(4),
+                sequence: u16_at(6),
+            },
+            11 => Self::TimeExceeded {
+                code,
+                unused: [buf[4], buf[5], buf[6], buf[7]],
+            },
+            12 => Self::ParameterProblem {
+                code,
+                pointer: buf[4],
+                unused: [buf[5], buf[6], buf[7]],
+            },
+            13 => Self::Timestamp {
+                code,
+                identifier: u16_at(4),
+                sequence: u16_at(6),
+                originate: u32_at(8),
+                receive: u32_at(12),
+                transmit: u32_at(16),
+            },
+            14 => Self::TimestampReply {
+                code,
+                identifier: u16_at(4),
+                sequence: u16_at(6),
+                originate: u32_at(8),
+                receive: u32_at(12),
+                transmit: u32_at(16),
+            },
+            _ => Self::Unknown {
+                icmp_type,
+                code,
+                rest: [buf[4], buf[5], buf[6], buf[7]],
+            },
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use crate::{
+    Error, Field, FieldKind, Layer, LayerImmutable, LayerMutable, Layers, Result,
+    create_default_immutable, create_modify, create_set_payload,
+};
+use pnet::packet::icmpv6::{Icmpv6Packet, MutableIcmpv6Packet, checksum};
+use std::fmt::{Debug, Display};
+use std::net::Ipv6Addr;
+
+mod message;
+mod options;
+pub use message::{Icmpv6Message, MldRecord};
+pub use options::NdpOption;
+
+/// Immutable representation of an icmpv6 packet
+#[derive(Debug)]
+pub struct Icmpv6<'a> {
+    buf: &'a [u8],
+}
+
+#[derive(Clone)]
+/// Mutable representation of an icmpv6 packet
+pub struct Icmpv6Mut {
+    /// the complete message as bytes
+    pub buf: Vec<u8>,
+}
+
+impl Icmpv6Mut {
+    /// Returns the typed message. Malformed messages are returned as `Icmpv6Message::Unknown`.
+    #[must_use]
+    pub fn message(&self) -> Icmpv6Message {
+        Icmpv6Message::parse(&self.buf)
+    }
+
+    /// Replaces the type, code and body of the packet with the message
+    pub fn set_message(&mut self, message: &Icmpv6Message) {
+        self.buf = message.write();
+    }
+
+    /// Builds the packet with the checksum including the IPv6 pseudo-header
+    pub(crate) fn build_from_ipv6(mut self, saddr: Ipv6Addr, daddr: Ipv6Addr) -> Result<Vec<u8>> {
+        let packet = Icmpv6Packet::new(&self.buf).ok_or(Error::Truncated {
+            layer: Self::LAYER,
+            offset: 0,
+        })?;
+        let checksum = checksum(&packet, &saddr, &daddr);
+        self.modify()?.set_checksum(checksum);
+        Ok(self.buf)
+    }
+}
+
+impl<'a> LayerMutable<'a> for Icmpv6Mut {
+    type PacketMut = MutableIcmpv6Packet<'a>;
+    const LAYER: Layers = Layers::Icmpv6;
+    const FIELDS: &'static [Field] = &[
+        Field::int("type", 0, 8),
+        Field::int("code", 8, 8),
+        Field::int("chksum", 16, 16),
+        Field::new("body", 32, FieldKind::Bytes),
+    ];
+    fn new() -> Self {
+        Self {
+            buf: vec![0; Icmpv6Packet::minimum_packet_size()],
+        }
+    }
+    create_modify!();
+    create_set_payload!();
+
+    fn from_buf(buf: Vec<u8>) -> Result<Self> {
+        if buf.len() < Icmpv6Packet::minimum_packet_size() {
+            return Err(Error::Truncated {
+                layer: Self::LAYER,
+                offset: 0,
+            });
+        }
+        Ok(Self { buf })
+    }
+
+    /// The checksum depends on the IPv6 addresses, so it is only calculated when the packet is
+    /// built from the `Ipv6Mut` layer.
+    fn build(self) -> Result<Vec<u8>> {
+        Ok(self.buf)
+    }
+}
+
+impl<'a> LayerImmutable<'a> for Icmpv6<'a> {
+    type Packet = Icmpv6Packet<'a>;
+    type PacketMut = MutableIcmpv6Packet<'a>;
+    type LayerMutType = Icmpv6Mut;
+    const LAYER: Layers = Layers::Icmpv6;
+
+    create_default_immutable!();
+    fn get_layer_from_buf(_buf: &'_ [u8], _layer: Layers) -> Option<Layer<'_>> {
+        None
+    }
+}
+
+impl Display for Icmpv6Mut {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(eth) = Icmpv6Packet::new(&self.buf) {
+            write!(f, "Icmpv6 (type: {:?})", eth.get_icmpv6_type())?;
+        }
+        Ok(())
+    }
+}
+
+impl Debug for Icmpv6Mut {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Icmpv6 ({:02x?})", self.buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{HopByHopMut, Ipv6ExtOption, Ipv6Mut, LayerMut};
+    use pnet::util::MacAddr;
+
+    #[test]
+    fn test_icmpv6_ndp() {
+        let src: Ipv6Addr = "fe80::1".parse().expect("addr");
+        let dst: Ipv6Addr = "ff02::1:ff00:2".parse().expect("addr");
+        let solicitation = Icmpv6Message::NeighborSolicitation {
+            target: "fe80::2".parse().expect("addr"),
+            options: vec![NdpOption::SourceLinkLayerAddr(MacAddr::new(
+                0x3c, 0xce, 0x33, 0x33, 0x33, 0x33,
+            ))],
+        };
+
+        let mut icmpv6 = Icmpv6Mut::new();
+        icmpv6.set_message(&solicitation);
+
+        let mut ipv6 = Ipv6Mut::new();
+        {
+            let mut pkt = ipv6.modify().expect("ipv6");
+            pkt.set_source(src);
+            pkt.set_destination(dst);
+            pkt.set_hop_limit(255);
+        }
+        ipv6.add(LayerMut::Icmpv6(icmpv6)).expect("icmpv6");
+        let build = ipv6.build().expect("could not build");
+
+        assert_eq!(build[6], 58);
+        assert_eq!(build.len(), 40 + 32);
+        let icmpv6 = Icmpv6Packet::new(&build[40..]).expect("icmpv6");
+        assert_eq!(icmpv6.get_checksum(), checksum(&icmpv6, &src, &dst));
+
+        let mut parsed = Ipv6Mut::from_buf(build.clone()).expect("could not parse");
+        assert!(matches!(
+            parsed.get_layer(&Layers::Icmpv6),
+            Some(LayerMut::Icmpv6(icmpv6)) if icmpv6.message() == solicitation
+        ));
+        assert_eq!(build, parsed.build().expect("could not build"));
+    }
+
+    #[test]
+    fn test_icmpv6_mld() {
+        // MLDv2 report with one record behind a Hop-by-Hop header with router alert
+        let report = Icmpv6Message::MldV2Report {
+            records: vec![MldRecord {
+                record_type: 4,
+                multicast: "ff02::fb".parse().expect("addr"),
+                sources: vec![],
+                aux_data: vec![],
+            }],
+        };
+        let mut icmpv6 = Icmpv6Mut::new();
+        icmpv6.set_message(&report);
+        assert_eq!(icmpv6.buf.len(), 28);
+
+        let mut hop = HopByHopMut::new();
+        hop.set_options(&[Ipv6ExtOption::RouterAlert(0)])
+            .expect("option");
+        let mut ipv6 = Ipv6Mut::new();
+        ipv6.add(LayerMut::HopByHop(hop)).expect("hop");
+        ipv6.add(LayerMut::Icmpv6(icmpv6)).expect("icmpv6");
+        let build = ipv6.build().expect("could not build");
+        assert_eq!(build[40], 58);
+
+        let mut parsed = Ipv6Mut::from_buf(build).expect("could not parse");
+        assert!(matches!(
+            parsed.get_layer(&Layers::Icmpv6),
+            Some(LayerMut::Icmpv6(icmpv6)) if icmpv6.message() == report
+        ));
+
+        // malformed messages are kept as they are
+        let icmpv6 = Icmpv6Mut::from_buf(vec![135, 0, 0, 0, 1]).expect("could not parse");
+        assert_eq!(
+            icmpv6.message(),
+            Icmpv6Message::Unknown {
+                icmp_type: 135,
+                code: 0,
+                body: vec![1]
+            }
+        );
+    }
+}
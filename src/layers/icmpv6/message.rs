@@ -0,0 +1,466 @@
+use super::options::{NdpOption, parse_options, write_options};
+use std::net::Ipv6Addr;
+
+/// Multicast address record of a `MLDv2` report as defined in RFC 3810
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MldRecord {
+    /// Record type e.g. 1 for `MODE_IS_INCLUDE` or 4 for `CHANGE_TO_EXCLUDE_MODE`
+    pub record_type: u8,
+    /// The multicast address the record belongs to
+    pub multicast: Ipv6Addr,
+    /// The source addresses
+    pub sources: Vec<Ipv6Addr>,
+    /// Auxiliary data, the length must be a multiple of 4 bytes
+    pub aux_data: Vec<u8>,
+}
+
+/// Typed representation of an `ICMPv6` message.
+/// The checksum is not part of the message, it is calculated when the packet is built.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Icmpv6Message {
+    /// Destination unreachable (1)
+    DestinationUnreachable {
+        /// Reason why the destination is unreachable
+        code: u8,
+        /// As much of the invoking packet as possible
+        invoking: Vec<u8>,
+    },
+    /// Packet too big (2)
+    PacketTooBig {
+        /// MTU of the next-hop link
+        mtu: u32,
+        /// As much of the invoking packet as possible
+        invoking: Vec<u8>,
+    },
+    /// Time exceeded (3)
+    TimeExceeded {
+        /// 0: hop limit exceeded, 1: fragment reassembly time exceeded
+        code: u8,
+        /// As much of the invoking packet as possible
+        invoking: Vec<u8>,
+    },
+    /// Parameter problem (4)
+    ParameterProblem {
+        /// Kind of the problem
+        code: u8,
+        /// Offset of the error in the invoking packet
+        pointer: u32,
+        /// As much of the invoking packet as possible
+        invoking: Vec<u8>,
+    },
+    /// Echo request (128)
+    EchoRequest {
+        /// Identifier to match the reply
+        identifier: u16,
+        /// Sequence number to match the reply
+        sequence: u16,
+        /// Arbitrary data
+        data: Vec<u8>,
+    },
+    /// Echo reply (129)
+    EchoReply {
+        /// Identifier of the request
+        identifier: u16,
+        /// Sequence number of the request
+        sequence: u16,
+        /// Data of the request
+        data: Vec<u8>,
+    },
+    /// Multicast listener query (130) version 1
+    MldQuery {
+        /// Maximum response delay in milliseconds
+        max_response_delay: u16,
+        /// Multicast address, unspecified for a general query
+        group: Ipv6Addr,
+    },
+    /// Multicast listener report (131) version 1
+    MldReport {
+        /// Multicast address
+        group: Ipv6Addr,
+    },
+    /// Multicast listener done (132)
+    MldDone {
+        /// Multicast address
+        group: Ipv6Addr,
+    },
+    /// Router solicitation (133)
+    RouterSolicitation {
+        /// Neighbor Discovery options
+        options: Vec<NdpOption>,
+    },
+    /// Router advertisement (134)
+    RouterAdvertisement {
+        /// Default hop limit, 0 if unspecified
+        hop_limit: u8,
+        /// Managed (M), other (O) and further flags
+        flags: u8,
+        /// Lifetime as default router in seconds
+        lifetime: u16,
+        /// Reachable time in milliseconds
+        reachable_time: u32,
+        /// Retransmission timer in milliseconds
+        retrans_timer: u32,
+        /// Neighbor Discovery options
+        options: Vec<NdpOption>,
+    },
+    /// Neighbor solicitation (135)
+    NeighborSolicitation {
+        /// Address which should be resolved
+        target: Ipv6Addr,
+        /// Neighbor Discovery options
+        options: Vec<NdpOption>,
+    },
+    /// Neighbor advertisement (136)
+    NeighborAdvertisement {
+        /// Router (0x80), solicited (0x40) and override (0x20) flag
+        flags: u8,
+        /// Address which is advertised
+        target: Ipv6Addr,
+        /// Neighbor Discovery options
+        options: Vec<NdpOption>,
+    },
+    /// Redirect (137)
+    Redirect {
+        /// The better first hop
+        target: Ipv6Addr,
+        /// Destination which is redirected
+        destination: Ipv6Addr,
+        /// Neighbor Discovery options
+        options: Vec<NdpOption>,
+    },
+    /// Multicast listener query (130) version 2
+    MldV2Query {
+        /// Maximum response code
+        max_response_code: u16,
+        /// Multicast address, unspecified for a general query
+        group: Ipv6Addr,
+        /// Suppress router-side processing (S) flag and querier's robustness variable (QRV)
+        flags: u8,
+        /// Querier's query interval code
+        qqic: u8,
+        /// Source addresses
+        sources: Vec<Ipv6Addr>,
+    },
+    /// Multicast listener report (143) version 2
+    MldV2Report {
+        /// Multicast address records
+        records: Vec<MldRecord>,
+    },
+    /// Message with a type not known by `pnet_layers` or a malformed message
+    Unknown {
+        /// The message type
+        icmp_type: u8,
+        /// The message code
+        code: u8,
+        /// Message body after the checksum
+        body: Vec<u8>,
+    },
+}
+
+impl MldRecord {
+    fn write(&self, buf: &mut Vec<u8>) {
+        #[allow(clippy::cast_possible_truncation)]
+        buf.extend_from_slice(&[self.record_type, (self.aux_data.len() / 4) as u8]);
+        #[allow(clippy::cast_possible_truncation)]
+        buf.extend_from_slice(&(self.sources.len() as u16).to_be_bytes());
+        buf.extend_from_slice(&self.multicast.octets());
+        for source in &self.sources {
+            buf.extend_from_slice(&source.octets());
+        }
+        buf.extend_from_slice(&self.aux_data);
+    }
+}
+
+fn u16_at(buf: &[u8], i: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(buf.get(i..i + 2)?.try_into().ok()?))
+}
+
+fn u32_at(buf: &[u8], i: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(buf.get(i..i + 4)?.try_into().ok()?))
+}
+
+fn addr_at(buf: &[u8], i: usize) -> Option<Ipv6Addr> {
+    Some(Ipv6Addr::from(
+        <[u8; 16]>::try_from(buf.get(i..i + 16)?).ok()?,
+    ))
+}
+
+fn addrs(buf: &[u8]) -> Option<Vec<Ipv6Addr>> {
+    if !buf.len().is_multiple_of(16) {
+        return None;
+    }
+    (0..buf.len())
+        .step_by(16)
+        .map(|i| addr_at(buf, i))
+        .collect()
+}
+
+fn parse_records(mut buf: &[u8], count: u16) -> Option<Vec<MldRecord>> {
+    let mut records = vec![];
+    for _ in 0..count {
+        let aux_len = usize::from(*buf.get(1)?) * 4;
+        let sources = usize::from(u16_at(buf, 2)?) * 16;
+        let len = 20 + sources + aux_len;
+        records.push(MldRecord {
+            record_type: buf[0],
+            multicast: addr_at(buf, 4)?,
+            sources: addrs(buf.get(20..20 + sources)?)?,
+            aux_data: buf.get(20 + sources..len)?.to_vec(),
+        });
+        buf = &buf[len..];
+    }
+    buf.is_empty().then_some(records)
+}
+
+impl Icmpv6Message {
+    /// Returns the message type
+    #[must_use]
+    pub fn icmp_type(&self) -> u8 {
+        match self {
+            Self::DestinationUnreachable { .. } => 1,
+            Self::PacketTooBig { .. } => 2,
+            Self::TimeExceeded { .. } => 3,
+            Self::ParameterProblem { .. } => 4,
+            Self::EchoRequest { .. } => 128,
+            Self::EchoReply { .. } => 129,
+            Self::MldQuery { .. } | Self::MldV2Query { .. } => 130,
+            Self::MldReport { .. } => 131,
+            Self::MldDone { .. } => 132,
+            Self::RouterSolicitation { .. } => 133,
+            Self::RouterAdvertisement { .. } => 134,
+            Self::NeighborSolicitation { .. } => 135,
+            Self::NeighborAdvertisement { .. } => 136,
+            Self::Redirect { .. } => 137,
+            Self::MldV2Report { .. } => 143,
+            Self::Unknown { icmp_type, .. } => *icmp_type,
+        }
+    }
+
+    /// Returns the message code
+    #[must_use]
+    pub fn code(&self) -> u8 {
+        match self {
+            Self::DestinationUnreachable { code, .. }
+            | Self::TimeExceeded { code, .. }
+            | Self::ParameterProblem { code, .. }
+            | Self::Unknown { code, .. } => *code,
+            _ => 0,
+        }
+    }
+
+    /// Returns the message body following the checksum
+    #[allow(clippy::too_many_lines)]
+    fn body(&self) -> Vec<u8> {
+        let mut body = vec![];
+        match self {
+            Self::DestinationUnreachable { invoking, .. } | Self::TimeExceeded { invoking, .. } => {
+                body = [&[0; 4][..], invoking].concat();
+            }
+            Self::PacketTooBig {
+                mtu: pointer,
+                invoking,
+            }
+            | Self::ParameterProblem {
+                pointer, invoking, ..
+            } => body = [&pointer.to_be_bytes()[..], invoking].concat(),
+            Self::EchoRequest {
+                identifier,
+                sequence,
+                data,
+            }
+            | Self::EchoReply {
+                identifier,
+                sequence,
+                data,
+            } => {
+                body = [&identifier.to_be_bytes()[..], &sequence.to_be_bytes(), data].concat();
+            }
+            Self::MldQuery {
+                max_response_delay,
+                group,
+            } => {
+                body = [
+                    &max_response_delay.to_be_bytes()[..],
+                    &[0; 2],
+                    &group.octets(),
+                ]
+                .concat();
+            }
+            Self::MldReport { group } | Self::MldDone { group } => {
+                body = [&[0; 4][..], &group.octets()].concat();
+            }
+            Self::RouterSolicitation { options } => {
+                body.extend_from_slice(&[0; 4]);
+                write_options(options, &mut body);
+            }
+            Self::RouterAdvertisement {
+                hop_limit,
+                flags,
+                lifetime,
+                reachable_time,
+                retrans_timer,
+                options,
+            } => {
+                body = [
+                    &[*hop_limit, *flags][..],
+                    &lifetime.to_be_bytes(),
+                    &reachable_time.to_be_bytes(),
+                    &retrans_timer.to_be_bytes(),
+                ]
+                .concat();
+                write_options(options, &mut body);
+            }
+            Self::NeighborSolicitation { target, options } => {
+                body = [&[0; 4][..], &target.octets()].concat();
+                write_options(options, &mut body);
+            }
+            Self::NeighborAdvertisement {
+                flags,
+                target,
+                options,
+            } => {
+                body = [&[*flags, 0, 0, 0][..], &target.octets()].concat();
+                write_options(options, &mut body);
+            }
+            Self::Redirect {
+                target,
+                destination,
+                options,
+            } => {
+                body = [&[0; 4][..], &target.octets(), &destination.octets()].concat();
+                write_options(options, &mut body);
+            }
+            Self::MldV2Query {
+                max_response_code,
+                group,
+                flags,
+                qqic,
+                sources,
+            } => {
+                #[allow(clippy::cast_possible_truncation)]
+                let count = sources.len() as u16;
+                body = [
+                    &max_response_code.to_be_bytes()[..],
+                    &[0; 2],
+                    &group.octets(),
+                    &[*flags, *qqic],
+                    &count.to_be_bytes(),
+                ]
+                .concat();
+                for source in sources {
+                    body.extend_from_slice(&source.octets());
+                }
+            }
+            Self::MldV2Report { records } => {
+                #[allow(clippy::cast_possible_truncation)]
+                let count = records.len() as u16;
+                body = [&[0; 2][..], &count.to_be_bytes()].concat();
+                for record in records {
+                    record.write(&mut body);
+                }
+            }
+            Self::Unknown { body: data, .. } => body.clone_from(data),
+        }
+        body
+    }
+
+    /// Returns the message in its wire format with a zero checksum
+    pub(crate) fn write(&self) -> Vec<u8> {
+        [&[self.icmp_type(), self.code(), 0, 0][..], &self.body()].concat()
+    }
+
+    /// Parses the message from the complete `ICMPv6` packet
+    pub(crate) fn parse(buf: &[u8]) -> Self {
+        let icmp_type = buf.first().copied().unwrap_or_default();
+        let code = buf.get(1).copied().unwrap_or_default();
+        let body = buf.get(4..).unwrap_or_default();
+        Self::parse_body(icmp_type, code, body).unwrap_or_else(|| Self::Unknown {
+            icmp_type,
+            code,
+            body: body.to_vec(),
+        })
+    }
+
+    fn parse_body(icmp_type: u8, code: u8, body: &[u8]) -> Option<Self> {
+        let rest = |i: usize| body.get(i..).map(<[u8]>::to_vec);
+        Some(match icmp_type {
+            1 => Self::DestinationUnreachable {
+                code,
+                invoking: rest(4)?,
+            },
+            2 => Self::PacketTooBig {
+                mtu: u32_at(body, 0)?,
+                invoking: rest(4)?,
+            },
+            3 => Self::TimeExceeded {
+                code,
+                invoking: rest(4)?,
+            },
+            4 => Self::ParameterProblem {
+                code,
+                pointer: u32_at(body, 0)?,
+                invoking: rest(4)?,
+            },
+            128 => Self::EchoRequest {
+                identifier: u16_at(body, 0)?,
+                sequence: u16_at(body, 2)?,
+                data: rest(4)?,
+            },
+            129 => Self::EchoReply {
+                identifier: u16_at(body, 0)?,
+                sequence: u16_at(body, 2)?,
+                data: rest(4)?,
+            },
+            130 if body.len() == 20 => Self::MldQuery {
+                max_response_delay: u16_at(body, 0)?,
+                group: addr_at(body, 4)?,
+            },
+            130 => Self::MldV2Query {
+                max_response_code: u16_at(body, 0)?,
+                group: addr_at(body, 4)?,
+                flags: *body.get(20)?,
+                qqic: *body.get(21)?,
+                sources: {
+                    let sources = usize::from(u16_at(body, 22)?) * 16;
+                    (body.len() == 24 + sources).then_some(())?;
+                    addrs(&body[24..])?
+                },
+            },
+            131 => Self::MldReport {
+                group: addr_at(body, 4)?,
+            },
+            132 => Self::MldDone {
+                group: addr_at(body, 4)?,
+            },
+            133 => Self::RouterSolicitation {
+                options: parse_options(body.get(4..)?),
+            },
+            134 => Self::RouterAdvertisement {
+                hop_limit: *body.first()?,
+                flags: *body.get(1)?,
+                lifetime: u16_at(body, 2)?,
+                reachable_time: u32_at(body, 4)?,
+                retrans_timer: u32_at(body, 8)?,
+                options: parse_options(body.get(12..)?),
+            },
+            135 => Self::NeighborSolicitation {
+                target: addr_at(body, 4)?,
+                options: parse_options(body.get(20..)?),
+            },
+            136 => Self::NeighborAdvertisement {
+                flags: *body.first()?,
+                target: addr_at(body, 4)?,
+                options: parse_options(body.get(20..)?),
+            },
+            137 => Self::Redirect {
+                target: addr_at(body, 4)?,
+                destination: addr_at(body, 20)?,
+                options: parse_options(body.get(36..)?),
+            },
+            143 => Self::MldV2Report {
+                records: parse_records(body.get(4..)?, u16_at(body, 2)?)?,
+            },
+            _ => return None,
+        })
+    }
+}
@@ -0,0 +1,148 @@
+use pnet::util::MacAddr;
+use std::net::Ipv6Addr;
+
+/// Neighbor Discovery option as defined in RFC 4861
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NdpOption {
+    /// Source link-layer address (1)
+    SourceLinkLayerAddr(MacAddr),
+    /// Target link-layer address (2)
+    TargetLinkLayerAddr(MacAddr),
+    /// Prefix information (3)
+    PrefixInformation {
+        /// Number of leading bits of the prefix that are valid
+        prefix_length: u8,
+        /// On-link (L) and autonomous address-configuration (A) flags
+        flags: u8,
+        /// Valid lifetime in seconds
+        valid_lifetime: u32,
+        /// Preferred lifetime in seconds
+        preferred_lifetime: u32,
+        /// The prefix
+        prefix: Ipv6Addr,
+    },
+    /// Redirected header (4) with the original packet
+    RedirectedHeader(Vec<u8>),
+    /// MTU (5)
+    Mtu(u32),
+    /// Option with a type not known by `pnet_layers`
+    Unknown {
+        /// The option type
+        kind: u8,
+        /// Option data without the type and length byte
+        data: Vec<u8>,
+    },
+    /// Bytes which could not be parsed as options. These are written as they are.
+    Raw(Vec<u8>),
+}
+
+impl NdpOption {
+    /// Returns the option type
+    #[must_use]
+    pub fn kind(&self) -> Option<u8> {
+        Some(match self {
+            Self::SourceLinkLayerAddr(_) => 1,
+            Self::TargetLinkLayerAddr(_) => 2,
+            Self::PrefixInformation { .. } => 3,
+            Self::RedirectedHeader(_) => 4,
+            Self::Mtu(_) => 5,
+            Self::Unknown { kind, .. } => *kind,
+            Self::Raw(_) => return None,
+        })
+    }
+
+    fn data(&self) -> Vec<u8> {
+        match self {
+            Self::SourceLinkLayerAddr(mac) | Self::TargetLinkLayerAddr(mac) => {
+                mac.octets().to_vec()
+            }
+            Self::PrefixInformation {
+                prefix_length,
+                flags,
+                valid_lifetime,
+                preferred_lifetime,
+                prefix,
+            } => [
+                &[*prefix_length, *flags][..],
+                &valid_lifetime.to_be_bytes(),
+                &preferred_lifetime.to_be_bytes(),
+                &[0; 4],
+                &prefix.octets(),
+            ]
+            .concat(),
+            Self::RedirectedHeader(packet) => [&[0; 6][..], packet].concat(),
+            Self::Mtu(mtu) => [&[0; 2][..], &mtu.to_be_bytes()].concat(),
+            Self::Unknown { data, .. } => data.clone(),
+            Self::Raw(_) => vec![],
+        }
+    }
+
+    /// Writes the option in its wire format to `buf`. The option is padded with zeros to a
+    /// multiple of 8 bytes.
+    pub(crate) fn write(&self, buf: &mut Vec<u8>) {
+        if let Self::Raw(data) = self {
+            buf.extend_from_slice(data);
+            return;
+        }
+        let data = self.data();
+        let len = (data.len() + 2).div_ceil(8);
+        buf.push(self.kind().unwrap_or_default());
+        #[allow(clippy::cast_possible_truncation)]
+        buf.push(len as u8);
+        buf.extend_from_slice(&data);
+        buf.resize(buf.len() + len * 8 - data.len() - 2, 0);
+    }
+
+    fn parse(kind: u8, data: &[u8]) -> Self {
+        let u32_at =
+            |i: usize| u32::from_be_bytes([data[i], data[i + 1], data[i + 2], data[i + 3]]);
+        let option = match (kind, data.len()) {
+            (1, 6) => Some(Self::SourceLinkLayerAddr(MacAddr::new(
+                data[0], data[1], data[2], data[3], data[4], data[5],
+            ))),
+            (2, 6) => Some(Self::TargetLinkLayerAddr(MacAddr::new(
+                data[0], data[1], data[2], data[3], data[4], data[5],
+            ))),
+            (3, 30) => {
+                <[u8; 16]>::try_from(&data[14..30])
+                    .ok()
+                    .map(|prefix| Self::PrefixInformation {
+                        prefix_length: data[0],
+                        flags: data[1],
+                        valid_lifetime: u32_at(2),
+                        preferred_lifetime: u32_at(6),
+                        prefix: Ipv6Addr::from(prefix),
+                    })
+            }
+            (4, len) if len >= 6 => Some(Self::RedirectedHeader(data[6..].to_vec())),
+            (5, 6) => Some(Self::Mtu(u32_at(2))),
+            _ => None,
+        };
+        option.unwrap_or_else(|| Self::Unknown {
+            kind,
+            data: data.to_vec(),
+        })
+    }
+}
+
+/// Parses the options of a Neighbor Discovery message
+pub(crate) fn parse_options(mut buf: &[u8]) -> Vec<NdpOption> {
+    let mut options = vec![];
+    while let Some(&kind) = buf.first() {
+        let len = buf.get(1).map_or(0, |len| usize::from(*len) * 8);
+        if len == 0 || len > buf.len() {
+            options.push(NdpOption::Raw(buf.to_vec()));
+            break;
+        }
+        options.push(NdpOption::parse(kind, &buf[2..len]));
+        buf = &buf[len..];
+    }
+    options
+}
+
+/// Returns the options in the wire format
+pub(crate) fn write_options(options: &[NdpOption], buf: &mut Vec<u8>) {
+    for option in options {
+        option.write(buf);
+    }
+}
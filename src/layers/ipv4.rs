@@ -1,15 +1,23 @@
+use crate::layers::options::OptionsHeader;
 use crate::magics::MAGIC_IPV4_TTL;
+use crate::registry;
 use crate::{
-    Icmp, IcmpMut, Layer, LayerImmutable, LayerMut, LayerMutable, Layers, Tcp, TcpMut, Udp, UdpMut,
-    create_add_layer, create_default_immutable, create_from_buf, create_get_layer, create_modify,
-    create_set_payload, create_switch_layer,
+    Error, Field, FieldKind, Icmp, Layer, LayerImmutable, LayerMut, LayerMutable, Layers,
+    PayloadMut, Raw, Result, Tcp, TrailerMut, Udp, create_add_layer, create_default_immutable,
+    create_from_buf, create_get_layer, create_modify, create_set_payload, create_switch_layer,
+    create_upper_layer,
 };
-use pnet::packet::Packet;
-use pnet::packet::ip::IpNextHeaderProtocols;
+use pnet::packet::ip::{IpNextHeaderProtocol, IpNextHeaderProtocols};
 use pnet::packet::ipv4::{Ipv4Packet, MutableIpv4Packet};
 use rand::Rng;
 use std::fmt::{Debug, Display};
 
+mod options;
+pub use options::Ipv4Option;
+
+/// Options and header length of the mutable layer
+const HEADER: OptionsHeader = OptionsHeader::new(Layers::Ipv4, 0, 0);
+
 /// Immutable representation of an Ipv4 packet
 #[derive(Debug)]
 pub struct Ipv4<'a> {
@@ -17,55 +25,137 @@ pub struct Ipv4<'a> {
 }
 
 #[derive(Clone)]
-/// Mutable representation of an Ipv4 packet
+/// Mutable representation of an Ipv4 packet. A `Payload` upper layer keeps bytes which can
+/// not be parsed as the announced protocol, e.g. the truncated transport header of a datagram
+/// quoted by an ICMP error.
 pub struct Ipv4Mut {
-    /// the packet as bytes
+    /// the packet header including the options as bytes
     pub buf: Vec<u8>,
     /// the upper layer
     pub upper_layer: Option<Box<LayerMut>>,
+    /// the bytes after the total length, e.g. a vendor trailer of a bare IPv4 packet
+    pub trailer: Option<TrailerMut>,
+}
+
+impl Ipv4Mut {
+    /// Returns the parsed options of the IPv4 header
+    #[must_use]
+    pub fn options(&self) -> Vec<Ipv4Option> {
+        HEADER.options(&self.buf)
+    }
+
+    /// Replaces the options of the IPv4 header. The options are padded to a 32-bit boundary
+    /// and the header length is updated.
+    ///
+    /// # Errors
+    /// Returns `Error::LengthOverflow` if the options are longer than 40 bytes
+    pub fn set_options(&mut self, options: &[Ipv4Option]) -> Result<()> {
+        HEADER.set_options(&mut self.buf, options)
+    }
+
+    /// Adds an option in front of the end of option list and bytes which could not be parsed
+    ///
+    /// # Errors
+    /// Returns `Error::LengthOverflow` if the options are getting longer than 40 bytes
+    pub fn add_option(&mut self, option: Ipv4Option) -> Result<()> {
+        HEADER.add_option(&mut self.buf, option)
+    }
+
+    /// Removes all options with the given option number and returns them
+    ///
+    /// # Errors
+    /// Returns an error if the options could not be written back
+    pub fn remove_option(&mut self, number: u8) -> Result<Vec<Ipv4Option>> {
+        HEADER.remove_option(&mut self.buf, number)
+    }
+
+    /// Parses a datagram quoted by an ICMP error message. The quoted datagram is usually
+    /// truncated, so an upper layer which can not be parsed is kept as `Payload`. The same
+    /// applies to bytes after the total length, a quoted datagram has no trailer.
+    pub(crate) fn from_quoted(buf: Vec<u8>) -> Result<Self> {
+        if let Ok(ipv4) = Self::from_buf(buf.clone())
+            && ipv4.trailer.is_none()
+        {
+            return Ok(ipv4);
+        }
+        let ipv4 = Ipv4Packet::new(&buf).ok_or(Error::Truncated {
+            layer: Self::LAYER,
+            offset: 0,
+        })?;
+        let len = header_len(&ipv4);
+        if len > buf.len() {
+            return Err(Error::Truncated {
+                layer: Self::LAYER,
+                offset: 0,
+            });
+        }
+        let mut buf = buf;
+        let data = buf.split_off(len);
+        let upper_layer =
+            (!data.is_empty()).then(|| Box::new(LayerMut::Payload(PayloadMut { buf: data })));
+        Ok(Self {
+            buf,
+            upper_layer,
+            trailer: None,
+        })
+    }
 }
 
 impl<'a> LayerMutable<'a> for Ipv4Mut {
     type PacketMut = MutableIpv4Packet<'a>;
+    const LAYER: Layers = Layers::Ipv4;
+    const FIELDS: &'static [Field] = &[
+        Field::int("version", 0, 4),
+        Field::int("ihl", 4, 4),
+        Field::int("dscp", 8, 6),
+        Field::int("ecn", 14, 2),
+        Field::int("len", 16, 16),
+        Field::int("id", 32, 16),
+        Field::new("flags", 48, FieldKind::Flags("MDR")),
+        Field::int("frag", 51, 13),
+        Field::int("ttl", 64, 8),
+        Field::int("proto", 72, 8),
+        Field::int("chksum", 80, 16),
+        Field::new("src", 96, FieldKind::Ipv4),
+        Field::new("dst", 128, FieldKind::Ipv4),
+        Field::new("options", 160, FieldKind::Bytes),
+    ];
     fn new() -> Self {
         Self {
             buf: vec![0; Ipv4Packet::minimum_packet_size()],
             upper_layer: None,
+            trailer: None,
+        }
+    }
+
+    fn update_header(&mut self, field: &Field) {
+        if field.name == "options" {
+            HEADER.update_len(&mut self.buf);
         }
     }
 
     create_modify!();
-    create_set_payload!();
+    create_upper_layer!();
+    create_set_payload!(|buf: &mut [u8]| HEADER.sized_len(buf));
     create_switch_layer!();
-    create_add_layer!(Udp, Tcp, Icmp; {});
-    create_get_layer!(Udp, Tcp, Icmp);
-    create_from_buf!(
-        Ipv4Packet,
-        get_next_level_protocol,
-        IpNextHeaderProtocols,
-        Udp => UdpMut,
-        Icmp => IcmpMut,
-        Tcp => TcpMut
-    );
-
-    fn build(mut self) -> Option<Vec<u8>> {
-        let mut upper_layer = None;
-
-        #[allow(clippy::collapsible_if)]
-        if self.upper_layer.is_some() {
-            #[allow(clippy::unwrap_used)]
-            if matches!(**self.upper_layer.as_ref().unwrap(), LayerMut::Udp(_)) {
-                upper_layer = Some(IpNextHeaderProtocols::Udp);
-            }
-            #[allow(clippy::unwrap_used)]
-            if matches!(**self.upper_layer.as_ref().unwrap(), LayerMut::Tcp(_)) {
-                upper_layer = Some(IpNextHeaderProtocols::Tcp);
-            }
-            #[allow(clippy::unwrap_used)]
-            if matches!(**self.upper_layer.as_ref().unwrap(), LayerMut::Icmp(_)) {
-                upper_layer = Some(IpNextHeaderProtocols::Icmp);
-            }
-        }
+    create_add_layer!(Udp, Tcp, Icmp, Raw, Custom, Payload; {}; trailer);
+    create_get_layer!(Udp, Tcp, Icmp, Raw, Custom, Payload);
+    create_from_buf!(Ipv4Packet, get_next_level_protocol, header_len, payload_len; trailer);
+
+    fn trailer(&self) -> Option<&TrailerMut> {
+        self.trailer.as_ref()
+    }
+
+    fn build(mut self) -> Result<Vec<u8>> {
+        let upper_layer = self
+            .upper_layer
+            .as_deref()
+            .and_then(registry::ip_proto)
+            .map(IpNextHeaderProtocol);
+
+        // options set as raw bytes are padded to a multiple of 4 bytes, the bytes after the
+        // header are a payload set by `set_payload`
+        let header_len = HEADER.build_len(&mut self.buf)?;
 
         let saddr;
         let daddr;
@@ -83,9 +173,8 @@ impl<'a> LayerMutable<'a> for Ipv4Mut {
                 let ident: u16 = rand::rng().random_range(0..u16::MAX);
                 ipv4.set_identification(ident);
             }
-            if ipv4.get_header_length() == 0 {
-                ipv4.set_header_length(5);
-            }
+            #[allow(clippy::cast_possible_truncation)]
+            ipv4.set_header_length((header_len / 4) as u8);
             if ipv4.get_ttl() == 0 {
                 ipv4.set_ttl(MAGIC_IPV4_TTL);
             }
@@ -99,8 +188,16 @@ impl<'a> LayerMutable<'a> for Ipv4Mut {
                 LayerMut::Udp(udp) => udp.build_from_ipv4(saddr, daddr)?,
                 LayerMut::Tcp(tcp) => tcp.build_from_ipv4(saddr, daddr)?,
                 LayerMut::Icmp(pkt) => pkt.build()?,
+                LayerMut::Raw(raw) => raw.build()?,
+                LayerMut::Custom(custom) => custom.build()?,
+                LayerMut::Payload(payload) => payload.build()?,
                 // LayerMut::Tcp(tcp) => vlan.build(),
-                _ => panic!("child not possible"),
+                other => {
+                    return Err(Error::IllegalStacking {
+                        layer: other.kind(),
+                        lower: Self::LAYER,
+                    });
+                }
             },
             None => vec![],
         };
@@ -108,7 +205,10 @@ impl<'a> LayerMutable<'a> for Ipv4Mut {
         self.buf.extend_from_slice(&payload);
 
         if self.buf.len() > u16::MAX as usize {
-            return None;
+            return Err(Error::LengthOverflow {
+                layer: Self::LAYER,
+                length: self.buf.len(),
+            });
         }
 
         #[allow(clippy::cast_possible_truncation)]
@@ -119,8 +219,11 @@ impl<'a> LayerMutable<'a> for Ipv4Mut {
             ipv4.set_total_length(len);
             ipv4.set_checksum(pnet::packet::ipv4::checksum(&ipv4.to_immutable()));
         }
+        if let Some(trailer) = self.trailer {
+            self.buf.extend_from_slice(&trailer.buf);
+        }
 
-        Some(self.buf)
+        Ok(self.buf)
     }
 }
 
@@ -130,13 +233,13 @@ impl<'a> LayerImmutable<'a> for Ipv4<'a> {
     type Packet = Ipv4Packet<'a>;
     type PacketMut = MutableIpv4Packet<'a>;
     type LayerMutType = Ipv4Mut;
+    const LAYER: Layers = Layers::Ipv4;
 
     create_default_immutable!();
 
     fn get_layer_from_buf(buf: &'_ [u8], layer: Layers) -> Option<Layer<'_>> {
         let ipv4 = Ipv4Packet::new(buf)?;
-        let ipv4_size = Ipv4Packet::minimum_packet_size();
-        let buf = &buf[ipv4_size..buf.len()];
+        let buf = buf.get(header_len(&ipv4)..)?;
         Some(match ipv4.get_next_level_protocol() {
             IpNextHeaderProtocols::Udp => {
                 if matches!(layer, Layers::Udp) {
@@ -159,6 +262,7 @@ impl<'a> LayerImmutable<'a> for Ipv4<'a> {
                     Icmp::get_layer_from_buf(buf, layer)?
                 }
             }
+            _ if layer == Layers::Raw => Layer::Raw(Raw::new(buf)),
             _ => {
                 log::debug!(
                     "Ipv4 next level protocol missing: {}",
@@ -170,6 +274,20 @@ impl<'a> LayerImmutable<'a> for Ipv4<'a> {
     }
 }
 
+/// Returns the length of the payload given by the total length. A total length of zero is
+/// used by captures with segmentation offloading, the payload then fills the buffer.
+pub(super) fn payload_len(ipv4: &Ipv4Packet) -> Option<usize> {
+    match usize::from(ipv4.get_total_length()) {
+        0 => None,
+        len => Some(len.saturating_sub(header_len(ipv4))),
+    }
+}
+
+/// Returns the length of the header including the options
+pub(super) fn header_len(ipv4: &Ipv4Packet) -> usize {
+    (usize::from(ipv4.get_header_length()) * 4).max(Ipv4Packet::minimum_packet_size())
+}
+
 impl Display for Ipv4Mut {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         if let Some(eth) = Ipv4Packet::new(&self.buf) {
@@ -182,6 +300,9 @@ impl Display for Ipv4Mut {
             if let Some(upper) = &self.upper_layer {
                 write!(f, " > {upper}")?;
             }
+            if let Some(trailer) = &self.trailer {
+                write!(f, " > {trailer}")?;
+            }
         }
         Ok(())
     }
@@ -199,7 +320,112 @@ impl Debug for Ipv4Mut {
             if let Some(upper) = &self.upper_layer {
                 write!(f, " > {upper}")?;
             }
+            if let Some(trailer) = &self.trailer {
+                write!(f, " > {trailer}")?;
+            }
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn test_ipv4_options() {
+        // Ipv4 with router alert, record route and end of list > Udp > "hi"
+        let packet = hex::decode(
+            "4800002a123400004011b2810a0000010a00000294040000070704000000000003e807d0000a77b66869",
+        )
+        .expect("failed");
+
+        let mut ipv4 = Ipv4Mut::from_buf(packet.clone()).expect("could not parse");
+        assert_eq!(
+            ipv4.options(),
+            vec![
+                Ipv4Option::RouterAlert(0),
+                Ipv4Option::RecordRoute {
+                    pointer: 4,
+                    route: vec![Ipv4Addr::UNSPECIFIED]
+                },
+                Ipv4Option::EndOfList
+            ]
+        );
+        assert!(matches!(
+            ipv4.get_layer(&Layers::Udp),
+            Some(LayerMut::Udp(_))
+        ));
+        assert_eq!(
+            packet,
+            ipv4.clone().build().expect("could not build").as_slice()
+        );
+        assert!(matches!(
+            Ipv4::get_layer_from_buf(&packet, Layers::Udp),
+            Some(Layer::Udp(udp)) if udp.get_buf().len() == 10
+        ));
+
+        let removed = ipv4.remove_option(148).expect("could not remove");
+        assert_eq!(removed, vec![Ipv4Option::RouterAlert(0)]);
+        ipv4.add_option(Ipv4Option::Nop).expect("could not add");
+        let build = ipv4.build().expect("could not build");
+        // record route (7) + nop (1) = 8 bytes options
+        assert_eq!(build[0], 0x47);
+        assert_eq!(&build[20..28], &[7, 7, 4, 0, 0, 0, 0, 1]);
+
+        let mut ipv4 = Ipv4Mut::new();
+        assert!(matches!(
+            ipv4.set_options(&[Ipv4Option::Security(vec![0; 40])]),
+            Err(Error::LengthOverflow { .. })
+        ));
+        // the length byte of the option overflows
+        assert!(matches!(
+            ipv4.set_options(&[Ipv4Option::Security(vec![0; 300])]),
+            Err(Error::LengthOverflow { length: 302, .. })
+        ));
+
+        // options are added in front of the end of the list and the bytes after it
+        let LayerMut::Ipv4(mut ipv4) =
+            LayerMut::parse("Ipv4(options: 0x0100ffff)").expect("could not parse")
+        else {
+            panic!("no ipv4 layer");
+        };
+        ipv4.add_option(Ipv4Option::Nop).expect("could not add");
+        assert_eq!(
+            ipv4.options(),
+            vec![
+                Ipv4Option::Nop,
+                Ipv4Option::Nop,
+                Ipv4Option::EndOfList,
+                // followed by the padding to a 32-bit boundary
+                Ipv4Option::Raw(vec![0xff, 0xff, 0, 0, 0])
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ipv4_set_payload() {
+        let LayerMut::Ipv4(mut ipv4) =
+            LayerMut::parse("Ipv4(options: 0x01010101)").expect("could not parse")
+        else {
+            panic!("no ipv4 layer");
+        };
+        ipv4.set_payload(&[1, 2, 3]);
+        ipv4.set_payload(&[4, 5, 6]);
+        assert_eq!(ipv4.options(), vec![Ipv4Option::Nop; 4]);
+        let build = ipv4.build().expect("could not build");
+        assert_eq!(build.len(), 27);
+        assert_eq!(build[0], 0x46);
+        assert_eq!(&build[20..], &[1, 1, 1, 1, 4, 5, 6]);
+
+        // the header length of a parsed layer is kept
+        let mut ipv4 = Ipv4Mut::from_buf(build).expect("could not parse");
+        ipv4.upper_layer = None;
+        ipv4.set_payload(&[7]);
+        ipv4.set_options(&[Ipv4Option::RouterAlert(0)])
+            .expect("could not set");
+        let build = ipv4.build().expect("could not build");
+        assert_eq!(&build[20..], &[148, 4, 0, 0, 7]);
+    }
+}
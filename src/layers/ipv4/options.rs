@@ -0,0 +1,172 @@
+use crate::layers::options::TlvOption;
+use std::net::Ipv4Addr;
+
+/// IPv4 option, see `Ipv4Mut::options`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Ipv4Option {
+    /// End of option list (0)
+    EndOfList,
+    /// No operation (1), used to align options
+    Nop,
+    /// Record route (7)
+    RecordRoute {
+        /// Pointer to the next free slot, starting at 4
+        pointer: u8,
+        /// Recorded (or reserved) addresses
+        route: Vec<Ipv4Addr>,
+    },
+    /// Timestamp (68)
+    Timestamp {
+        /// Pointer to the next free slot, starting at 5
+        pointer: u8,
+        /// Number of hosts which could not register a timestamp
+        overflow: u8,
+        /// 0: timestamps only, 1: address and timestamp, 3: prespecified addresses
+        flag: u8,
+        /// Timestamps with the address if the flag is not 0
+        timestamps: Vec<(Option<Ipv4Addr>, u32)>,
+    },
+    /// Security (130) as defined in RFC 1108
+    Security(Vec<u8>),
+    /// Loose source and record route (131)
+    LooseSourceRoute {
+        /// Pointer to the next address, starting at 4
+        pointer: u8,
+        /// Route to take
+        route: Vec<Ipv4Addr>,
+    },
+    /// Strict source and record route (137)
+    StrictSourceRoute {
+        /// Pointer to the next address, starting at 4
+        pointer: u8,
+        /// Route to take
+        route: Vec<Ipv4Addr>,
+    },
+    /// Router alert (148)
+    RouterAlert(u16),
+    /// Option with a number not known by `pnet_layers`
+    Unknown {
+        /// The option number including the copied flag and class
+        number: u8,
+        /// Option data without the number and length byte
+        data: Vec<u8>,
+    },
+    /// Bytes which could not be parsed as options. These are written as they are.
+    Raw(Vec<u8>),
+}
+
+impl Ipv4Option {
+    /// Returns the option number including the copied flag and option class
+    #[must_use]
+    pub fn number(&self) -> Option<u8> {
+        Some(match self {
+            Self::EndOfList => 0,
+            Self::Nop => 1,
+            Self::RecordRoute { .. } => 7,
+            Self::Timestamp { .. } => 68,
+            Self::Security(_) => 130,
+            Self::LooseSourceRoute { .. } => 131,
+            Self::StrictSourceRoute { .. } => 137,
+            Self::RouterAlert(_) => 148,
+            Self::Unknown { number, .. } => *number,
+            Self::Raw(_) => return None,
+        })
+    }
+}
+
+impl TlvOption for Ipv4Option {
+    const END_OF_LIST: Self = Self::EndOfList;
+    const NOP: Self = Self::Nop;
+
+    fn code(&self) -> Option<u8> {
+        self.number()
+    }
+
+    fn data(&self) -> Vec<u8> {
+        match self {
+            Self::EndOfList | Self::Nop => vec![],
+            Self::RecordRoute { pointer, route }
+            | Self::LooseSourceRoute { pointer, route }
+            | Self::StrictSourceRoute { pointer, route } => {
+                let mut data = vec![*pointer];
+                for addr in route {
+                    data.extend_from_slice(&addr.octets());
+                }
+                data
+            }
+            Self::Timestamp {
+                pointer,
+                overflow,
+                flag,
+                timestamps,
+            } => {
+                let mut data = vec![*pointer, (overflow << 4) | (flag & 0x0f)];
+                for (addr, timestamp) in timestamps {
+                    if let Some(addr) = addr {
+                        data.extend_from_slice(&addr.octets());
+                    }
+                    data.extend_from_slice(&timestamp.to_be_bytes());
+                }
+                data
+            }
+            Self::Security(data) | Self::Unknown { data, .. } | Self::Raw(data) => data.clone(),
+            Self::RouterAlert(value) => value.to_be_bytes().to_vec(),
+        }
+    }
+
+    fn parse(number: u8, data: &[u8]) -> Self {
+        let route = |data: &[u8]| {
+            if data.is_empty() || !(data.len() - 1).is_multiple_of(4) {
+                return None;
+            }
+            Some((
+                data[0],
+                data[1..]
+                    .chunks(4)
+                    .map(|c| Ipv4Addr::new(c[0], c[1], c[2], c[3]))
+                    .collect(),
+            ))
+        };
+        let option = match number {
+            7 => route(data).map(|(pointer, route)| Self::RecordRoute { pointer, route }),
+            131 => route(data).map(|(pointer, route)| Self::LooseSourceRoute { pointer, route }),
+            137 => route(data).map(|(pointer, route)| Self::StrictSourceRoute { pointer, route }),
+            68 if data.len() >= 2 => {
+                let flag = data[1] & 0x0f;
+                let entry_len = if flag == 0 { 4 } else { 8 };
+                if (data.len() - 2).is_multiple_of(entry_len) {
+                    let timestamps = data[2..]
+                        .chunks(entry_len)
+                        .map(|c| {
+                            let (addr, ts) = c.split_at(entry_len - 4);
+                            let addr = (entry_len == 8)
+                                .then(|| Ipv4Addr::new(addr[0], addr[1], addr[2], addr[3]));
+                            (addr, u32::from_be_bytes([ts[0], ts[1], ts[2], ts[3]]))
+                        })
+                        .collect();
+                    Some(Self::Timestamp {
+                        pointer: data[0],
+                        overflow: data[1] >> 4,
+                        flag,
+                        timestamps,
+                    })
+                } else {
+                    None
+                }
+            }
+            130 => Some(Self::Security(data.to_vec())),
+            148 if data.len() == 2 => {
+                Some(Self::RouterAlert(u16::from_be_bytes([data[0], data[1]])))
+            }
+            _ => None,
+        };
+        option.unwrap_or_else(|| Self::Unknown {
+            number,
+            data: data.to_vec(),
+        })
+    }
+
+    fn raw(data: Vec<u8>) -> Self {
+        Self::Raw(data)
+    }
+}
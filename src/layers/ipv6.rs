@@ -1,12 +1,14 @@
+use crate::registry;
 use crate::{
-    Layer, LayerImmutable, LayerMut, LayerMutable, Layers, Tcp, TcpMut, Udp, UdpMut,
-    create_add_layer, create_default_immutable, create_from_buf, create_get_layer, create_modify,
-    create_set_payload, create_switch_layer,
+    Ah, DestOpts, Error, Field, FieldKind, Fragment, HopByHop, Icmpv6, Layer, LayerImmutable,
+    LayerMut, LayerMutable, Layers, Raw, Result, Routing, Tcp, TrailerMut, Udp, create_add_layer,
+    create_default_immutable, create_from_buf, create_get_layer, create_modify, create_set_payload,
+    create_switch_layer, create_upper_layer,
 };
-use pnet::packet::Packet;
-use pnet::packet::ip::IpNextHeaderProtocols;
+use pnet::packet::ip::{IpNextHeaderProtocol, IpNextHeaderProtocols};
 use pnet::packet::ipv6::{Ipv6Packet, MutableIpv6Packet};
 use std::fmt::{Debug, Display};
+use std::net::Ipv6Addr;
 
 /// Immutable representation of an Ipv6 packet
 #[derive(Debug)]
@@ -17,46 +19,59 @@ pub struct Ipv6<'a> {
 #[derive(Clone)]
 /// Mutable representation of an Ipv6 packet
 pub struct Ipv6Mut {
-    buf: Vec<u8>,
-    upper_layer: Option<Box<LayerMut>>,
+    pub(crate) buf: Vec<u8>,
+    pub(crate) upper_layer: Option<Box<LayerMut>>,
+    /// The bytes after the payload length, e.g. a vendor trailer of a bare IPv6 packet
+    pub(crate) trailer: Option<TrailerMut>,
 }
 
 impl<'a> LayerMutable<'a> for Ipv6Mut {
     type PacketMut = MutableIpv6Packet<'a>;
+    const LAYER: Layers = Layers::Ipv6;
+    const FIELDS: &'static [Field] = &[
+        Field::int("version", 0, 4),
+        Field::int("tc", 4, 8),
+        Field::int("fl", 12, 20),
+        Field::int("plen", 32, 16),
+        Field::int("nh", 48, 8),
+        Field::int("hlim", 56, 8),
+        Field::new("src", 64, FieldKind::Ipv6),
+        Field::new("dst", 192, FieldKind::Ipv6),
+    ];
     fn new() -> Self {
         Self {
             buf: vec![0; Ipv6Packet::minimum_packet_size()],
             upper_layer: None,
+            trailer: None,
         }
     }
 
     create_modify!();
+    create_upper_layer!();
     create_set_payload!();
     create_switch_layer!();
-    create_add_layer!(Udp, Tcp; {});
-    create_get_layer!(Udp, Tcp);
+    create_add_layer!(HopByHop, Routing, Fragment, DestOpts, Ah, Icmpv6, Udp, Tcp, Raw, Custom; {}; trailer);
+    create_get_layer!(
+        HopByHop, Routing, Fragment, DestOpts, Ah, Icmpv6, Udp, Tcp, Raw, Custom
+    );
     create_from_buf!(
         Ipv6Packet,
         get_next_header,
-        IpNextHeaderProtocols,
-        Udp => UdpMut,
-        Tcp => TcpMut
+        |_: &Ipv6Packet| Ipv6Packet::minimum_packet_size(),
+        payload_len;
+        trailer
     );
 
-    fn build(mut self) -> Option<Vec<u8>> {
-        let mut upper_layer = None;
+    fn trailer(&self) -> Option<&TrailerMut> {
+        self.trailer.as_ref()
+    }
 
-        #[allow(clippy::collapsible_if)]
-        if self.upper_layer.is_some() {
-            #[allow(clippy::unwrap_used)]
-            if matches!(**self.upper_layer.as_ref().unwrap(), LayerMut::Udp(_)) {
-                upper_layer = Some(IpNextHeaderProtocols::Udp);
-            }
-            #[allow(clippy::unwrap_used)]
-            if matches!(**self.upper_layer.as_ref().unwrap(), LayerMut::Tcp(_)) {
-                upper_layer = Some(IpNextHeaderProtocols::Tcp);
-            }
-        }
+    fn build(mut self) -> Result<Vec<u8>> {
+        let upper_layer = self
+            .upper_layer
+            .as_deref()
+            .and_then(registry::ip_proto)
+            .map(IpNextHeaderProtocol);
 
         let saddr;
         let daddr;
@@ -70,48 +85,37 @@ impl<'a> LayerMutable<'a> for Ipv6Mut {
             if ipv6.get_version() == 0 {
                 ipv6.set_version(6);
             }
-            // if ipv4.get_identification() == 0 {
-            //     let ident: u16 = rand::rng().random_range(0..u16::MAX);
-            //     ipv4.set_identification(ident);
-            // }
-            // if ipv4.get_header_length() == 0 {
-            //     ipv4.set_header_length(5);
-            // }
-            // if ipv4.get_ttl() == 0 {
-            //     ipv4.set_ttl(MAGIC_IPV4_TTL);
-            // }
             if let Some(upper) = upper_layer {
                 ipv6.set_next_header(upper);
             }
         }
 
         let payload = match self.upper_layer.clone() {
-            Some(child) => match *child {
-                LayerMut::Udp(udp) => udp.build_from_ipv6(saddr, daddr)?,
-                LayerMut::Tcp(tcp) => tcp.build_from_ipv6(saddr, daddr)?,
-                // LayerMut::Tcp(tcp) => vlan.build(),
-                _ => panic!("child not possible"),
-            },
+            Some(child) => build_upper(*child, Self::LAYER, Some((saddr, daddr)))?,
             None => vec![],
         };
 
         self.buf.extend_from_slice(&payload);
 
         if self.buf.len() > u16::MAX as usize {
-            return None;
+            return Err(Error::LengthOverflow {
+                layer: Self::LAYER,
+                length: self.buf.len(),
+            });
         }
 
         #[allow(clippy::cast_possible_truncation)]
         let len = self.buf.len() as u16 - Ipv6Packet::minimum_packet_size() as u16;
-        assert_eq!(Ipv6Packet::minimum_packet_size(), 40);
 
         {
             let mut ipv6 = self.modify()?;
             ipv6.set_payload_length(len);
-            // ipv6.set_checksum(pnet::packet::ipv4::checksum(&ipv6.to_immutable()));
+        }
+        if let Some(trailer) = self.trailer {
+            self.buf.extend_from_slice(&trailer.buf);
         }
 
-        Some(self.buf)
+        Ok(self.buf)
     }
 }
 
@@ -121,36 +125,86 @@ impl<'a> LayerImmutable<'a> for Ipv6<'a> {
     type Packet = Ipv6Packet<'a>;
     type PacketMut = MutableIpv6Packet<'a>;
     type LayerMutType = Ipv6Mut;
+    const LAYER: Layers = Layers::Ipv6;
 
     create_default_immutable!();
 
     fn get_layer_from_buf(buf: &'_ [u8], layer: Layers) -> Option<Layer<'_>> {
-        let ipv4 = Ipv6Packet::new(buf)?;
-        let ipv4_size = Ipv6Packet::minimum_packet_size();
-        let buf = &buf[ipv4_size..buf.len()];
-        Some(match ipv4.get_next_header() {
-            IpNextHeaderProtocols::Udp => {
-                if matches!(layer, Layers::Udp) {
-                    Layer::Udp(Udp::new(buf))
-                } else {
-                    Udp::get_layer_from_buf(buf, layer)?
-                }
-            }
-            IpNextHeaderProtocols::Tcp => {
-                if matches!(layer, Layers::Tcp) {
-                    Layer::Tcp(Tcp::new(buf))
-                } else {
-                    Tcp::get_layer_from_buf(buf, layer)?
-                }
-            }
-            _ => {
-                log::debug!(
-                    "Ipv6 next level protocol missing: {}",
-                    ipv4.get_next_header(),
-                );
-                return None;
-            }
-        })
+        let ipv6 = Ipv6Packet::new(buf)?;
+        let buf = &buf[Ipv6Packet::minimum_packet_size()..];
+        get_layer_with_next_header(ipv6.get_next_header(), buf, layer)
+    }
+}
+
+/// Returns the length of the payload given by the payload length. A payload length of zero is
+/// used by jumbograms, the payload then fills the buffer.
+pub(super) fn payload_len(ipv6: &Ipv6Packet) -> Option<usize> {
+    match ipv6.get_payload_length() {
+        0 => None,
+        len => Some(usize::from(len)),
+    }
+}
+
+/// Builds the layer following an IPv6 header or extension header. The addresses are the
+/// source and final destination used for the pseudo-header checksum of the transport layer.
+pub(crate) fn build_upper(
+    layer: LayerMut,
+    lower: Layers,
+    addrs: Option<(Ipv6Addr, Ipv6Addr)>,
+) -> Result<Vec<u8>> {
+    match (layer, addrs) {
+        (LayerMut::Udp(udp), Some((saddr, daddr))) => udp.build_from_ipv6(saddr, daddr),
+        (LayerMut::Tcp(tcp), Some((saddr, daddr))) => tcp.build_from_ipv6(saddr, daddr),
+        (LayerMut::Icmpv6(icmpv6), Some((saddr, daddr))) => icmpv6.build_from_ipv6(saddr, daddr),
+        (LayerMut::Icmpv6(icmpv6), None) => icmpv6.build(),
+        (LayerMut::Udp(udp), None) => udp.build(),
+        (LayerMut::Tcp(tcp), None) => tcp.build(),
+        (LayerMut::HopByHop(ext), addrs) => ext.build_ext(addrs),
+        (LayerMut::Routing(ext), addrs) => ext.build_ext(addrs),
+        (LayerMut::Fragment(ext), addrs) => ext.build_ext(addrs),
+        (LayerMut::DestOpts(ext), addrs) => ext.build_ext(addrs),
+        (LayerMut::Ah(ext), addrs) => ext.build_ext(addrs),
+        (LayerMut::Payload(payload), _) if lower == Layers::Fragment => payload.build(),
+        (LayerMut::Raw(raw), _) => raw.build(),
+        (LayerMut::Custom(custom), _) => custom.build(),
+        (other, _) => Err(Error::IllegalStacking {
+            layer: other.kind(),
+            lower,
+        }),
+    }
+}
+
+/// Searches `buf` for `layer` where `buf` starts with the header announced by `next`
+pub(crate) fn get_layer_with_next_header(
+    next: IpNextHeaderProtocol,
+    buf: &[u8],
+    layer: Layers,
+) -> Option<Layer<'_>> {
+    fn find<'b, L: LayerImmutable<'b>>(
+        buf: &'b [u8],
+        layer: Layers,
+        wrap: fn(L) -> Layer<'b>,
+    ) -> Option<Layer<'b>> {
+        if layer == L::LAYER {
+            Some(wrap(L::new(buf)))
+        } else {
+            L::get_layer_from_buf(buf, layer)
+        }
+    }
+    match next {
+        IpNextHeaderProtocols::Hopopt => find::<HopByHop>(buf, layer, Layer::HopByHop),
+        IpNextHeaderProtocols::Ipv6Route => find::<Routing>(buf, layer, Layer::Routing),
+        IpNextHeaderProtocols::Ipv6Frag => find::<Fragment>(buf, layer, Layer::Fragment),
+        IpNextHeaderProtocols::Ipv6Opts => find::<DestOpts>(buf, layer, Layer::DestOpts),
+        IpNextHeaderProtocols::Ah => find::<Ah>(buf, layer, Layer::Ah),
+        IpNextHeaderProtocols::Icmpv6 => find::<Icmpv6>(buf, layer, Layer::Icmpv6),
+        IpNextHeaderProtocols::Udp => find::<Udp>(buf, layer, Layer::Udp),
+        IpNextHeaderProtocols::Tcp => find::<Tcp>(buf, layer, Layer::Tcp),
+        _ if layer == Layers::Raw => Some(Layer::Raw(Raw::new(buf))),
+        _ => {
+            log::debug!("Ipv6 next header missing: {next}");
+            None
+        }
     }
 }
 
@@ -166,6 +220,9 @@ impl Display for Ipv6Mut {
             if let Some(upper) = &self.upper_layer {
                 write!(f, " > {upper}")?;
             }
+            if let Some(trailer) = &self.trailer {
+                write!(f, " > {trailer}")?;
+            }
         }
         Ok(())
     }
@@ -183,6 +240,9 @@ impl Debug for Ipv6Mut {
             if let Some(upper) = &self.upper_layer {
                 write!(f, " > {upper}")?;
             }
+            if let Some(trailer) = &self.trailer {
+                write!(f, " > {trailer}")?;
+            }
         }
         Ok(())
     }
@@ -0,0 +1,773 @@
+use crate::layers::ipv6::{build_upper, get_layer_with_next_header};
+use crate::registry;
+use crate::{
+    Error, Field, FieldKind, Layer, LayerImmutable, LayerMut, LayerMutable, Layers, Payload,
+    Result, create_add_layer, create_default_immutable, create_from_buf, create_get_layer,
+    create_modify, create_set_payload, create_switch_layer, create_upper_layer,
+};
+use pnet::packet::Packet;
+use pnet::packet::ip::IpNextHeaderProtocol;
+use pnet_macros::packet;
+use pnet_macros_support::types::{u1, u2, u13be, u16be, u32be};
+use std::fmt::{Debug, Display};
+use std::net::Ipv6Addr;
+
+mod options;
+pub use options::Ipv6ExtOption;
+use options::{parse_options, write_options};
+
+/// Next header value used when no upper layer is defined
+const NO_NEXT_HEADER: u8 = 59;
+
+/// Hop-by-Hop or Destination Options header
+#[packet]
+pub struct OptionsHeader {
+    #[construct_with(u8)]
+    pub next_header: IpNextHeaderProtocol,
+    pub hdr_ext_len: u8,
+    #[length_fn = "options_len"]
+    pub options: Vec<u8>,
+    #[payload]
+    pub payload: Vec<u8>,
+}
+
+/// Routing header, the type specific fields are those of the segment routing header (type 4)
+/// and are reserved for the other types
+#[packet]
+pub struct RoutingHeader {
+    #[construct_with(u8)]
+    pub next_header: IpNextHeaderProtocol,
+    pub hdr_ext_len: u8,
+    pub routing_type: u8,
+    pub segments_left: u8,
+    pub last_entry: u8,
+    pub flags: u8,
+    pub tag: u16be,
+    #[length_fn = "routing_data_len"]
+    pub data: Vec<u8>,
+    #[payload]
+    pub payload: Vec<u8>,
+}
+
+/// Fragment header
+#[packet]
+pub struct FragmentHeader {
+    #[construct_with(u8)]
+    pub next_header: IpNextHeaderProtocol,
+    pub reserved: u8,
+    pub fragment_offset: u13be,
+    pub res: u2,
+    pub more_fragments: u1,
+    pub identification: u32be,
+    #[payload]
+    pub payload: Vec<u8>,
+}
+
+/// Authentication header as defined in RFC 4302
+#[packet]
+pub struct AuthHeader {
+    #[construct_with(u8)]
+    pub next_header: IpNextHeaderProtocol,
+    pub payload_len: u8,
+    pub reserved: u16be,
+    pub spi: u32be,
+    pub sequence: u32be,
+    #[length_fn = "icv_len"]
+    pub icv: Vec<u8>,
+    #[payload]
+    pub payload: Vec<u8>,
+}
+
+fn options_len(pkt: &OptionsHeaderPacket) -> usize {
+    usize::from(pkt.get_hdr_ext_len()) * 8 + 6
+}
+
+fn routing_data_len(pkt: &RoutingHeaderPacket) -> usize {
+    usize::from(pkt.get_hdr_ext_len()) * 8
+}
+
+fn icv_len(pkt: &AuthHeaderPacket) -> usize {
+    auth_header_len(pkt).saturating_sub(AuthHeaderPacket::minimum_packet_size())
+}
+
+/// Returns the length of the Hop-by-Hop or Destination Options header
+pub(super) fn options_header_len(pkt: &OptionsHeaderPacket) -> usize {
+    (usize::from(pkt.get_hdr_ext_len()) + 1) * 8
+}
+
+/// Returns the length of the Routing header
+pub(super) fn routing_header_len(pkt: &RoutingHeaderPacket) -> usize {
+    (usize::from(pkt.get_hdr_ext_len()) + 1) * 8
+}
+
+/// Returns the length of the Authentication header
+pub(super) fn auth_header_len(pkt: &AuthHeaderPacket) -> usize {
+    ((usize::from(pkt.get_payload_len()) + 2) * 4).max(AuthHeaderPacket::minimum_packet_size())
+}
+
+/// Sets the next header in `buf` and appends the built upper layer
+fn build_chain(
+    mut buf: Vec<u8>,
+    upper_layer: Option<Box<LayerMut>>,
+    layer: Layers,
+    addrs: Option<(Ipv6Addr, Ipv6Addr)>,
+) -> Result<Vec<u8>> {
+    if let (Some(next), Some(first)) = (
+        upper_layer.as_deref().and_then(registry::ip_proto),
+        buf.first_mut(),
+    ) {
+        *first = next;
+    }
+    if let Some(upper) = upper_layer {
+        buf.extend_from_slice(&build_upper(*upper, layer, addrs)?);
+    }
+    Ok(buf)
+}
+
+/// Returns the length field of an extension header in units of 8 bytes not including the
+/// first 8 bytes
+fn ext_len(layer: Layers, len: usize) -> Result<u8> {
+    u8::try_from(len / 8 - 1).map_err(|_| Error::LengthOverflow { layer, length: len })
+}
+
+/// Hop-by-Hop and Destination Options headers only differ in the next header value
+macro_rules! options_layer {
+    ($layer:ident, $mut_layer:ident, $name:literal) => {
+        #[doc = concat!("Immutable representation of an IPv6 ", $name, " header")]
+        #[derive(Debug)]
+        pub struct $layer<'a> {
+            buf: &'a [u8],
+        }
+
+        #[doc = concat!("Mutable representation of an IPv6 ", $name, " header")]
+        #[derive(Clone)]
+        pub struct $mut_layer {
+            /// the header including the options as bytes
+            pub buf: Vec<u8>,
+            /// the upper layer
+            pub upper_layer: Option<Box<LayerMut>>,
+        }
+
+        impl $mut_layer {
+            /// Returns the parsed options including the padding
+            #[must_use]
+            pub fn options(&self) -> Vec<Ipv6ExtOption> {
+                parse_options(self.buf.get(2..).unwrap_or_default())
+            }
+
+            /// Replaces the options. The options are padded with `Pad1` or `PadN` to a multiple
+            /// of 8 bytes and the header length is updated.
+            ///
+            /// # Errors
+            /// Returns `Error::LengthOverflow` if the header gets longer than 2048 bytes
+            pub fn set_options(&mut self, options: &[Ipv6ExtOption]) -> Result<()> {
+                let options = write_options(options);
+                let hdr_ext_len = ext_len(Self::LAYER, options.len() + 2)?;
+                self.buf.truncate(2);
+                self.buf.extend_from_slice(&options);
+                self.modify()?.set_hdr_ext_len(hdr_ext_len);
+                Ok(())
+            }
+
+            /// Adds an option in front of the padding
+            ///
+            /// # Errors
+            /// Returns `Error::LengthOverflow` if the header gets longer than 2048 bytes
+            pub fn add_option(&mut self, option: Ipv6ExtOption) -> Result<()> {
+                let mut options = self.options();
+                options.retain(|o| !matches!(o, Ipv6ExtOption::Pad1 | Ipv6ExtOption::PadN(_)));
+                options.push(option);
+                self.set_options(&options)
+            }
+
+            /// Removes all options with the given type and returns them
+            ///
+            /// # Errors
+            /// Returns an error if the options could not be written back
+            pub fn remove_option(&mut self, kind: u8) -> Result<Vec<Ipv6ExtOption>> {
+                let (removed, options): (Vec<_>, Vec<_>) = self
+                    .options()
+                    .into_iter()
+                    .partition(|o| o.kind() == Some(kind));
+                self.set_options(&options)?;
+                Ok(removed)
+            }
+
+            pub(crate) fn build_ext(
+                mut self,
+                addrs: Option<(Ipv6Addr, Ipv6Addr)>,
+            ) -> Result<Vec<u8>> {
+                if !self.buf.len().is_multiple_of(8) {
+                    let options = self.options();
+                    self.set_options(&options)?;
+                }
+                let hdr_ext_len = ext_len(Self::LAYER, self.buf.len())?;
+                self.modify()?.set_hdr_ext_len(hdr_ext_len);
+                build_chain(self.buf, self.upper_layer, Self::LAYER, addrs)
+            }
+        }
+
+        impl<'a> LayerMutable<'a> for $mut_layer {
+            type PacketMut = MutableOptionsHeaderPacket<'a>;
+            const LAYER: Layers = Layers::$layer;
+            const FIELDS: &'static [Field] = &[
+                Field::int("nh", 0, 8),
+                Field::int("len", 8, 8),
+                Field::new("options", 16, FieldKind::Bytes),
+            ];
+            fn new() -> Self {
+                Self {
+                    // no next header and PadN with 4 bytes
+                    buf: vec![NO_NEXT_HEADER, 0, 1, 4, 0, 0, 0, 0],
+                    upper_layer: None,
+                }
+            }
+
+            create_modify!();
+            create_upper_layer!();
+            create_set_payload!();
+            create_switch_layer!(upper);
+            create_add_layer!(HopByHop, Routing, Fragment, DestOpts, Ah, Icmpv6, Udp, Tcp, Raw, Custom; {});
+            create_get_layer!(HopByHop, Routing, Fragment, DestOpts, Ah, Icmpv6, Udp, Tcp, Raw, Custom);
+            create_from_buf!(OptionsHeaderPacket, get_next_header, options_header_len);
+
+            fn build(self) -> Result<Vec<u8>> {
+                self.build_ext(None)
+            }
+        }
+
+        impl<'a> LayerImmutable<'a> for $layer<'a> {
+            type Packet = OptionsHeaderPacket<'a>;
+            type PacketMut = MutableOptionsHeaderPacket<'a>;
+            type LayerMutType = $mut_layer;
+            const LAYER: Layers = Layers::$layer;
+
+            create_default_immutable!();
+
+            fn get_layer_from_buf(buf: &'_ [u8], layer: Layers) -> Option<Layer<'_>> {
+                let pkt = OptionsHeaderPacket::new(buf)?;
+                let next = pkt.get_next_header();
+                let buf = buf.get(options_header_len(&pkt)..)?;
+                get_layer_with_next_header(next, buf, layer)
+            }
+        }
+
+        impl Display for $mut_layer {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                write!(f, "{} (options: {})", stringify!($layer), self.options().len())?;
+                if let Some(upper) = &self.upper_layer {
+                    write!(f, " > {upper}")?;
+                }
+                Ok(())
+            }
+        }
+
+        impl Debug for $mut_layer {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                Display::fmt(self, f)
+            }
+        }
+    };
+}
+
+options_layer!(HopByHop, HopByHopMut, "Hop-by-Hop Options");
+options_layer!(DestOpts, DestOptsMut, "Destination Options");
+
+/// Immutable representation of an IPv6 Routing header
+#[derive(Debug)]
+pub struct Routing<'a> {
+    buf: &'a [u8],
+}
+
+/// Returns the addresses of the routing header in `buf`, see `RoutingMut::segments`
+fn segments(buf: &[u8]) -> Vec<Ipv6Addr> {
+    let Some(pkt) = RoutingHeaderPacket::new(buf) else {
+        return vec![];
+    };
+    let data = buf.get(8..).unwrap_or_default();
+    let count = match pkt.get_routing_type() {
+        4 => usize::from(pkt.get_last_entry()) + 1,
+        _ => data.len() / 16,
+    };
+    data.chunks_exact(16)
+        .take(count)
+        .filter_map(|c| <[u8; 16]>::try_from(c).ok().map(Ipv6Addr::from))
+        .collect()
+}
+
+/// Returns the final destination of the routing header in `buf`, see
+/// `RoutingMut::final_destination`
+pub(crate) fn final_destination(buf: &[u8]) -> Option<Ipv6Addr> {
+    let pkt = RoutingHeaderPacket::new(buf)?;
+    if pkt.get_segments_left() == 0 {
+        return None;
+    }
+    match pkt.get_routing_type() {
+        0 | 2 => segments(buf).last().copied(),
+        4 => segments(buf).first().copied(),
+        _ => None,
+    }
+}
+
+/// Mutable representation of an IPv6 Routing header
+#[derive(Clone)]
+pub struct RoutingMut {
+    /// the header including the type specific data as bytes
+    pub buf: Vec<u8>,
+    /// the upper layer
+    pub upper_layer: Option<Box<LayerMut>>,
+}
+
+impl RoutingMut {
+    /// Returns the addresses of the routing header. For a segment routing header (type 4) the
+    /// segment list is returned in the order of the header, so the final segment comes first.
+    #[must_use]
+    pub fn segments(&self) -> Vec<Ipv6Addr> {
+        segments(&self.buf)
+    }
+
+    /// Replaces the type specific data with the addresses and updates the header length.
+    /// For a segment routing header (type 4) the last entry is updated and TLVs are removed.
+    ///
+    /// # Errors
+    /// Returns `Error::LengthOverflow` if there are more than 127 addresses
+    pub fn set_segments(&mut self, segments: &[Ipv6Addr]) -> Result<()> {
+        let hdr_ext_len = ext_len(Self::LAYER, 8 + segments.len() * 16)?;
+        self.buf.truncate(8);
+        for segment in segments {
+            self.buf.extend_from_slice(&segment.octets());
+        }
+        let mut pkt = self.modify()?;
+        pkt.set_hdr_ext_len(hdr_ext_len);
+        if pkt.get_routing_type() == 4 {
+            #[allow(clippy::cast_possible_truncation)]
+            pkt.set_last_entry(segments.len().saturating_sub(1) as u8);
+        }
+        Ok(())
+    }
+
+    /// Returns the final destination of the packet if the routing header has segments left.
+    /// This address is used in the pseudo-header checksum of the upper layer.
+    #[must_use]
+    pub fn final_destination(&self) -> Option<Ipv6Addr> {
+        final_destination(&self.buf)
+    }
+
+    pub(crate) fn build_ext(mut self, addrs: Option<(Ipv6Addr, Ipv6Addr)>) -> Result<Vec<u8>> {
+        let addrs = addrs.map(|(saddr, daddr)| (saddr, self.final_destination().unwrap_or(daddr)));
+        self.buf.resize(self.buf.len().div_ceil(8) * 8, 0);
+        let hdr_ext_len = ext_len(Self::LAYER, self.buf.len())?;
+        self.modify()?.set_hdr_ext_len(hdr_ext_len);
+        build_chain(self.buf, self.upper_layer, Self::LAYER, addrs)
+    }
+}
+
+impl<'a> LayerMutable<'a> for RoutingMut {
+    type PacketMut = MutableRoutingHeaderPacket<'a>;
+    const LAYER: Layers = Layers::Routing;
+    const FIELDS: &'static [Field] = &[
+        Field::int("nh", 0, 8),
+        Field::int("len", 8, 8),
+        Field::int("type", 16, 8),
+        Field::int("segleft", 24, 8),
+        Field::int("lastentry", 32, 8),
+        Field::int("flags", 40, 8),
+        Field::int("tag", 48, 16),
+        Field::new("data", 64, FieldKind::Bytes),
+    ];
+    fn new() -> Self {
+        Self {
+            buf: vec![NO_NEXT_HEADER, 0, 0, 0, 0, 0, 0, 0],
+            upper_layer: None,
+        }
+    }
+
+    create_modify!();
+    create_upper_layer!();
+    create_set_payload!();
+    create_switch_layer!(upper);
+    create_add_layer!(HopByHop, Routing, Fragment, DestOpts, Ah, Icmpv6, Udp, Tcp, Raw, Custom; {});
+    create_get_layer!(
+        HopByHop, Routing, Fragment, DestOpts, Ah, Icmpv6, Udp, Tcp, Raw, Custom
+    );
+    create_from_buf!(RoutingHeaderPacket, get_next_header, routing_header_len);
+
+    fn build(self) -> Result<Vec<u8>> {
+        self.build_ext(None)
+    }
+}
+
+impl<'a> LayerImmutable<'a> for Routing<'a> {
+    type Packet = RoutingHeaderPacket<'a>;
+    type PacketMut = MutableRoutingHeaderPacket<'a>;
+    type LayerMutType = RoutingMut;
+    const LAYER: Layers = Layers::Routing;
+
+    create_default_immutable!();
+
+    fn get_layer_from_buf(buf: &'_ [u8], layer: Layers) -> Option<Layer<'_>> {
+        let pkt = RoutingHeaderPacket::new(buf)?;
+        let next = pkt.get_next_header();
+        let buf = buf.get(routing_header_len(&pkt)..)?;
+        get_layer_with_next_header(next, buf, layer)
+    }
+}
+
+impl Display for RoutingMut {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(pkt) = RoutingHeaderPacket::new(&self.buf) {
+            write!(
+                f,
+                "Routing (type: {}, left: {})",
+                pkt.get_routing_type(),
+                pkt.get_segments_left()
+            )?;
+            if let Some(upper) = &self.upper_layer {
+                write!(f, " > {upper}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Debug for RoutingMut {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+/// Immutable representation of an IPv6 Fragment header
+#[derive(Debug)]
+pub struct Fragment<'a> {
+    buf: &'a [u8],
+}
+
+/// Mutable representation of an IPv6 Fragment header.
+/// Only atomic fragments (offset 0 without more fragments) are parsed further, the data of
+/// all other fragments is kept as `Payload`.
+#[derive(Clone)]
+pub struct FragmentMut {
+    /// the header as bytes
+    pub buf: Vec<u8>,
+    /// the upper layer
+    pub upper_layer: Option<Box<LayerMut>>,
+}
+
+impl FragmentMut {
+    pub(crate) fn build_ext(self, addrs: Option<(Ipv6Addr, Ipv6Addr)>) -> Result<Vec<u8>> {
+        build_chain(self.buf, self.upper_layer, Self::LAYER, addrs)
+    }
+}
+
+/// Returns true if the fragment is the only fragment of the packet
+pub(super) fn is_atomic(pkt: &FragmentHeaderPacket) -> bool {
+    pkt.get_fragment_offset() == 0 && pkt.get_more_fragments() == 0
+}
+
+impl<'a> LayerMutable<'a> for FragmentMut {
+    type PacketMut = MutableFragmentHeaderPacket<'a>;
+    const LAYER: Layers = Layers::Fragment;
+    const FIELDS: &'static [Field] = &[
+        Field::int("nh", 0, 8),
+        Field::int("res", 8, 8),
+        Field::int("offset", 16, 13),
+        Field::int("res2", 29, 2),
+        Field::int("m", 31, 1),
+        Field::int("id", 32, 32),
+    ];
+    fn new() -> Self {
+        Self {
+            buf: vec![NO_NEXT_HEADER, 0, 0, 0, 0, 0, 0, 0],
+            upper_layer: None,
+        }
+    }
+
+    create_modify!();
+    create_upper_layer!();
+    create_set_payload!();
+    create_switch_layer!(upper);
+    create_add_layer!(HopByHop, Routing, Fragment, DestOpts, Ah, Icmpv6, Udp, Tcp, Payload, Raw, Custom; {});
+    create_get_layer!(
+        HopByHop, Routing, Fragment, DestOpts, Ah, Icmpv6, Udp, Tcp, Payload, Raw, Custom
+    );
+
+    fn from_buf(mut buf: Vec<u8>) -> Result<Self> {
+        let pkt = FragmentHeaderPacket::new(&buf).ok_or(Error::Truncated {
+            layer: Self::LAYER,
+            offset: 0,
+        })?;
+        let header_len = FragmentHeaderPacket::minimum_packet_size();
+        let payload = pkt.payload().to_vec();
+
+        // the upper layer of a fragment is only parsed if the datagram is not fragmented
+        let next = is_atomic(&pkt).then(|| pkt.get_next_header().into());
+        let upper_layer = registry::parse_next(next, payload, Self::UPPER)
+            .map_err(|e| e.offset_by(header_len))?
+            .map(Box::new);
+
+        buf.truncate(header_len);
+
+        Ok(Self { buf, upper_layer })
+    }
+
+    fn build(self) -> Result<Vec<u8>> {
+        self.build_ext(None)
+    }
+}
+
+impl<'a> LayerImmutable<'a> for Fragment<'a> {
+    type Packet = FragmentHeaderPacket<'a>;
+    type PacketMut = MutableFragmentHeaderPacket<'a>;
+    type LayerMutType = FragmentMut;
+    const LAYER: Layers = Layers::Fragment;
+
+    create_default_immutable!();
+
+    fn get_layer_from_buf(buf: &'_ [u8], layer: Layers) -> Option<Layer<'_>> {
+        let pkt = FragmentHeaderPacket::new(buf)?;
+        let next = pkt.get_next_header();
+        let atomic = is_atomic(&pkt);
+        let buf = buf.get(FragmentHeaderPacket::minimum_packet_size()..)?;
+        if atomic {
+            get_layer_with_next_header(next, buf, layer)
+        } else {
+            (layer == Layers::Payload).then(|| Layer::Payload(Payload::new(buf)))
+        }
+    }
+}
+
+impl Display for FragmentMut {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(pkt) = FragmentHeaderPacket::new(&self.buf) {
+            write!(
+                f,
+                "Fragment (offset: {}, id: {})",
+                pkt.get_fragment_offset(),
+                pkt.get_identification()
+            )?;
+            if let Some(upper) = &self.upper_layer {
+                write!(f, " > {upper}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Debug for FragmentMut {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+/// Immutable representation of an IPv6 Authentication header
+#[derive(Debug)]
+pub struct Ah<'a> {
+    buf: &'a [u8],
+}
+
+/// Mutable representation of an IPv6 Authentication header.
+/// The integrity check value is not calculated and must be set with `set_icv`.
+#[derive(Clone)]
+pub struct AhMut {
+    /// the header including the integrity check value as bytes
+    pub buf: Vec<u8>,
+    /// the upper layer
+    pub upper_layer: Option<Box<LayerMut>>,
+}
+
+impl AhMut {
+    /// Returns the integrity check value including its padding
+    #[must_use]
+    pub fn icv(&self) -> &[u8] {
+        self.buf
+            .get(AuthHeaderPacket::minimum_packet_size()..)
+            .unwrap_or_default()
+    }
+
+    /// Replaces the integrity check value. The value is padded with zeros so that the header
+    /// is a multiple of 8 bytes as required for IPv6.
+    ///
+    /// # Errors
+    /// Returns `Error::LengthOverflow` if the header gets longer than 1028 bytes
+    pub fn set_icv(&mut self, icv: &[u8]) -> Result<()> {
+        let len = (AuthHeaderPacket::minimum_packet_size() + icv.len()).div_ceil(8) * 8;
+        let payload_len = u8::try_from(len / 4 - 2).map_err(|_| Error::LengthOverflow {
+            layer: Self::LAYER,
+            length: len,
+        })?;
+        self.buf.truncate(AuthHeaderPacket::minimum_packet_size());
+        self.buf.extend_from_slice(icv);
+        self.buf.resize(len, 0);
+        self.modify()?.set_payload_len(payload_len);
+        Ok(())
+    }
+
+    pub(crate) fn build_ext(mut self, addrs: Option<(Ipv6Addr, Ipv6Addr)>) -> Result<Vec<u8>> {
+        let icv = self.icv().to_vec();
+        self.set_icv(&icv)?;
+        build_chain(self.buf, self.upper_layer, Self::LAYER, addrs)
+    }
+}
+
+impl<'a> LayerMutable<'a> for AhMut {
+    type PacketMut = MutableAuthHeaderPacket<'a>;
+    const LAYER: Layers = Layers::Ah;
+    const FIELDS: &'static [Field] = &[
+        Field::int("nh", 0, 8),
+        Field::int("len", 8, 8),
+        Field::int("reserved", 16, 16),
+        Field::int("spi", 32, 32),
+        Field::int("seq", 64, 32),
+        Field::new("icv", 96, FieldKind::Bytes),
+    ];
+    fn new() -> Self {
+        let mut buf = vec![0; 16];
+        buf[0] = NO_NEXT_HEADER;
+        buf[1] = 2;
+        Self {
+            buf,
+            upper_layer: None,
+        }
+    }
+
+    create_modify!();
+    create_upper_layer!();
+    create_set_payload!();
+    create_switch_layer!(upper);
+    create_add_layer!(HopByHop, Routing, Fragment, DestOpts, Ah, Icmpv6, Udp, Tcp, Raw, Custom; {});
+    create_get_layer!(
+        HopByHop, Routing, Fragment, DestOpts, Ah, Icmpv6, Udp, Tcp, Raw, Custom
+    );
+    create_from_buf!(AuthHeaderPacket, get_next_header, auth_header_len);
+
+    fn build(self) -> Result<Vec<u8>> {
+        self.build_ext(None)
+    }
+}
+
+impl<'a> LayerImmutable<'a> for Ah<'a> {
+    type Packet = AuthHeaderPacket<'a>;
+    type PacketMut = MutableAuthHeaderPacket<'a>;
+    type LayerMutType = AhMut;
+    const LAYER: Layers = Layers::Ah;
+
+    create_default_immutable!();
+
+    fn get_layer_from_buf(buf: &'_ [u8], layer: Layers) -> Option<Layer<'_>> {
+        let pkt = AuthHeaderPacket::new(buf)?;
+        let next = pkt.get_next_header();
+        let buf = buf.get(auth_header_len(&pkt)..)?;
+        get_layer_with_next_header(next, buf, layer)
+    }
+}
+
+impl Display for AhMut {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(pkt) = AuthHeaderPacket::new(&self.buf) {
+            write!(f, "Ah (spi: {:#x})", pkt.get_spi())?;
+            if let Some(upper) = &self.upper_layer {
+                write!(f, " > {upper}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Debug for AhMut {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Ipv6, Ipv6Mut, PayloadMut, UdpMut};
+    use pnet::packet::udp::{UdpPacket, ipv6_checksum};
+
+    #[test]
+    fn test_ipv6_ext_chain() {
+        let src: Ipv6Addr = "2001:db8::1".parse().expect("addr");
+        let dst: Ipv6Addr = "2001:db8::2".parse().expect("addr");
+        let last: Ipv6Addr = "2001:db8::3".parse().expect("addr");
+
+        let mut ipv6 = Ipv6Mut::new();
+        {
+            let mut pkt = ipv6.modify().expect("ipv6");
+            pkt.set_source(src);
+            pkt.set_destination(dst);
+        }
+        let mut hop = HopByHopMut::new();
+        hop.add_option(Ipv6ExtOption::RouterAlert(0))
+            .expect("option");
+        let mut srh = RoutingMut::new();
+        {
+            let mut pkt = srh.modify().expect("srh");
+            pkt.set_routing_type(4);
+            pkt.set_segments_left(1);
+        }
+        srh.set_segments(&[last, dst]).expect("segments");
+        assert_eq!(srh.final_destination(), Some(last));
+
+        ipv6.add(LayerMut::HopByHop(hop)).expect("hop");
+        ipv6.add(LayerMut::Routing(srh)).expect("srh");
+        ipv6.add(LayerMut::Udp(UdpMut::new())).expect("udp");
+        ipv6.add(LayerMut::Payload(
+            PayloadMut::from_buf(b"hi".to_vec()).expect("payload"),
+        ))
+        .expect("payload");
+
+        let build = ipv6.build().expect("could not build");
+        // Ipv6 (40) + Hop-by-Hop (8) + SRH (8 + 2 * 16) + Udp (8) + payload (2)
+        assert_eq!(build.len(), 98);
+        assert_eq!(build[6], 0);
+        assert_eq!(&build[40..48], &[43, 0, 5, 2, 0, 0, 1, 0]);
+        assert_eq!(&build[48..52], &[17, 4, 4, 1]);
+
+        // the checksum uses the final destination of the segment routing header
+        let udp = UdpPacket::new(&build[88..]).expect("udp");
+        assert_eq!(udp.get_checksum(), ipv6_checksum(&udp, &src, &last));
+
+        let mut parsed = Ipv6Mut::from_buf(build.clone()).expect("could not parse");
+        assert!(matches!(
+            parsed.get_layer(&Layers::Routing),
+            Some(LayerMut::Routing(srh)) if srh.segments() == vec![last, dst]
+        ));
+        assert!(matches!(
+            parsed.get_layer(&Layers::HopByHop),
+            Some(LayerMut::HopByHop(hop)) if hop.options()[0] == Ipv6ExtOption::RouterAlert(0)
+        ));
+        assert_eq!(build, parsed.build().expect("could not build"));
+        assert!(matches!(
+            Ipv6::get_layer_from_buf(&build, Layers::Payload),
+            Some(Layer::Payload(payload)) if payload.buf == b"hi"
+        ));
+    }
+
+    #[test]
+    fn test_ipv6_fragment() {
+        // Fragment with offset 8 and more fragments > 4 bytes data
+        let packet = hex::decode("1100004112345678deadbeef").expect("failed");
+        let fragment = FragmentMut::from_buf(packet.clone()).expect("could not parse");
+        assert!(matches!(
+            fragment.upper_layer.as_deref(),
+            Some(LayerMut::Payload(payload)) if payload.buf == [0xde, 0xad, 0xbe, 0xef]
+        ));
+        assert_eq!(packet, fragment.build().expect("could not build"));
+
+        // atomic fragment > Udp
+        let packet = hex::decode("11000000123456780035003500080000").expect("failed");
+        let mut fragment = FragmentMut::from_buf(packet).expect("could not parse");
+        assert!(matches!(
+            fragment.get_layer(&Layers::Udp),
+            Some(LayerMut::Udp(_))
+        ));
+
+        let mut dest = DestOptsMut::from_buf(hex::decode("3b00010400000000").expect("failed"))
+            .expect("could not parse");
+        assert_eq!(dest.options(), vec![Ipv6ExtOption::PadN(4)]);
+        dest.set_options(&[Ipv6ExtOption::Jumbo(70000)])
+            .expect("options");
+        assert_eq!(dest.buf, hex::decode("3b00c20400011170").expect("failed"));
+    }
+}
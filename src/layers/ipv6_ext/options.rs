@@ -0,0 +1,103 @@
+/// Option of the Hop-by-Hop or Destination Options header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Ipv6ExtOption {
+    /// Single byte of padding (0)
+    Pad1,
+    /// Padding (1) with the given number of zero bytes following the length byte
+    PadN(u8),
+    /// Router alert (5) as defined in RFC 2711
+    RouterAlert(u16),
+    /// Jumbo payload length (194) as defined in RFC 2675
+    Jumbo(u32),
+    /// Option with a type not known by `pnet_layers`
+    Unknown {
+        /// The option type including the action and change bits
+        kind: u8,
+        /// Option data without the type and length byte
+        data: Vec<u8>,
+    },
+    /// Bytes which could not be parsed as options. These are written as they are.
+    Raw(Vec<u8>),
+}
+
+impl Ipv6ExtOption {
+    /// Returns the option type
+    #[must_use]
+    pub fn kind(&self) -> Option<u8> {
+        Some(match self {
+            Self::Pad1 => 0,
+            Self::PadN(_) => 1,
+            Self::RouterAlert(_) => 5,
+            Self::Jumbo(_) => 194,
+            Self::Unknown { kind, .. } => *kind,
+            Self::Raw(_) => return None,
+        })
+    }
+
+    /// Writes the option in its wire format to `buf`
+    pub(crate) fn write(&self, buf: &mut Vec<u8>) {
+        let data = match self {
+            Self::Pad1 => return buf.push(0),
+            Self::Raw(data) => return buf.extend_from_slice(data),
+            Self::PadN(len) => vec![0; usize::from(*len)],
+            Self::RouterAlert(value) => value.to_be_bytes().to_vec(),
+            Self::Jumbo(len) => len.to_be_bytes().to_vec(),
+            Self::Unknown { data, .. } => data.clone(),
+        };
+        buf.push(self.kind().unwrap_or_default());
+        #[allow(clippy::cast_possible_truncation)]
+        buf.push(data.len() as u8);
+        buf.extend_from_slice(&data);
+    }
+
+    fn parse(kind: u8, data: &[u8]) -> Self {
+        match (kind, data.len()) {
+            #[allow(clippy::cast_possible_truncation)]
+            (1, len) if data.iter().all(|b| *b == 0) => Self::PadN(len as u8),
+            (5, 2) => Self::RouterAlert(u16::from_be_bytes([data[0], data[1]])),
+            (194, 4) => Self::Jumbo(u32::from_be_bytes([data[0], data[1], data[2], data[3]])),
+            _ => Self::Unknown {
+                kind,
+                data: data.to_vec(),
+            },
+        }
+    }
+}
+
+/// Parses the options of a Hop-by-Hop or Destination Options header
+pub(crate) fn parse_options(mut buf: &[u8]) -> Vec<Ipv6ExtOption> {
+    let mut options = vec![];
+    while let Some(&kind) = buf.first() {
+        if kind == 0 {
+            options.push(Ipv6ExtOption::Pad1);
+            buf = &buf[1..];
+            continue;
+        }
+        let len = match buf.get(1) {
+            Some(len) if usize::from(*len) + 2 <= buf.len() => usize::from(*len) + 2,
+            _ => {
+                options.push(Ipv6ExtOption::Raw(buf.to_vec()));
+                break;
+            }
+        };
+        options.push(Ipv6ExtOption::parse(kind, &buf[2..len]));
+        buf = &buf[len..];
+    }
+    options
+}
+
+/// Returns the options in the wire format. The options are padded with `Pad1` or `PadN` so
+/// that the header including the next header and length byte is a multiple of 8 bytes.
+pub(crate) fn write_options(options: &[Ipv6ExtOption]) -> Vec<u8> {
+    let mut buf = vec![];
+    for option in options {
+        option.write(&mut buf);
+    }
+    match (8 - (buf.len() + 2) % 8) % 8 {
+        0 => (),
+        1 => Ipv6ExtOption::Pad1.write(&mut buf),
+        #[allow(clippy::cast_possible_truncation)]
+        padding => Ipv6ExtOption::PadN((padding - 2) as u8).write(&mut buf),
+    }
+    buf
+}
@@ -0,0 +1,315 @@
+use crate::layers::ipv6_ext::{
+    AuthHeaderPacket, FragmentHeaderPacket, OptionsHeaderPacket, RoutingHeaderPacket,
+    auth_header_len, is_atomic, options_header_len, routing_header_len,
+};
+use crate::layers::{ether, icmp, ipv4, ipv6, tcp};
+use crate::registry::{self, Binding, Next, Protocol};
+use crate::{Layer, LayerMut, Layers};
+use pnet::packet::Packet;
+use pnet::packet::arp::ArpPacket;
+use pnet::packet::ethernet::EthernetPacket;
+use pnet::packet::icmpv6::Icmpv6Packet;
+use pnet::packet::ipv4::Ipv4Packet;
+use pnet::packet::ipv6::Ipv6Packet;
+use pnet::packet::tcp::TcpPacket;
+use pnet::packet::udp::UdpPacket;
+use pnet::packet::vlan::VlanPacket;
+use std::sync::Arc;
+
+/// A layer found by `LayerIter` together with its position in the walked buffer
+#[derive(Debug)]
+pub struct LayerSpan<'a> {
+    /// The layer, its buffer starts with the header and ends with its payload
+    pub layer: Layer<'a>,
+    /// Offset of the layer from the start of the walked buffer
+    pub offset: usize,
+    /// Length of the header, for `Payload`, `Raw` and `Trailer` the length of the data
+    pub len: usize,
+}
+
+/// Result of splitting a layer into header and payload
+struct Split {
+    header_len: usize,
+    /// End of the payload relative to the start of the layer
+    end: usize,
+    next: Option<Next>,
+}
+
+/// Iterator over all layers of a buffer in a single pass without copying.
+///
+/// The layers are recognized like in `LayerMutable::from_buf`, including protocols bound in
+/// the `registry`, so the kinds match the `LayerMut` chain of the same buffer. The walk ends
+/// after a truncated layer, the trailers of the Ethernet and IP layers are yielded last.
+///
+/// ```
+/// use pnet_layers::{Ether, LayerImmutable, LayerMut, Layers};
+///
+/// let bytes = LayerMut::parse("Ether / Vlan / Vlan / Ipv4 / Udp / \"abc\"")?.build()?;
+/// let kinds: Vec<Layers> = Ether::new(&bytes).layers().map(|span| span.layer.kind()).collect();
+/// assert_eq!(
+///     kinds,
+///     [Layers::Ether, Layers::Vlan, Layers::Vlan, Layers::Ipv4, Layers::Udp, Layers::Payload]
+/// );
+/// # Ok::<(), pnet_layers::Error>(())
+/// ```
+pub struct LayerIter<'a> {
+    buf: &'a [u8],
+    offset: usize,
+    /// End of the buffer of the next layer
+    end: usize,
+    next: Option<Next>,
+    /// Start of the trailers of the Ethernet and IP layers from the outermost layer
+    trailers: Vec<usize>,
+    /// The next layer is the datagram quoted by an ICMP error
+    quote: bool,
+    /// The walk ended at a layer which could not be parsed
+    truncated: bool,
+}
+
+impl<'a> LayerIter<'a> {
+    /// Walks the layers of `buf` starting with a layer of the type `first`
+    #[must_use]
+    pub fn new(buf: &'a [u8], first: Layers) -> Self {
+        Self {
+            buf,
+            offset: 0,
+            end: buf.len(),
+            next: Some(Next::Builtin(first)),
+            trailers: vec![],
+            quote: false,
+            truncated: false,
+        }
+    }
+
+    /// Walks the layers of `buf` starting with a layer of the protocol, e.g. of a layer
+    /// defined by `#[derive(Layer)]`
+    #[must_use]
+    pub fn with_protocol(buf: &'a [u8], protocol: Arc<dyn Protocol>) -> Self {
+        Self {
+            next: Some(Next::Custom(protocol)),
+            ..Self::new(buf, Layers::Custom)
+        }
+    }
+
+    fn next_span(&mut self, next: &Next) -> Option<LayerSpan<'a>> {
+        let buf = self.buf.get(self.offset..self.end)?;
+        let (kind, split) = match next {
+            Next::Builtin(Layers::Ether) => {
+                // bytes after the end of the network layer are the trailer
+                let end = ether::frame_len(buf).unwrap_or(buf.len()).min(buf.len());
+                (Layers::Ether, split_ether(buf, end))
+            }
+            Next::Builtin(Layers::Ipv4) if self.quote => (Layers::Ipv4, split_quoted(buf)),
+            Next::Builtin(kind) => (*kind, split(*kind, buf)),
+            Next::Custom(protocol) => (Layers::Custom, split_custom(protocol.as_ref(), buf)),
+        };
+        let split = split?;
+        let end = split.end.min(buf.len());
+        if matches!(kind, Layers::Ether | Layers::Ipv4 | Layers::Ipv6) && end < buf.len() {
+            self.trailers.push(self.offset + end);
+        }
+        let span = LayerSpan {
+            layer: Layer::new(kind, &buf[..end]),
+            offset: self.offset,
+            len: split.header_len.min(end),
+        };
+        if split.header_len < end {
+            self.next = split.next;
+            self.quote = kind == Layers::Icmp;
+            self.end = self.offset + end;
+            self.offset += split.header_len;
+        }
+        Some(span)
+    }
+}
+
+impl<'a> Iterator for LayerIter<'a> {
+    type Item = LayerSpan<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(next) = self.next.take() {
+            if let Some(span) = self.next_span(&next) {
+                return Some(span);
+            }
+            self.truncated = true;
+        }
+        // the trailer of an upper layer ends at the trailer of the next lower layer
+        let start = self.trailers.pop()?;
+        let end = self.trailers.last().copied().unwrap_or(self.buf.len());
+        let buf = self.buf.get(start..end)?;
+        Some(LayerSpan {
+            layer: Layer::new(Layers::Trailer, buf),
+            offset: start,
+            len: buf.len(),
+        })
+    }
+}
+
+/// Returns the header length and the end of the payload of a layer of the same type as `layer`
+/// at the start of `buf`, whatever layer the header announces next
+pub(crate) fn bounds(layer: &LayerMut, buf: &[u8]) -> Option<(usize, usize)> {
+    let split = match layer {
+        LayerMut::Ether(_) => split_ether(buf, ether::frame_len(buf).unwrap_or(buf.len()))?,
+        LayerMut::Custom(custom) => split_custom(custom.protocol().as_ref(), buf)?,
+        other => split(other.kind(), buf)?,
+    };
+    let end = split.end.min(buf.len());
+    Some((split.header_len.min(end), end))
+}
+
+/// Splits a header with a payload of the layer `next`
+fn split_payload(header_len: usize, payload: &[u8], next: Next) -> Split {
+    Split {
+        header_len,
+        end: header_len + payload.len(),
+        next: Some(next),
+    }
+}
+
+/// Splits a header with a payload of the given length or filling the buffer, the bytes after
+/// the payload are the trailer
+fn split_len(header_len: usize, payload_len: Option<usize>, buf: &[u8], next: Next) -> Split {
+    Split {
+        header_len,
+        end: payload_len.map_or(buf.len(), |len| header_len.saturating_add(len)),
+        next: Some(next),
+    }
+}
+
+/// Splits a layer without an upper layer
+fn split_whole(buf: &[u8]) -> Split {
+    Split {
+        header_len: buf.len(),
+        end: buf.len(),
+        next: None,
+    }
+}
+
+fn split_ether(buf: &[u8], end: usize) -> Option<Split> {
+    let pkt = EthernetPacket::new(buf)?;
+    Some(Split {
+        header_len: EthernetPacket::minimum_packet_size(),
+        end,
+        next: Some(registry::next(
+            Some(pkt.get_ethertype().into()),
+            Layers::Ether.upper(),
+        )),
+    })
+}
+
+fn split_custom(protocol: &dyn Protocol, buf: &[u8]) -> Option<Split> {
+    let header_len = protocol.header_len(buf).ok()?;
+    let binding = protocol.next(buf.get(..header_len)?);
+    Some(Split {
+        header_len,
+        end: buf.len(),
+        next: Some(registry::next(binding, Layers::Custom.upper())),
+    })
+}
+
+fn split_icmp(buf: &[u8]) -> Option<Split> {
+    let icmp_type = *buf.first()?;
+    let header_len = icmp::header_len(icmp_type);
+    let data = buf.get(header_len..)?;
+    let next = if icmp::quotes_ipv4(icmp_type, data) {
+        Layers::Ipv4
+    } else {
+        Layers::Payload
+    };
+    Some(split_payload(header_len, data, Next::Builtin(next)))
+}
+
+/// Splits a datagram quoted by an ICMP error like `Ipv4Mut::from_quoted`, a quote which can not
+/// be walked to its end or has bytes after its total length is kept as header and `Payload`
+fn split_quoted(buf: &[u8]) -> Option<Split> {
+    let split = split(Layers::Ipv4, buf)?;
+    let mut walk = LayerIter::new(buf, Layers::Ipv4);
+    walk.by_ref().for_each(drop);
+    if walk.truncated || split.end < buf.len() {
+        let data = &buf[split.header_len..];
+        return Some(split_payload(
+            split.header_len,
+            data,
+            Next::Builtin(Layers::Payload),
+        ));
+    }
+    Some(split)
+}
+
+fn split_fragment(buf: &[u8]) -> Option<Split> {
+    let pkt = FragmentHeaderPacket::new(buf)?;
+    let binding = is_atomic(&pkt).then(|| pkt.get_next_header().into());
+    Some(split_payload(
+        FragmentHeaderPacket::minimum_packet_size(),
+        pkt.payload(),
+        registry::next(binding, Layers::Fragment.upper()),
+    ))
+}
+
+/// Splits a built-in layer other than `Ether` into header and payload
+fn split(kind: Layers, buf: &[u8]) -> Option<Split> {
+    let checked = |header_len: usize| (header_len <= buf.len()).then_some(header_len);
+    Some(match kind {
+        Layers::Vlan => {
+            let pkt = VlanPacket::new(buf)?;
+            let next = registry::next(Some(pkt.get_ethertype().into()), kind.upper());
+            split_payload(VlanPacket::minimum_packet_size(), pkt.payload(), next)
+        }
+        Layers::Arp => {
+            ArpPacket::new(buf)?;
+            split_whole(buf)
+        }
+        Layers::Ipv4 => {
+            let pkt = Ipv4Packet::new(buf)?;
+            let next = registry::next(Some(pkt.get_next_level_protocol().into()), kind.upper());
+            let header_len = checked(ipv4::header_len(&pkt))?;
+            split_len(header_len, ipv4::payload_len(&pkt), buf, next)
+        }
+        Layers::Ipv6 => {
+            let pkt = Ipv6Packet::new(buf)?;
+            let next = registry::next(Some(pkt.get_next_header().into()), kind.upper());
+            split_len(
+                Ipv6Packet::minimum_packet_size(),
+                ipv6::payload_len(&pkt),
+                buf,
+                next,
+            )
+        }
+        Layers::HopByHop | Layers::DestOpts => {
+            let pkt = OptionsHeaderPacket::new(buf)?;
+            let next = registry::next(Some(pkt.get_next_header().into()), kind.upper());
+            split_payload(checked(options_header_len(&pkt))?, pkt.payload(), next)
+        }
+        Layers::Routing => {
+            let pkt = RoutingHeaderPacket::new(buf)?;
+            let next = registry::next(Some(pkt.get_next_header().into()), kind.upper());
+            split_payload(checked(routing_header_len(&pkt))?, pkt.payload(), next)
+        }
+        Layers::Ah => {
+            let pkt = AuthHeaderPacket::new(buf)?;
+            let next = registry::next(Some(pkt.get_next_header().into()), kind.upper());
+            split_payload(checked(auth_header_len(&pkt))?, pkt.payload(), next)
+        }
+        Layers::Fragment => split_fragment(buf)?,
+        Layers::Icmp => split_icmp(buf)?,
+        Layers::Icmpv6 => {
+            Icmpv6Packet::new(buf)?;
+            split_whole(buf)
+        }
+        Layers::Udp => {
+            let pkt = UdpPacket::new(buf)?;
+            let next =
+                registry::next_ports(Binding::UdpPort, pkt.get_source(), pkt.get_destination());
+            split_payload(UdpPacket::minimum_packet_size(), pkt.payload(), next)
+        }
+        Layers::Tcp => {
+            let pkt = TcpPacket::new(buf)?;
+            let next =
+                registry::next_ports(Binding::TcpPort, pkt.get_source(), pkt.get_destination());
+            split_payload(checked(tcp::header_len(&pkt))?, pkt.payload(), next)
+        }
+        Layers::Ether | Layers::Custom | Layers::Payload | Layers::Raw | Layers::Trailer => {
+            split_whole(buf)
+        }
+    })
+}
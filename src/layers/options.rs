@@ -0,0 +1,230 @@
+use crate::{Error, Layers, Result};
+
+/// Maximum length of the options (header length of 15 words minus the 20 bytes fixed header)
+pub(crate) const MAX_OPTIONS_LEN: usize = 40;
+
+/// Length of the fixed part of the header in front of the options
+const FIXED_LEN: usize = 20;
+
+/// Option in the kind-length-data format of IPv4 and TCP. The end of option list (0) and no
+/// operation (1) consist of the kind only.
+///
+/// Like the options of the IPv6 extension headers and Neighbor Discovery, options which can not
+/// be represented by a typed variant are kept as `Unknown` or `Raw`, so that parsing and
+/// building a packet does not modify the options.
+pub(crate) trait TlvOption: Sized {
+    /// End of option list (0)
+    const END_OF_LIST: Self;
+    /// No operation (1)
+    const NOP: Self;
+
+    /// Returns the kind of the option, `None` for bytes which could not be parsed as options
+    fn code(&self) -> Option<u8>;
+
+    /// Returns the data without the kind and length byte, for bytes which could not be parsed
+    /// as options the bytes
+    fn data(&self) -> Vec<u8>;
+
+    /// Parses the data of an option with a kind other than 0 and 1
+    fn parse(kind: u8, data: &[u8]) -> Self;
+
+    /// Keeps bytes which could not be parsed as options
+    fn raw(data: Vec<u8>) -> Self;
+}
+
+/// Parses the options part of a header
+pub(crate) fn parse_options<T: TlvOption>(mut buf: &[u8]) -> Vec<T> {
+    let mut options = vec![];
+    while let Some(&kind) = buf.first() {
+        match kind {
+            0 => {
+                options.push(T::END_OF_LIST);
+                // everything after the end of the list is padding
+                if buf[1..].iter().any(|b| *b != 0) {
+                    options.push(T::raw(buf[1..].to_vec()));
+                }
+                break;
+            }
+            1 => {
+                options.push(T::NOP);
+                buf = &buf[1..];
+            }
+            _ => {
+                let len = buf.get(1).map_or(0, |len| usize::from(*len));
+                if len < 2 || len > buf.len() {
+                    options.push(T::raw(buf.to_vec()));
+                    break;
+                }
+                options.push(T::parse(kind, &buf[2..len]));
+                buf = &buf[len..];
+            }
+        }
+    }
+    options
+}
+
+/// Returns the options of a `layer` header in the wire format padded to a 32-bit boundary
+///
+/// # Errors
+/// Returns `Error::LengthOverflow` if an option is too long for its length byte
+pub(crate) fn write_options<T: TlvOption>(layer: Layers, options: &[T]) -> Result<Vec<u8>> {
+    let mut buf = vec![];
+    for option in options {
+        match option.code() {
+            Some(kind @ (0 | 1)) => buf.push(kind),
+            Some(kind) => {
+                let data = option.data();
+                let length = data.len() + 2;
+                let len =
+                    u8::try_from(length).map_err(|_| Error::LengthOverflow { layer, length })?;
+                buf.push(kind);
+                buf.push(len);
+                buf.extend_from_slice(&data);
+            }
+            None => buf.extend_from_slice(&option.data()),
+        }
+    }
+    // padding with end of option list
+    buf.resize(buf.len().next_multiple_of(4), 0);
+    Ok(buf)
+}
+
+/// Header with options after a fixed part of 20 bytes and a 4-bit header length counting 32-bit
+/// words like IPv4 and TCP. The buffer of the mutable layer may be followed by a payload set
+/// by `set_payload`.
+#[derive(Clone, Copy)]
+pub(crate) struct OptionsHeader {
+    layer: Layers,
+    /// Byte holding the header length
+    byte: usize,
+    /// Shift of the header length in its byte
+    shift: u8,
+}
+
+impl OptionsHeader {
+    pub(crate) const fn new(layer: Layers, byte: usize, shift: u8) -> Self {
+        Self { layer, byte, shift }
+    }
+
+    /// Returns the length of the header. As long as the header length is not set the whole
+    /// buffer is the header.
+    pub(crate) fn len(self, buf: &[u8]) -> usize {
+        match buf
+            .get(self.byte)
+            .map(|b| usize::from((b >> self.shift) & 0x0f) * 4)
+        {
+            Some(len) if len >= FIXED_LEN => len,
+            _ => buf.len().next_multiple_of(4),
+        }
+    }
+
+    /// Writes the length of the header. It is kept unset if the header is too long for the
+    /// field, so `build` reports the overflow.
+    fn set_len(self, buf: &mut [u8], len: usize) {
+        let words = u8::try_from(len / 4)
+            .ok()
+            .filter(|words| *words <= 0x0f)
+            .unwrap_or_default();
+        if let Some(b) = buf.get_mut(self.byte) {
+            *b = (*b & !(0x0f << self.shift)) | (words << self.shift);
+        }
+    }
+
+    /// Returns the length of the header in front of a payload and makes sure it is set, so the
+    /// payload is not taken for options
+    pub(crate) fn sized_len(self, buf: &mut [u8]) -> usize {
+        let len = self.len(buf);
+        self.set_len(buf, len);
+        len
+    }
+
+    /// Updates the header length after the options were set as raw bytes. A header without
+    /// options may keep an unset header length.
+    pub(crate) fn update_len(self, buf: &mut [u8]) {
+        let len = buf.len().next_multiple_of(4);
+        let is_set = buf
+            .get(self.byte)
+            .is_some_and(|b| (b >> self.shift) & 0x0f != 0);
+        if len > FIXED_LEN || is_set {
+            self.set_len(buf, len);
+        }
+    }
+
+    /// Pads the options to the header length and returns it
+    ///
+    /// # Errors
+    /// Returns `Error::LengthOverflow` if the options are longer than 40 bytes
+    pub(crate) fn build_len(self, buf: &mut Vec<u8>) -> Result<usize> {
+        let len = self.len(buf);
+        if buf.len() < len {
+            buf.resize(len, 0);
+        }
+        if len > FIXED_LEN + MAX_OPTIONS_LEN {
+            return Err(Error::LengthOverflow {
+                layer: self.layer,
+                length: len,
+            });
+        }
+        Ok(len)
+    }
+
+    /// Returns the parsed options of the header
+    pub(crate) fn options<T: TlvOption>(self, buf: &[u8]) -> Vec<T> {
+        let end = self.len(buf).min(buf.len());
+        parse_options(buf.get(FIXED_LEN..end).unwrap_or_default())
+    }
+
+    /// Replaces the options of the header, the options are padded to a 32-bit boundary and
+    /// the header length is updated
+    ///
+    /// # Errors
+    /// Returns `Error::LengthOverflow` if the options are longer than 40 bytes
+    pub(crate) fn set_options<T: TlvOption>(self, buf: &mut Vec<u8>, options: &[T]) -> Result<()> {
+        let options = write_options(self.layer, options)?;
+        if options.len() > MAX_OPTIONS_LEN {
+            return Err(Error::LengthOverflow {
+                layer: self.layer,
+                length: FIXED_LEN + options.len(),
+            });
+        }
+        let end = self.len(buf).min(buf.len());
+        let payload = buf.split_off(end);
+        buf.resize(FIXED_LEN, 0);
+        buf.extend_from_slice(&options);
+        let len = buf.len();
+        self.set_len(buf, len);
+        buf.extend_from_slice(&payload);
+        Ok(())
+    }
+
+    /// Adds an option in front of the end of option list and bytes which could not be parsed
+    ///
+    /// # Errors
+    /// Returns `Error::LengthOverflow` if the options are getting longer than 40 bytes
+    pub(crate) fn add_option<T: TlvOption>(self, buf: &mut Vec<u8>, option: T) -> Result<()> {
+        let mut options = self.options::<T>(buf);
+        // a trailing end of option list is written again as padding
+        if options.last().is_some_and(|o| o.code() == Some(0)) {
+            options.pop();
+        }
+        let end = options
+            .iter()
+            .position(|o| matches!(o.code(), Some(0) | None))
+            .unwrap_or(options.len());
+        options.insert(end, option);
+        self.set_options(buf, &options)
+    }
+
+    /// Removes all options of the given kind and returns them
+    ///
+    /// # Errors
+    /// Returns an error if the options could not be written back
+    pub(crate) fn remove_option<T: TlvOption>(self, buf: &mut Vec<u8>, kind: u8) -> Result<Vec<T>> {
+        let (removed, options): (Vec<T>, Vec<T>) = self
+            .options(buf)
+            .into_iter()
+            .partition(|o: &T| o.code() == Some(kind));
+        self.set_options(buf, &options)?;
+        Ok(removed)
+    }
+}
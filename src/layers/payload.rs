@@ -1,8 +1,8 @@
 #![allow(unexpected_cfgs)]
 
 use crate::{
-    Layer, LayerImmutable, LayerMutable, Layers, create_default_immutable, create_modify,
-    create_set_payload,
+    Field, FieldKind, Layer, LayerImmutable, LayerMutable, Layers, Result,
+    create_default_immutable, create_modify, create_set_payload,
 };
 use pnet::packet::arp::MutableArpPacket;
 use pnet_macros::packet;
@@ -30,6 +30,8 @@ pub struct PayloadMut {
 
 impl<'a> LayerMutable<'a> for PayloadMut {
     type PacketMut = MutableArpPacket<'a>;
+    const LAYER: Layers = Layers::Payload;
+    const FIELDS: &'static [Field] = &[Field::new("load", 0, FieldKind::Bytes)];
     fn new() -> Self {
         Self { buf: vec![0; 0] }
     }
@@ -37,12 +39,12 @@ impl<'a> LayerMutable<'a> for PayloadMut {
     create_modify!();
     create_set_payload!();
 
-    fn from_buf(buf: Vec<u8>) -> Option<Self> {
-        Some(Self { buf })
+    fn from_buf(buf: Vec<u8>) -> Result<Self> {
+        Ok(Self { buf })
     }
 
-    fn build(self) -> Option<Vec<u8>> {
-        Some(self.buf)
+    fn build(self) -> Result<Vec<u8>> {
+        Ok(self.buf)
     }
 }
 
@@ -50,6 +52,7 @@ impl<'a> LayerImmutable<'a> for Payload<'a> {
     type Packet = PayloadDummyPacket<'a>;
     type PacketMut = MutablePayloadDummyPacket<'a>;
     type LayerMutType = PayloadMut;
+    const LAYER: Layers = Layers::Payload;
 
     create_default_immutable!();
     fn get_layer_from_buf(_buf: &'_ [u8], _layer: Layers) -> Option<Layer<'_>> {
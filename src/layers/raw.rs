@@ -0,0 +1,92 @@
+use crate::layers::payload::{MutablePayloadDummyPacket, PayloadDummyPacket};
+use crate::registry::Protocol;
+use crate::{
+    CustomMut, Field, FieldKind, Layer, LayerImmutable, LayerMut, LayerMutable, Layers, Result,
+    create_default_immutable, create_modify, create_set_payload,
+};
+use std::fmt::Display;
+use std::sync::Arc;
+
+/// Immutable representation of a payload of an unsupported protocol
+#[derive(Debug)]
+pub struct Raw<'a> {
+    /// buf
+    pub buf: &'a [u8],
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// Mutable representation of a payload of an unsupported protocol, e.g. PTP after an Ethernet
+/// header or GRE after an IPv4 header. The payload is kept as it is, so parsing and building
+/// a packet with an unknown protocol is byte-exact.
+pub struct RawMut {
+    /// buf
+    pub buf: Vec<u8>,
+    /// The `EtherType` or IP protocol number announcing the payload in the lower layer. A value
+    /// other than `0` is written to the lower layer on build, otherwise the field of the lower
+    /// layer is kept.
+    pub proto: u16,
+}
+
+impl RawMut {
+    /// Parses the payload as the given layer type and all of its upper layers
+    ///
+    /// # Errors
+    /// Returns the error of `LayerMut::from_buf` if the payload is no valid layer of the type
+    pub fn reinterpret(&self, kind: Layers) -> Result<LayerMut> {
+        LayerMut::from_buf(kind, self.buf.clone())
+    }
+
+    /// Parses the payload as a layer of the protocol and the upper layers announced by it
+    ///
+    /// # Errors
+    /// Returns the error of `CustomMut::parse` if the payload is too short for the header
+    pub fn reinterpret_as(&self, protocol: Arc<dyn Protocol>) -> Result<LayerMut> {
+        CustomMut::parse(protocol, self.buf.clone()).map(LayerMut::Custom)
+    }
+}
+
+impl<'a> LayerMutable<'a> for RawMut {
+    type PacketMut = MutablePayloadDummyPacket<'a>;
+    const LAYER: Layers = Layers::Raw;
+    const FIELDS: &'static [Field] = &[Field::new("load", 0, FieldKind::Bytes)];
+    fn new() -> Self {
+        Self {
+            buf: vec![],
+            proto: 0,
+        }
+    }
+
+    create_modify!();
+    create_set_payload!();
+
+    fn from_buf(buf: Vec<u8>) -> Result<Self> {
+        Ok(Self { buf, proto: 0 })
+    }
+
+    fn build(self) -> Result<Vec<u8>> {
+        Ok(self.buf)
+    }
+}
+
+impl<'a> LayerImmutable<'a> for Raw<'a> {
+    type Packet = PayloadDummyPacket<'a>;
+    type PacketMut = MutablePayloadDummyPacket<'a>;
+    type LayerMutType = RawMut;
+    const LAYER: Layers = Layers::Raw;
+
+    create_default_immutable!();
+    fn get_layer_from_buf(_buf: &'_ [u8], _layer: Layers) -> Option<Layer<'_>> {
+        None
+    }
+}
+
+impl Display for RawMut {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Raw (proto: 0x{:04x}, {} bytes)",
+            self.proto,
+            self.buf.len()
+        )
+    }
+}
@@ -1,13 +1,21 @@
+use crate::layers::options::OptionsHeader;
+use crate::registry::{self, Binding};
 use crate::{
-    Layer, LayerImmutable, LayerMut, LayerMutable, Layers, Payload, PayloadMut, create_add_layer,
-    create_default_immutable, create_get_layer, create_modify, create_set_payload,
-    create_switch_layer,
+    Error, Field, FieldKind, Layer, LayerImmutable, LayerMut, LayerMutable, Layers, Payload,
+    Result, create_add_layer, create_default_immutable, create_get_layer, create_modify,
+    create_set_payload, create_switch_layer, create_upper_layer,
 };
 use pnet::packet::Packet;
 use pnet::packet::tcp::{MutableTcpPacket, TcpPacket, ipv4_checksum, ipv6_checksum};
 use std::fmt::Display;
 use std::net::{Ipv4Addr, Ipv6Addr};
 
+mod options;
+pub use options::TcpOption;
+
+/// Options and header length of the mutable layer
+const HEADER: OptionsHeader = OptionsHeader::new(Layers::Tcp, 12, 4);
+
 /// Immutable representation of an UPD packet
 #[derive(Debug)]
 pub struct Tcp<'a> {
@@ -17,32 +25,83 @@ pub struct Tcp<'a> {
 #[derive(Debug, Clone)]
 /// Mutable representation of an UPD packet
 pub struct TcpMut {
-    buf: Vec<u8>,
-    upper_layer: Option<Box<LayerMut>>,
+    pub(crate) buf: Vec<u8>,
+    pub(crate) upper_layer: Option<Box<LayerMut>>,
 }
 
 impl TcpMut {
-    pub(super) fn build_from_ipv4(self, saddr: Ipv4Addr, daddr: Ipv4Addr) -> Option<Vec<u8>> {
+    /// Returns the parsed options of the TCP header
+    #[must_use]
+    pub fn options(&self) -> Vec<TcpOption> {
+        HEADER.options(&self.buf)
+    }
+
+    /// Replaces the options of the TCP header. The options are padded to a 32-bit boundary
+    /// and the data offset is updated.
+    ///
+    /// # Errors
+    /// Returns `Error::LengthOverflow` if the options are longer than 40 bytes
+    pub fn set_options(&mut self, options: &[TcpOption]) -> Result<()> {
+        HEADER.set_options(&mut self.buf, options)
+    }
+
+    /// Adds an option in front of the end of option list and bytes which could not be parsed
+    ///
+    /// # Errors
+    /// Returns `Error::LengthOverflow` if the options are getting longer than 40 bytes
+    pub fn add_option(&mut self, option: TcpOption) -> Result<()> {
+        HEADER.add_option(&mut self.buf, option)
+    }
+
+    /// Removes all options with the given option kind and returns them
+    ///
+    /// # Errors
+    /// Returns an error if the options could not be written back
+    pub fn remove_option(&mut self, kind: u8) -> Result<Vec<TcpOption>> {
+        HEADER.remove_option(&mut self.buf, kind)
+    }
+
+    pub(super) fn build_from_ipv4(self, saddr: Ipv4Addr, daddr: Ipv4Addr) -> Result<Vec<u8>> {
         let mut tcp_build = self.build()?;
         {
-            let mut tcp = MutableTcpPacket::new(&mut tcp_build)?;
+            let mut tcp = MutableTcpPacket::new(&mut tcp_build).ok_or(Error::Truncated {
+                layer: Layers::Tcp,
+                offset: 0,
+            })?;
             tcp.set_checksum(ipv4_checksum(&tcp.to_immutable(), &saddr, &daddr));
         }
 
-        Some(tcp_build)
+        Ok(tcp_build)
     }
-    pub(super) fn build_from_ipv6(self, saddr: Ipv6Addr, daddr: Ipv6Addr) -> Option<Vec<u8>> {
+    pub(super) fn build_from_ipv6(self, saddr: Ipv6Addr, daddr: Ipv6Addr) -> Result<Vec<u8>> {
         let mut udp_build = self.build()?;
         {
-            let mut udp = MutableTcpPacket::new(&mut udp_build)?;
+            let mut udp = MutableTcpPacket::new(&mut udp_build).ok_or(Error::Truncated {
+                layer: Layers::Tcp,
+                offset: 0,
+            })?;
             udp.set_checksum(ipv6_checksum(&udp.to_immutable(), &saddr, &daddr));
         }
-        Some(udp_build)
+        Ok(udp_build)
     }
 }
 
 impl<'a> LayerMutable<'a> for TcpMut {
     type PacketMut = MutableTcpPacket<'a>;
+    const LAYER: Layers = Layers::Tcp;
+    const FIELDS: &'static [Field] = &[
+        Field::int("sport", 0, 16),
+        Field::int("dport", 16, 16),
+        Field::int("seq", 32, 32),
+        Field::int("ack", 64, 32),
+        Field::int("dataofs", 96, 4),
+        Field::int("reserved", 100, 4),
+        Field::new("flags", 104, FieldKind::Flags("FSRPAUEC")),
+        Field::int("window", 112, 16),
+        Field::int("chksum", 128, 16),
+        Field::int("urgptr", 144, 16),
+        Field::new("options", 160, FieldKind::Bytes),
+    ];
     fn new() -> Self {
         Self {
             buf: vec![0; 20],
@@ -50,51 +109,77 @@ impl<'a> LayerMutable<'a> for TcpMut {
         }
     }
 
+    fn update_header(&mut self, field: &Field) {
+        if field.name == "options" {
+            HEADER.update_len(&mut self.buf);
+        }
+    }
+
     create_modify!();
-    create_set_payload!();
+    create_upper_layer!();
+    create_set_payload!(|buf: &mut [u8]| HEADER.sized_len(buf));
     create_switch_layer!();
-    create_add_layer!(Payload; {});
-    create_get_layer!(Payload);
+    create_add_layer!(Payload, Custom; {});
+    create_get_layer!(Payload, Custom);
 
-    fn from_buf(mut buf: Vec<u8>) -> Option<Self> {
-        let tcp = TcpPacket::new(&buf)?;
+    fn from_buf(mut buf: Vec<u8>) -> Result<Self> {
+        let tcp = TcpPacket::new(&buf).ok_or(Error::Truncated {
+            layer: Self::LAYER,
+            offset: 0,
+        })?;
+        if header_len(&tcp) > buf.len() {
+            return Err(Error::Truncated {
+                layer: Self::LAYER,
+                offset: 0,
+            });
+        }
 
         let payload = tcp.payload();
-        let mut upper_layer = None;
-
-        if !payload.is_empty() {
-            upper_layer = Some(Box::new(LayerMut::Payload(PayloadMut::from_buf(
-                payload.to_vec(),
-            )?)));
-        }
+        // a protocol bound to the destination or source port is parsed as custom layer
+        let upper_layer = registry::parse_ports(
+            Binding::TcpPort,
+            tcp.get_source(),
+            tcp.get_destination(),
+            payload,
+        )
+        .map_err(|e| e.offset_by(buf.len() - payload.len()))?
+        .map(Box::new);
 
         buf.resize(buf.len() - payload.len(), 0);
 
-        Some(Self { buf, upper_layer })
+        Ok(Self { buf, upper_layer })
     }
 
-    fn build(mut self) -> Option<Vec<u8>> {
+    fn build(mut self) -> Result<Vec<u8>> {
+        // options set as raw bytes are padded to a multiple of 4 bytes, the bytes after the
+        // header are a payload set by `set_payload`
+        let header_len = HEADER.build_len(&mut self.buf)?;
+
         let payload = match self.upper_layer {
             Some(child) => match *child {
                 LayerMut::Payload(arp) => arp.build()?,
-                _ => return None,
+                LayerMut::Custom(custom) => custom.build()?,
+                other => {
+                    return Err(Error::IllegalStacking {
+                        layer: other.kind(),
+                        lower: Self::LAYER,
+                    });
+                }
             },
             None => vec![],
         };
         self.buf.extend_from_slice(&payload);
 
-        let data_offset = 20 / 4;
-        if data_offset > u8::MAX as usize {
-            return None;
-        }
-
         #[allow(clippy::cast_possible_truncation)]
-        let data_offset = data_offset as u8;
+        let data_offset = (header_len / 4) as u8;
         {
-            let mut tcp = Self::PacketMut::new(self.buf.as_mut())?;
+            let mut tcp = Self::PacketMut::new(self.buf.as_mut()).ok_or(Error::Truncated {
+                layer: Self::LAYER,
+                offset: 0,
+            })?;
             tcp.set_data_offset(data_offset);
         }
-        Some(self.buf)
+        Ok(self.buf)
     }
 }
 
@@ -102,12 +187,13 @@ impl<'a> LayerImmutable<'a> for Tcp<'a> {
     type Packet = TcpPacket<'a>;
     type PacketMut = MutableTcpPacket<'a>;
     type LayerMutType = TcpMut;
+    const LAYER: Layers = Layers::Tcp;
 
     create_default_immutable!();
 
     fn get_layer_from_buf(buf: &'_ [u8], layer: Layers) -> Option<Layer<'_>> {
-        let tcp_size = TcpPacket::minimum_packet_size();
-        let buf = &buf[tcp_size..buf.len()];
+        let tcp = TcpPacket::new(buf)?;
+        let buf = buf.get(header_len(&tcp)..)?;
         if matches!(layer, Layers::Payload) {
             Some(Layer::Payload(Payload::new(buf)))
         } else {
@@ -116,6 +202,11 @@ impl<'a> LayerImmutable<'a> for Tcp<'a> {
     }
 }
 
+/// Returns the length of the header including the options
+pub(super) fn header_len(tcp: &TcpPacket) -> usize {
+    (usize::from(tcp.get_data_offset()) * 4).max(TcpPacket::minimum_packet_size())
+}
+
 impl Display for TcpMut {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         if let Some(eth) = TcpPacket::new(&self.buf) {
@@ -153,4 +244,63 @@ mod tests {
             assert_eq!(pnet.get_source(), 46060);
         }
     }
+
+    #[test]
+    fn test_tcp_options() {
+        // SYN with mss, sack permitted, timestamps, nop, window scale
+        let packet = hex::decode(
+            "c0de0050000003e800000000a0027210000000000204058c0402080a0001e24000000000010303070102",
+        )
+        .expect("failed");
+        let mut tcp = TcpMut::from_buf(packet.clone()).expect("could not parse");
+        assert_eq!(
+            tcp.options(),
+            vec![
+                TcpOption::Mss(1420),
+                TcpOption::SackPermitted,
+                TcpOption::Timestamps {
+                    value: 123_456,
+                    echo_reply: 0
+                },
+                TcpOption::Nop,
+                TcpOption::WindowScale(7),
+            ]
+        );
+        assert!(matches!(
+            tcp.get_layer(&Layers::Payload),
+            Some(LayerMut::Payload(payload)) if payload.buf == [1, 2]
+        ));
+        assert!(matches!(
+            Tcp::get_layer_from_buf(&packet, Layers::Payload),
+            Some(Layer::Payload(payload)) if payload.buf == [1, 2]
+        ));
+        assert_eq!(
+            &packet,
+            tcp.clone().build().expect("could not build").as_slice()
+        );
+
+        tcp.remove_option(8).expect("could not remove");
+        tcp.add_option(TcpOption::Sack(vec![(1, 2)]))
+            .expect("could not add");
+        let build = tcp.build().expect("could not build");
+        // mss (4) + sack perm (2) + nop (1) + wscale (3) + sack (10) = 20 bytes
+        assert_eq!(build[12] >> 4, 10);
+        assert_eq!(&build[30..], &[5, 10, 0, 0, 0, 1, 0, 0, 0, 2, 1, 2]);
+    }
+
+    #[test]
+    fn test_tcp_set_payload() {
+        let LayerMut::Tcp(mut tcp) =
+            LayerMut::parse("Tcp(options: 0x020405b4)").expect("could not parse")
+        else {
+            panic!("no tcp layer");
+        };
+        tcp.set_payload(&[1, 2, 3]);
+        tcp.set_payload(&[4, 5, 6]);
+        assert_eq!(tcp.options(), vec![TcpOption::Mss(1460)]);
+        tcp.add_option(TcpOption::Nop).expect("could not add");
+        let build = tcp.build().expect("could not build");
+        assert_eq!(build[12] >> 4, 7);
+        assert_eq!(&build[20..], &[2, 4, 5, 0xb4, 1, 0, 0, 0, 4, 5, 6]);
+    }
 }
@@ -0,0 +1,131 @@
+use crate::layers::options::TlvOption;
+
+/// TCP option, see `TcpMut::options`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TcpOption {
+    /// End of option list (0)
+    EndOfList,
+    /// No operation (1), used to align options
+    Nop,
+    /// Maximum segment size (2)
+    Mss(u16),
+    /// Window scale (3)
+    WindowScale(u8),
+    /// Selective acknowledgements permitted (4)
+    SackPermitted,
+    /// Selective acknowledgement (5) with the left and right edge of each block
+    Sack(Vec<(u32, u32)>),
+    /// Timestamps (8)
+    Timestamps {
+        /// Timestamp value
+        value: u32,
+        /// Timestamp echo reply
+        echo_reply: u32,
+    },
+    /// TCP MD5 signature (19) as defined in RFC 2385
+    Md5(Vec<u8>),
+    /// TCP authentication option (29) as defined in RFC 5925
+    Ao {
+        /// Key id of the master key tuple used to generate the MAC
+        key_id: u8,
+        /// Key id the sender is ready to receive
+        rnext_key_id: u8,
+        /// The message authentication code
+        mac: Vec<u8>,
+    },
+    /// Option with a kind not known by `pnet_layers`
+    Unknown {
+        /// The option kind
+        kind: u8,
+        /// Option data without the kind and length byte
+        data: Vec<u8>,
+    },
+    /// Bytes which could not be parsed as options. These are written as they are.
+    Raw(Vec<u8>),
+}
+
+impl TcpOption {
+    /// Returns the option kind
+    #[must_use]
+    pub fn kind(&self) -> Option<u8> {
+        Some(match self {
+            Self::EndOfList => 0,
+            Self::Nop => 1,
+            Self::Mss(_) => 2,
+            Self::WindowScale(_) => 3,
+            Self::SackPermitted => 4,
+            Self::Sack(_) => 5,
+            Self::Timestamps { .. } => 8,
+            Self::Md5(_) => 19,
+            Self::Ao { .. } => 29,
+            Self::Unknown { kind, .. } => *kind,
+            Self::Raw(_) => return None,
+        })
+    }
+}
+
+impl TlvOption for TcpOption {
+    const END_OF_LIST: Self = Self::EndOfList;
+    const NOP: Self = Self::Nop;
+
+    fn code(&self) -> Option<u8> {
+        self.kind()
+    }
+
+    fn data(&self) -> Vec<u8> {
+        match self {
+            Self::EndOfList | Self::Nop | Self::SackPermitted => vec![],
+            Self::Mss(mss) => mss.to_be_bytes().to_vec(),
+            Self::WindowScale(shift) => vec![*shift],
+            Self::Sack(blocks) => blocks
+                .iter()
+                .flat_map(|(left, right)| [left.to_be_bytes(), right.to_be_bytes()])
+                .flatten()
+                .collect(),
+            Self::Timestamps { value, echo_reply } => {
+                [value.to_be_bytes(), echo_reply.to_be_bytes()].concat()
+            }
+            Self::Md5(data) | Self::Unknown { data, .. } | Self::Raw(data) => data.clone(),
+            Self::Ao {
+                key_id,
+                rnext_key_id,
+                mac,
+            } => [&[*key_id, *rnext_key_id], mac.as_slice()].concat(),
+        }
+    }
+
+    fn parse(kind: u8, data: &[u8]) -> Self {
+        let u32_at =
+            |i: usize| u32::from_be_bytes([data[i], data[i + 1], data[i + 2], data[i + 3]]);
+        let option = match (kind, data.len()) {
+            (2, 2) => Some(Self::Mss(u16::from_be_bytes([data[0], data[1]]))),
+            (3, 1) => Some(Self::WindowScale(data[0])),
+            (4, 0) => Some(Self::SackPermitted),
+            (5, len) if len > 0 && len.is_multiple_of(8) => Some(Self::Sack(
+                (0..len)
+                    .step_by(8)
+                    .map(|i| (u32_at(i), u32_at(i + 4)))
+                    .collect(),
+            )),
+            (8, 8) => Some(Self::Timestamps {
+                value: u32_at(0),
+                echo_reply: u32_at(4),
+            }),
+            (19, 16) => Some(Self::Md5(data.to_vec())),
+            (29, len) if len >= 2 => Some(Self::Ao {
+                key_id: data[0],
+                rnext_key_id: data[1],
+                mac: data[2..].to_vec(),
+            }),
+            _ => None,
+        };
+        option.unwrap_or_else(|| Self::Unknown {
+            kind,
+            data: data.to_vec(),
+        })
+    }
+
+    fn raw(data: Vec<u8>) -> Self {
+        Self::Raw(data)
+    }
+}
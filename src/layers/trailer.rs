@@ -0,0 +1,60 @@
+use crate::layers::payload::{MutablePayloadDummyPacket, PayloadDummyPacket};
+use crate::{
+    Field, FieldKind, Layer, LayerImmutable, LayerMutable, Layers, Result,
+    create_default_immutable, create_modify, create_set_payload,
+};
+use std::fmt::Display;
+
+/// Immutable representation of the bytes following the layers of an Ethernet frame
+#[derive(Debug)]
+pub struct Trailer<'a> {
+    /// buf
+    pub buf: &'a [u8],
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// Mutable representation of the bytes following the layers of an Ethernet frame, e.g. the
+/// padding to the minimum frame size or a vendor trailer. The trailer is kept by the
+/// `EtherMut` and appended after all upper layers on build.
+pub struct TrailerMut {
+    /// buf
+    pub buf: Vec<u8>,
+}
+
+impl<'a> LayerMutable<'a> for TrailerMut {
+    type PacketMut = MutablePayloadDummyPacket<'a>;
+    const LAYER: Layers = Layers::Trailer;
+    const FIELDS: &'static [Field] = &[Field::new("load", 0, FieldKind::Bytes)];
+    fn new() -> Self {
+        Self { buf: vec![] }
+    }
+
+    create_modify!();
+    create_set_payload!();
+
+    fn from_buf(buf: Vec<u8>) -> Result<Self> {
+        Ok(Self { buf })
+    }
+
+    fn build(self) -> Result<Vec<u8>> {
+        Ok(self.buf)
+    }
+}
+
+impl<'a> LayerImmutable<'a> for Trailer<'a> {
+    type Packet = PayloadDummyPacket<'a>;
+    type PacketMut = MutablePayloadDummyPacket<'a>;
+    type LayerMutType = TrailerMut;
+    const LAYER: Layers = Layers::Trailer;
+
+    create_default_immutable!();
+    fn get_layer_from_buf(_buf: &'_ [u8], _layer: Layers) -> Option<Layer<'_>> {
+        None
+    }
+}
+
+impl Display for TrailerMut {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Trailer ({} bytes)", self.buf.len())
+    }
+}
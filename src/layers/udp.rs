@@ -1,7 +1,8 @@
+use crate::registry::{self, Binding};
 use crate::{
-    Layer, LayerImmutable, LayerMut, LayerMutable, Layers, Payload, PayloadMut, create_add_layer,
-    create_default_immutable, create_get_layer, create_modify, create_set_payload,
-    create_switch_layer,
+    Error, Field, Layer, LayerImmutable, LayerMut, LayerMutable, Layers, Payload, Result,
+    create_add_layer, create_default_immutable, create_get_layer, create_modify,
+    create_set_payload, create_switch_layer, create_upper_layer,
 };
 use pnet::packet::Packet;
 use pnet::packet::udp::{MutableUdpPacket, UdpPacket, ipv4_checksum, ipv6_checksum};
@@ -17,33 +18,46 @@ pub struct Udp<'a> {
 #[derive(Clone)]
 /// Mutable representation of an UPD packet
 pub struct UdpMut {
-    buf: Vec<u8>,
+    pub(crate) buf: Vec<u8>,
     /// the upper layer
     pub upper_layer: Option<Box<LayerMut>>,
 }
 
 impl UdpMut {
-    pub(super) fn build_from_ipv4(self, saddr: Ipv4Addr, daddr: Ipv4Addr) -> Option<Vec<u8>> {
+    pub(super) fn build_from_ipv4(self, saddr: Ipv4Addr, daddr: Ipv4Addr) -> Result<Vec<u8>> {
         let mut udp_build = self.build()?;
         {
-            let mut udp = MutableUdpPacket::new(&mut udp_build)?;
+            let mut udp = MutableUdpPacket::new(&mut udp_build).ok_or(Error::Truncated {
+                layer: Layers::Udp,
+                offset: 0,
+            })?;
             udp.set_checksum(ipv4_checksum(&udp.to_immutable(), &saddr, &daddr));
         }
 
-        Some(udp_build)
+        Ok(udp_build)
     }
-    pub(super) fn build_from_ipv6(self, saddr: Ipv6Addr, daddr: Ipv6Addr) -> Option<Vec<u8>> {
+    pub(super) fn build_from_ipv6(self, saddr: Ipv6Addr, daddr: Ipv6Addr) -> Result<Vec<u8>> {
         let mut udp_build = self.build()?;
         {
-            let mut udp = MutableUdpPacket::new(&mut udp_build)?;
+            let mut udp = MutableUdpPacket::new(&mut udp_build).ok_or(Error::Truncated {
+                layer: Layers::Udp,
+                offset: 0,
+            })?;
             udp.set_checksum(ipv6_checksum(&udp.to_immutable(), &saddr, &daddr));
         }
-        Some(udp_build)
+        Ok(udp_build)
     }
 }
 
 impl<'a> LayerMutable<'a> for UdpMut {
     type PacketMut = MutableUdpPacket<'a>;
+    const LAYER: Layers = Layers::Udp;
+    const FIELDS: &'static [Field] = &[
+        Field::int("sport", 0, 16),
+        Field::int("dport", 16, 16),
+        Field::int("len", 32, 16),
+        Field::int("chksum", 48, 16),
+    ];
     fn new() -> Self {
         Self {
             buf: vec![0; UdpPacket::minimum_packet_size()],
@@ -52,49 +66,67 @@ impl<'a> LayerMutable<'a> for UdpMut {
     }
 
     create_modify!();
+    create_upper_layer!();
     create_set_payload!();
     create_switch_layer!();
-    create_add_layer!(Payload; { });
-    create_get_layer!(Payload);
+    create_add_layer!(Payload, Custom; { });
+    create_get_layer!(Payload, Custom);
 
-    fn from_buf(mut buf: Vec<u8>) -> Option<Self> {
-        let udp = UdpPacket::new(&buf)?;
+    fn from_buf(mut buf: Vec<u8>) -> Result<Self> {
+        let udp = UdpPacket::new(&buf).ok_or(Error::Truncated {
+            layer: Self::LAYER,
+            offset: 0,
+        })?;
 
         let payload = udp.payload();
-        let mut upper_layer = None;
-
-        if !payload.is_empty() {
-            upper_layer = Some(Box::new(LayerMut::Payload(PayloadMut::from_buf(
-                payload.to_vec(),
-            )?)));
-        }
+        // a protocol bound to the destination or source port is parsed as custom layer
+        let upper_layer = registry::parse_ports(
+            Binding::UdpPort,
+            udp.get_source(),
+            udp.get_destination(),
+            payload,
+        )
+        .map_err(|e| e.offset_by(buf.len() - payload.len()))?
+        .map(Box::new);
 
         buf.resize(buf.len() - payload.len(), 0);
 
-        Some(Self { buf, upper_layer })
+        Ok(Self { buf, upper_layer })
     }
 
-    fn build(mut self) -> Option<Vec<u8>> {
+    fn build(mut self) -> Result<Vec<u8>> {
         let payload = match self.upper_layer {
             Some(child) => match *child {
                 LayerMut::Payload(a) => a.build()?,
-                _ => return None,
+                LayerMut::Custom(custom) => custom.build()?,
+                other => {
+                    return Err(Error::IllegalStacking {
+                        layer: other.kind(),
+                        lower: Self::LAYER,
+                    });
+                }
             },
             None => vec![],
         };
         self.buf.extend_from_slice(&payload);
 
         if self.buf.len() > u16::MAX as usize {
-            return None;
+            return Err(Error::LengthOverflow {
+                layer: Self::LAYER,
+                length: self.buf.len(),
+            });
         }
 
         #[allow(clippy::cast_possible_truncation)]
         let len = self.buf.iter().len() as u16;
         {
-            let mut udp = Self::PacketMut::new(self.buf.as_mut())?;
+            let mut udp = Self::PacketMut::new(self.buf.as_mut()).ok_or(Error::Truncated {
+                layer: Self::LAYER,
+                offset: 0,
+            })?;
             udp.set_length(len);
         }
-        Some(self.buf)
+        Ok(self.buf)
     }
 }
 
@@ -102,12 +134,12 @@ impl<'a> LayerImmutable<'a> for Udp<'a> {
     type Packet = UdpPacket<'a>;
     type PacketMut = MutableUdpPacket<'a>;
     type LayerMutType = UdpMut;
+    const LAYER: Layers = Layers::Udp;
 
     create_default_immutable!();
 
     fn get_layer_from_buf(buf: &'_ [u8], layer: Layers) -> Option<Layer<'_>> {
-        let size = UdpPacket::minimum_packet_size();
-        let buf = &buf[size..buf.len()];
+        let buf = buf.get(UdpPacket::minimum_packet_size()..)?;
         if matches!(layer, Layers::Payload) {
             Some(Layer::Payload(Payload::new(buf)))
         } else {
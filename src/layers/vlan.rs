@@ -1,11 +1,11 @@
 use crate::layers::ether::get_layer_with_ether_type;
+use crate::registry;
 use crate::{
-    ArpMut, Ipv4Mut, Ipv6Mut, Layer, LayerImmutable, LayerMut, LayerMutable, Layers,
-    create_add_layer, create_default_immutable, create_from_buf, create_get_layer, create_modify,
-    create_set_payload,
+    Error, Field, Layer, LayerImmutable, LayerMut, LayerMutable, Layers, Result, create_add_layer,
+    create_default_immutable, create_from_buf, create_get_layer, create_modify, create_set_payload,
+    create_upper_layer,
 };
-use pnet::packet::Packet;
-use pnet::packet::ethernet::EtherTypes;
+use pnet::packet::ethernet::EtherType;
 use pnet::packet::vlan::{MutableVlanPacket, VlanPacket};
 use std::fmt::{Debug, Display};
 
@@ -18,13 +18,20 @@ pub struct Vlan<'a> {
 #[derive(Clone)]
 /// Mutable representation of an VLAN packet
 pub struct VlanMut {
-    buf: Vec<u8>,
+    pub(crate) buf: Vec<u8>,
     /// The upper layer of the vlan
     pub upper_layer: Option<Box<LayerMut>>,
 }
 
 impl<'a> LayerMutable<'a> for VlanMut {
     type PacketMut = MutableVlanPacket<'a>;
+    const LAYER: Layers = Layers::Vlan;
+    const FIELDS: &'static [Field] = &[
+        Field::int("prio", 0, 3),
+        Field::int("dei", 3, 1),
+        Field::int("id", 4, 12),
+        Field::int("type", 16, 16),
+    ];
     fn new() -> Self {
         Self {
             buf: vec![0; VlanPacket::minimum_packet_size()],
@@ -39,37 +46,20 @@ impl<'a> LayerMutable<'a> for VlanMut {
     }
 
     create_modify!();
+    create_upper_layer!();
     create_set_payload!();
-    create_add_layer!(Vlan, Ipv6, Ipv4, Arp; { Ipv6 => Vlan, Ipv4 => Vlan, Arp => Vlan});
-    create_get_layer!(Vlan, Ipv4, Ipv6, Arp);
-    create_from_buf!(
-        VlanPacket,
-        get_ethertype,
-        EtherTypes,
-        Vlan => VlanMut,
-        Ipv4 => Ipv4Mut,
-        Ipv6 => Ipv6Mut,
-        Arp => ArpMut
-    );
+    create_add_layer!(Vlan, Ipv6, Ipv4, Arp, Raw, Custom; { Ipv6 => Vlan, Ipv4 => Vlan, Arp => Vlan});
+    create_get_layer!(Vlan, Ipv4, Ipv6, Arp, Raw, Custom);
+    create_from_buf!(VlanPacket, get_ethertype);
 
-    fn build(mut self) -> Option<Vec<u8>> {
-        if self.upper_layer.is_some() {
-            #[allow(clippy::unwrap_used)]
-            if matches!(**self.upper_layer.as_ref().unwrap(), LayerMut::Ipv4(_)) {
-                self.modify()?.set_ethertype(EtherTypes::Ipv4);
-            }
-            #[allow(clippy::unwrap_used)]
-            if matches!(**self.upper_layer.as_ref().unwrap(), LayerMut::Ipv6(_)) {
-                self.modify()?.set_ethertype(EtherTypes::Ipv6);
-            }
-            #[allow(clippy::unwrap_used)]
-            if matches!(**self.upper_layer.as_ref().unwrap(), LayerMut::Vlan(_)) {
-                self.modify()?.set_ethertype(EtherTypes::Vlan);
-            }
-            #[allow(clippy::unwrap_used)]
-            if matches!(**self.upper_layer.as_ref().unwrap(), LayerMut::Arp(_)) {
-                self.modify()?.set_ethertype(EtherTypes::Arp);
-            }
+    fn build(mut self) -> Result<Vec<u8>> {
+        let ethertype = self
+            .upper_layer
+            .as_deref()
+            .and_then(registry::ether_type)
+            .map(EtherType);
+        if let Some(ethertype) = ethertype {
+            self.modify()?.set_ethertype(ethertype);
         }
         let payload = match self.upper_layer {
             Some(child) => match *child {
@@ -77,12 +67,19 @@ impl<'a> LayerMutable<'a> for VlanMut {
                 LayerMut::Ipv6(ipv6) => ipv6.build()?,
                 LayerMut::Vlan(vlan) => vlan.build()?,
                 LayerMut::Arp(arp) => arp.build()?,
-                _ => panic!("child not possible"),
+                LayerMut::Raw(raw) => raw.build()?,
+                LayerMut::Custom(custom) => custom.build()?,
+                other => {
+                    return Err(Error::IllegalStacking {
+                        layer: other.kind(),
+                        lower: Self::LAYER,
+                    });
+                }
             },
             None => vec![],
         };
         self.buf.extend_from_slice(&payload);
-        Some(self.buf)
+        Ok(self.buf)
     }
 }
 
@@ -90,6 +87,7 @@ impl<'a> LayerImmutable<'a> for Vlan<'a> {
     type Packet = VlanPacket<'a>;
     type PacketMut = MutableVlanPacket<'a>;
     type LayerMutType = VlanMut;
+    const LAYER: Layers = Layers::Vlan;
 
     create_default_immutable!();
 
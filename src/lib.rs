@@ -15,22 +15,22 @@
 //!
 //! let mut ether = EtherMut::new();
 //!
-//! if let Some(mut eth) = ether.modify() {
+//! if let Ok(mut eth) = ether.modify() {
 //!     eth.set_source(MacAddr::from_str("3c:ce:33:33:33:33").unwrap());
 //!     eth.set_destination(MacAddr::broadcast());
 //! }
 //!
-//! ether.add(LayerMut::Vlan(VlanMut::new()));
-//! ether.add(LayerMut::Ipv4(Ipv4Mut::new()));
-//! ether.add(LayerMut::Udp(UdpMut::new()));
-//! ether.add(LayerMut::Payload(PayloadMut::from_buf(vec![10; 10]).unwrap()));
+//! ether.add(LayerMut::Vlan(VlanMut::new())).unwrap();
+//! ether.add(LayerMut::Ipv4(Ipv4Mut::new())).unwrap();
+//! ether.add(LayerMut::Udp(UdpMut::new())).unwrap();
+//! ether.add(LayerMut::Payload(PayloadMut::from_buf(vec![10; 10]).unwrap())).unwrap();
 //!
-//! for vlan_id in [1u16, 2, 3, 4] {
-//!     let mut ether = ether.clone();
-//!     if let Some(LayerMut::Vlan(vlan)) = ether.get_layer(&Layers::Vlan) {
-//!         vlan.modify().unwrap().set_vlan_identifier(vlan_id);
-//!     }
+//! // a template yields a copy of the stack for each value of the VLAN id
+//! let mut template = template::Template::new(ether);
+//! template.set(Layers::Vlan, "id", template::Values::Range(1..=4)).unwrap();
 //!
+//! for ether in &template {
+//!     let ether = ether.unwrap();
 //!     println!("{ether}");
 //!     // Ether (s: 3c:ce:33:33:33:33, d: 3c:ce:33:33:33:33) > Vlan (id: 1) > Ipv4 (s: 0.0.0.0, d: 0.0.0.0) > Udp (s: 0, d: 0) > [10, 10, 10, 10, 10, 10, 10, 10, 10, 10]
 //!     // Ether (s: 3c:ce:33:33:33:33, d: 3c:ce:33:33:33:33) > Vlan (id: 2) > Ipv4 (s: 0.0.0.0, d: 0.0.0.0) > Udp (s: 0, d: 0) > [10, 10, 10, 10, 10, 10, 10, 10, 10, 10]
@@ -48,12 +48,12 @@
 //!     // when doing an firewall test. See all magic bytes in the
 //!     // `magics.rs` file
 //!
-//!     if let Some(bytes) = ether.build() {
+//!     if let Ok(bytes) = ether.build() {
 //!         // bytes now be send
 //!         // let _ = tx.send_to(&bytes, None);
 //!
 //!         // EtherMut can also be created from a buffer...
-//!         if let Some(mut send) = EtherMut::from_buf(bytes) {
+//!         if let Ok(mut send) = EtherMut::from_buf(bytes) {
 //!             println!("{send}");
 //!             // Ether (s: 3c:ce:33:33:33:33, d: 3c:ce:33:33:33:33) > Vlan (id: 1) > Ipv4 (s: 0.0.0.0, d: 0.0.0.0) > Udp (s: 0, d: 0) > [10, 10, 10, 10, 10, 10, 10, 10, 10, 10]
 //!             // ...
@@ -66,26 +66,100 @@
 //!
 //! ```
 
+mod error;
+mod field;
 mod layers;
 #[macro_use]
 mod macros;
 mod craft;
+mod serialize;
 
 /// Some functions to easier craft specific packets
 pub mod helper;
 
+pub mod answers;
+pub mod fuzz;
+pub mod io;
+
 pub mod magics;
+pub mod overrides;
+pub mod pcap;
+pub mod registry;
+pub mod sr;
+pub mod template;
 /// optional traits
 pub mod traits;
+pub mod validate;
 
+pub use crate::error::{Error, Result};
+pub use crate::field::{Field, FieldKind, Value};
 pub use crate::layers::arp::{Arp, ArpMut};
+pub use crate::layers::custom::{Custom, CustomMut};
 pub use crate::layers::ether::{Ether, EtherMut};
-pub use crate::layers::icmp::{Icmp, IcmpMut};
-pub use crate::layers::ipv4::{Ipv4, Ipv4Mut};
+pub use crate::layers::icmp::{Icmp, IcmpMessage, IcmpMut};
+pub use crate::layers::icmpv6::{Icmpv6, Icmpv6Message, Icmpv6Mut, MldRecord, NdpOption};
+pub use crate::layers::ipv4::{Ipv4, Ipv4Mut, Ipv4Option};
 pub use crate::layers::ipv6::{Ipv6, Ipv6Mut};
+pub use crate::layers::ipv6_ext::{
+    Ah, AhMut, DestOpts, DestOptsMut, Fragment, FragmentMut, HopByHop, HopByHopMut, Ipv6ExtOption,
+    Routing, RoutingMut,
+};
+pub use crate::layers::iter::{LayerIter, LayerSpan};
 pub use crate::layers::payload::{Payload, PayloadMut};
-pub use crate::layers::tcp::{Tcp, TcpMut};
+pub use crate::layers::raw::{Raw, RawMut};
+pub use crate::layers::tcp::{Tcp, TcpMut, TcpOption};
+pub use crate::layers::trailer::{Trailer, TrailerMut};
 pub use crate::layers::udp::{Udp, UdpMut};
 pub use crate::layers::vlan::{Vlan, VlanMut};
 pub use layers::Layers;
-pub use layers::{Layer, LayerImmutable, LayerMut, LayerMutable};
+pub use layers::{Layer, LayerImmutable, LayerMut, LayerMutable, LayersMut};
+
+/// Defines a layer from a `pnet_macros::packet` struct. The derive is placed below `#[packet]`
+/// and generates the `<Name>Layer` protocol, which is bound in the `registry` and parsed and
+/// crafted as `LayerMut::Custom`. All header fields before the `#[payload]` can be accessed by
+/// their name, a variable length field only as the last header field. Like the built-in
+/// layers, the header is also available as `<Name>Ref` implementing `LayerImmutable` and
+/// `<Name>Mut` implementing `LayerMutable` and `Display`, which converts into the
+/// `LayerMut::Custom` of the protocol.
+///
+/// The `#[layer(..)]` attribute accepts:
+/// - `name = "Name"` the name of the layer, defaults to the name of the struct
+/// - `next = ether_type(field)` the field announcing the payload, also `ip_proto`, `udp_port`
+///   or `tcp_port`. An `ether_type` or `ip_proto` field is set on build from the upper layer.
+/// - `length = field` set on build to the length of the header and payload
+/// - `payload_length = field` set on build to the length of the payload
+/// - `checksum = field` set on build to the internet checksum of the header and payload
+/// - `build = path` a function called on build with the arguments of `Protocol::build`
+/// - `bind(ether_type = 0x88b5, udp_port = 4789)` the bindings of `<Name>Layer::register`
+///
+/// ```
+/// use pnet_layers::{Layer, LayerMut, Value};
+/// use pnet_macros::packet;
+/// use pnet_macros_support::types::u16be;
+///
+/// #[packet]
+/// #[derive(Layer)]
+/// #[layer(next = ether_type(proto), bind(ether_type = 0x88b7))]
+/// pub struct Tag {
+///     pub id: u16be,
+///     pub proto: u16be,
+///     #[payload]
+///     pub payload: Vec<u8>,
+/// }
+///
+/// TagLayer::register();
+/// let packet = LayerMut::parse("Ether / Tag(id: 7) / Ipv4 / Udp")?;
+/// let bytes = packet.build()?;
+/// assert_eq!(bytes[12..18], [0x88, 0xb7, 0, 7, 0x08, 0x00]);
+/// let tag = TagLayer::from_buf(bytes[14..].to_vec())?;
+/// assert_eq!(TagLayer::packet(&tag).map(|tag| tag.get_id()), Some(7));
+/// assert_eq!(tag.get_field("proto"), Some(Value::Int(0x0800)));
+/// # Ok::<(), pnet_layers::Error>(())
+/// ```
+pub use pnet_layers_derive::Layer;
+
+/// Items used by the code generated by `#[derive(Layer)]`
+#[doc(hidden)]
+pub mod __private {
+    pub use pnet::util::checksum;
+}
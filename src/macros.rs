@@ -24,20 +24,31 @@ macro_rules! create_get_layer {
 /// create `add_layer` function
 #[macro_export]
 macro_rules! create_add_layer {
-    ($($layer:ident),*; { $($upper_layer:ident => $lower_layer:ident),* }) => {
+    // `trailer` is given for layers keeping a `Trailer` after all upper layers
+    ($($layer:ident),*; { $($upper_layer:ident => $lower_layer:ident),* } $(; $trailer:ident)?) => {
     // ($layer:ident, $($replace_layer:ident => $with_layer:ident),*) => {
 
 
         #[allow(unused_mut)]
-        fn add(&mut self, mut layer: LayerMut) -> bool {
+        fn add(&mut self, mut layer: LayerMut) -> $crate::Result<()> {
+            $(
+                // the first trailer belongs to the outermost layer, further ones to the upper
+                // layers like they are written by `LayerMut::canonical`
+                if matches!(layer, LayerMut::Trailer(_)) && self.$trailer.is_none() {
+                    if let LayerMut::Trailer(trailer) = layer {
+                        self.$trailer = Some(trailer);
+                    }
+                    return Ok(());
+                }
+            )?
             #[allow(unused)]
             if let Some(upper) = &mut self.upper_layer {
                 $(
                     if matches!(**upper, LayerMut::$upper_layer(_)) && matches!(layer, LayerMut::$lower_layer(_)) {
                         // If upper layer is $replace_layer and the layer to add is $layer, replace it
-                        layer.add(*upper.to_owned());
+                        layer.add(*upper.to_owned())?;
                         self.upper_layer.replace(Box::new(layer));
-                        return true;
+                        return Ok(());
                     }
                 )*
 
@@ -49,11 +60,16 @@ macro_rules! create_add_layer {
                 matches!(layer, LayerMut::$layer(_)) ||
             )* false {
                 self.upper_layer.replace(Box::new(layer));
-                true
+                Ok(())
             } else {
-                false
+                Err($crate::Error::IllegalStacking {
+                    layer: layer.kind(),
+                    lower: Self::LAYER,
+                })
             }
         }
+
+        const UPPER: &'static [Layers] = &[$(Layers::$layer),*];
     };
 }
 
@@ -61,8 +77,40 @@ macro_rules! create_add_layer {
 #[macro_export]
 macro_rules! create_modify {
     () => {
-        fn modify(&'a mut self) -> Option<Self::PacketMut> {
-            Self::PacketMut::new(self.buf.as_mut())
+        fn modify(&'a mut self) -> $crate::Result<Self::PacketMut> {
+            Self::PacketMut::new(self.buf.as_mut()).ok_or($crate::Error::Truncated {
+                layer: Self::LAYER,
+                offset: 0,
+            })
+        }
+    };
+}
+
+/// create `upper_layer` function
+#[macro_export]
+macro_rules! create_upper_layer {
+    () => {
+        fn upper_layer(&self) -> Option<&LayerMut> {
+            self.upper_layer.as_deref()
+        }
+
+        fn upper_layer_mut(&mut self) -> Option<&mut LayerMut> {
+            self.upper_layer.as_deref_mut()
+        }
+
+        fn replace_upper_layer(
+            &mut self,
+            layer: Option<LayerMut>,
+        ) -> $crate::Result<Option<LayerMut>> {
+            if let Some(layer) = &layer
+                && !self.accepts(&layer.kind())
+            {
+                return Err($crate::Error::IllegalStacking {
+                    layer: layer.kind(),
+                    lower: Self::LAYER,
+                });
+            }
+            Ok(std::mem::replace(&mut self.upper_layer, layer.map(Box::new)).map(|upper| *upper))
         }
     };
 }
@@ -71,21 +119,21 @@ macro_rules! create_modify {
 #[macro_export]
 macro_rules! create_set_payload {
     () => {
+        $crate::create_set_payload!(|_: &mut [u8]| Self::PacketMut::minimum_packet_size());
+    };
+    // `$header_len` returns the length of the header in front of the payload for layers with
+    // a variable header length
+    ($header_len:expr) => {
         fn set_payload(&'a mut self, payload: &[u8]) {
-            let header_size = Self::PacketMut::minimum_packet_size();
-            let len = self.buf.len() - header_size;
+            let header_size = ($header_len)(&mut self.buf);
+            let len = self.buf.len().saturating_sub(header_size);
             if payload.len() > len {
-                let increase = payload.len() - len;
-                println!("increased packet by: {increase}");
-                self.buf.resize((self.buf.len() + increase), 0);
+                log::debug!("increased packet by: {}", payload.len() - len);
             } else {
-                let increase = len - payload.len();
-                println!("decreased packet by: {increase}");
-                self.buf.resize((self.buf.len() - increase), 0);
-            }
-            for i in header_size..self.buf.len() {
-                self.buf[i] = payload[i - header_size];
+                log::debug!("decreased packet by: {}", len - payload.len());
             }
+            self.buf.resize(header_size, 0);
+            self.buf.extend_from_slice(payload);
         }
     };
 }
@@ -102,16 +150,22 @@ macro_rules! create_default_immutable {
             &self.buf
         }
 
-        fn as_mut(&self) -> Option<Self::LayerMutType> {
+        fn as_mut(&self) -> $crate::Result<Self::LayerMutType> {
             Self::LayerMutType::from_buf(self.buf.to_vec())
         }
 
-        fn as_pnet(&self) -> Option<Self::Packet> {
-            Self::Packet::new(self.buf)
+        fn as_pnet(&self) -> $crate::Result<Self::Packet> {
+            Self::Packet::new(self.buf).ok_or($crate::Error::Truncated {
+                layer: Self::LAYER,
+                offset: 0,
+            })
         }
 
-        fn as_mut_pnet(&self) -> Option<Self::PacketMut> {
-            Self::PacketMut::owned(self.buf.to_vec())
+        fn as_mut_pnet(&self) -> $crate::Result<Self::PacketMut> {
+            Self::PacketMut::owned(self.buf.to_vec()).ok_or($crate::Error::Truncated {
+                layer: Self::LAYER,
+                offset: 0,
+            })
         }
 
         fn get_layer(&'_ self, layer: Layers) -> Option<Layer<'_>> {
@@ -121,42 +175,67 @@ macro_rules! create_default_immutable {
 }
 
 #[macro_export]
-/// creates `from_buf` function
+/// creates `from_buf` function, the upper layer is parsed as one of the layer types in
+/// `LayerMutable::UPPER` or with a protocol bound in the registry
 macro_rules! create_from_buf {
-    ($pnet_packet:ident, $get_next_level_protocol:ident, $next_level_proto:ident, $($proto:ident => $mut_proto:ident),*) => {
-        fn from_buf(mut buf: Vec<u8>) -> Option<Self> {
-            let mut pkt = $pnet_packet::new(&buf)?;
-
-            let header_len = $pnet_packet::minimum_packet_size();
-
-
-            let mut payload = pkt.payload();
-            let ethernet_padding = buf.len() - header_len - payload.len();
-
-            if ethernet_padding > 0 {
-                buf.resize(buf.len() - ethernet_padding, 0);
-                pkt = $pnet_packet::new(&buf)?;
-                payload = pkt.payload();
-            }
-
-            let upper_layer = match pkt.$get_next_level_protocol() {
-                $(
-                    $next_level_proto::$proto => {
-                        log::debug!("Next level is {}", pkt.$get_next_level_protocol());
-                        Some(Box::new(LayerMut::$proto($mut_proto::from_buf(payload.to_vec())?)))
-                    }
-                )*
-                _ => {
-                    log::debug!("Next level not supported {}", pkt.$get_next_level_protocol());
-                    None
-                }
-            };
+    ($pnet_packet:ident, $get_next_level_protocol:ident) => {
+        $crate::create_from_buf!(
+            $pnet_packet,
+            $get_next_level_protocol,
+            |_: &$pnet_packet| $pnet_packet::minimum_packet_size()
+        );
+    };
+    // `$header_len` returns the length of the header for layers with a variable header length
+    ($pnet_packet:ident, $get_next_level_protocol:ident, $header_len:expr) => {
+        fn from_buf(mut buf: Vec<u8>) -> $crate::Result<Self> {
+            let (header_len, next) = $crate::create_from_buf!(@header $pnet_packet, $get_next_level_protocol, $header_len, buf);
+            let payload = buf.split_off(header_len);
+            let upper_layer = $crate::registry::parse_next(next, payload, Self::UPPER)
+                .map_err(|e| e.offset_by(header_len))?
+                .map(Box::new);
 
-            buf.resize(buf.len() - payload.len(), 0);
+            Ok(Self { buf, upper_layer })
+        }
+    };
+    // `$payload_len` returns the length of the payload given by the header or `None` if the
+    // payload fills the buffer, the bytes after the payload are kept as `trailer`
+    ($pnet_packet:ident, $get_next_level_protocol:ident, $header_len:expr, $payload_len:expr; trailer) => {
+        fn from_buf(mut buf: Vec<u8>) -> $crate::Result<Self> {
+            let (header_len, next) = $crate::create_from_buf!(@header $pnet_packet, $get_next_level_protocol, $header_len, buf);
+            let payload_len = $pnet_packet::new(&buf)
+                .and_then(|pkt| ($payload_len)(&pkt))
+                .map_or(buf.len(), |len: usize| header_len.saturating_add(len));
+            let trailer = (payload_len < buf.len()).then(|| $crate::TrailerMut {
+                buf: buf.split_off(payload_len),
+            });
+            let payload = buf.split_off(header_len);
+            let upper_layer = $crate::registry::parse_next(next, payload, Self::UPPER)
+                .map_err(|e| e.offset_by(header_len))?
+                .map(Box::new);
 
-            Some(Self { buf, upper_layer })
+            Ok(Self {
+                buf,
+                upper_layer,
+                trailer,
+            })
         }
     };
+    // checks the header and returns its length and the binding of the next layer
+    (@header $pnet_packet:ident, $get_next_level_protocol:ident, $header_len:expr, $buf:ident) => {{
+        let pkt = $pnet_packet::new(&$buf).ok_or($crate::Error::Truncated {
+            layer: Self::LAYER,
+            offset: 0,
+        })?;
+        let header_len = ($header_len)(&pkt);
+        if header_len > $buf.len() {
+            return Err($crate::Error::Truncated {
+                layer: Self::LAYER,
+                offset: 0,
+            });
+        }
+        log::debug!("Next level is {}", pkt.$get_next_level_protocol());
+        (header_len, Some(pkt.$get_next_level_protocol().into()))
+    }};
 }
 
 #[macro_export]
@@ -164,7 +243,7 @@ macro_rules! create_from_buf {
 macro_rules! create_switch_layer {
     () => {
         fn switch_src_dst(&mut self) {
-            if let Some(mut pkt) = self.modify() {
+            if let Ok(mut pkt) = self.modify() {
                 let src = pkt.get_source();
                 pkt.set_source(pkt.get_destination());
                 pkt.set_destination(src);
@@ -174,6 +253,14 @@ macro_rules! create_switch_layer {
             }
         }
     };
+    // for layers without a source and destination which only propagate to the upper layer
+    (upper) => {
+        fn switch_src_dst(&mut self) {
+            if let Some(upper_layer) = self.upper_layer.as_mut() {
+                upper_layer.switch_src_dst();
+            }
+        }
+    };
 }
 
 #[macro_export]
@@ -182,7 +269,7 @@ macro_rules! layers {
     ($($proto:ident => $mut_proto:ident),*) => {
 
 
-        #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        #[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
         /// Supported layer types to make them identifiable
         pub enum Layers {
             $(
@@ -211,8 +298,280 @@ macro_rules! layers {
         }
 
 
+        impl<'a> Layer<'a> {
+            /// Creates the immutable layer of the given type from the buffer starting with it
+            #[must_use]
+            pub fn new(kind: Layers, buf: &'a [u8]) -> Self {
+                match kind {
+                    $(Layers::$proto => Self::$proto(<$proto as LayerImmutable>::new(buf))),*
+                }
+            }
+
+            /// Returns the layer type of the layer
+            #[must_use]
+            pub fn kind(&self) -> Layers {
+                match self {
+                    $(Self::$proto(_) => Layers::$proto),*
+                }
+            }
+
+            /// Returns the buffer starting with the layer
+            #[must_use]
+            pub fn get_buf(&self) -> &[u8] {
+                match self {
+                    $(Self::$proto(a) => a.get_buf()),*
+                }
+            }
+        }
+
+        impl Layers {
+            /// Returns the layer type with the given name, the name is case insensitive
+            #[must_use]
+            pub fn from_name(name: &str) -> Option<Self> {
+                $(
+                    if name.eq_ignore_ascii_case(stringify!($proto)) {
+                        return Some(Self::$proto);
+                    }
+                )*
+                None
+            }
+
+            /// Returns the name of the layer type
+            #[must_use]
+            pub fn name(self) -> &'static str {
+                match self {
+                    $(Self::$proto => stringify!($proto)),*
+                }
+            }
+
+            /// Returns the fields of the layer header which can be accessed by their name
+            #[must_use]
+            pub fn fields(self) -> &'static [Field] {
+                match self {
+                    $(Self::$proto => <$mut_proto as LayerMutable>::FIELDS),*
+                }
+            }
+
+            /// Returns the layer types allowed directly on top of a layer of this type
+            #[must_use]
+            pub fn upper(self) -> &'static [Layers] {
+                match self {
+                    $(Self::$proto => <$mut_proto as LayerMutable>::UPPER),*
+                }
+            }
+        }
+
         impl LayerMut {
-            pub(crate) fn add(&mut self, layer: LayerMut) -> bool {
+            /// Creates a new layer of the given type with default values
+            #[must_use]
+            pub fn new(kind: Layers) -> Self {
+                match kind {
+                    $(Layers::$proto => Self::$proto(<$mut_proto as LayerMutable>::new())),*
+                }
+            }
+
+            /// Parses the layer of the given type and all upper layers from the buffer
+            ///
+            /// # Errors
+            /// Returns the error of the `from_buf` function of the layer
+            pub fn from_buf(kind: Layers, buf: Vec<u8>) -> Result<Self> {
+                match kind {
+                    $(Layers::$proto => <$mut_proto as LayerMutable>::from_buf(buf).map(Self::$proto)),*
+                }
+            }
+
+            /// Returns the upper layer if there is one
+            #[must_use]
+            pub fn upper_layer(&self) -> Option<&LayerMut> {
+                match self {
+                    $(Self::$proto(a) => a.upper_layer()),*
+                }
+            }
+
+            /// Returns a mutable reference to the upper layer if there is one
+            pub fn upper_layer_mut(&mut self) -> Option<&mut LayerMut> {
+                match self {
+                    $(Self::$proto(a) => a.upper_layer_mut()),*
+                }
+            }
+
+            /// Returns the name of the layer, for custom layers the name of the protocol
+            #[must_use]
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(Self::$proto(a) => a.name()),*
+                }
+            }
+
+            /// Returns the fields of the layer header, for custom layers the fields of the
+            /// protocol
+            #[must_use]
+            pub fn fields(&self) -> &'static [Field] {
+                match self {
+                    $(Self::$proto(a) => LayerMutable::fields(a)),*
+                }
+            }
+
+            /// Returns the trailer following all upper layers if there is one
+            #[must_use]
+            pub fn trailer(&self) -> Option<&TrailerMut> {
+                match self {
+                    $(Self::$proto(a) => a.trailer()),*
+                }
+            }
+
+            /// Returns the header of the layer as bytes
+            #[must_use]
+            pub fn header(&self) -> &[u8] {
+                match self {
+                    $(Self::$proto(a) => &a.buf),*
+                }
+            }
+
+            /// Returns the header of the layer for writing fields directly
+            pub(crate) fn header_mut(&mut self) -> &mut Vec<u8> {
+                match self {
+                    $(Self::$proto(a) => &mut a.buf),*
+                }
+            }
+
+            /// Returns the value of the header field with the given name
+            #[must_use]
+            pub fn get_field(&self, name: &str) -> Option<Value> {
+                self.fields()
+                    .iter()
+                    .find(|field| field.name == name)?
+                    .get(self.header())
+            }
+
+            /// Sets the header field with the given name
+            ///
+            /// # Errors
+            /// Returns `Error::InvalidValue` if the layer has no such field or the value does
+            /// not match the field
+            pub fn set_field(&mut self, name: &str, value: &Value) -> Result<()> {
+                let field = self
+                    .fields()
+                    .iter()
+                    .find(|field| field.name == name)
+                    .ok_or_else(|| Error::InvalidValue {
+                        field: "",
+                        message: format!("{} has no field `{name}`", self.name()),
+                    })?;
+                match self {
+                    $(Self::$proto(a) => {
+                        field.set(&mut a.buf, value)?;
+                        a.update_header(field);
+                        Ok(())
+                    }),*
+                }
+            }
+
+            /// Builds the layer and all upper layers, see `LayerMutable::build`
+            ///
+            /// # Errors
+            /// Returns the error of the `build` function of the layer
+            pub fn build(self) -> Result<Vec<u8>> {
+                match self {
+                    $(Self::$proto(a) => a.build()),*
+                }
+            }
+
+            /// Returns the layer type of the layer
+            #[must_use]
+            pub fn kind(&self) -> Layers {
+                match self {
+                    $(Self::$proto(_) => Layers::$proto),*
+                }
+            }
+            /// Returns true if the layer type is allowed directly on top of the layer, see
+            /// `LayerMutable::accepts`
+            #[must_use]
+            pub fn accepts(&self, layer: &Layers) -> bool {
+                match self {
+                    $(Self::$proto(a) => a.accepts(layer)),*
+                }
+            }
+
+            /// Replaces the upper layer and all layers above it, see
+            /// `LayerMutable::replace_upper_layer`
+            ///
+            /// # Errors
+            /// Returns `Error::IllegalStacking` if the layer is not allowed on this layer
+            pub fn replace_upper_layer(&mut self, layer: Option<LayerMut>) -> Result<Option<LayerMut>> {
+                match self {
+                    $(Self::$proto(a) => a.replace_upper_layer(layer)),*
+                }
+            }
+
+            /// Inserts the layer directly on top of this layer, see `LayerMutable::insert`
+            ///
+            /// # Errors
+            /// Returns `Error::IllegalStacking` if the layers can not be stacked like this
+            pub fn insert(&mut self, layer: LayerMut) -> Result<()> {
+                match self {
+                    $(Self::$proto(a) => a.insert(layer)),*
+                }
+            }
+
+            /// Removes the first upper layer of the requested type, see `LayerMutable::remove`
+            ///
+            /// # Errors
+            /// Returns `Error::MissingLayer` if there is no such upper layer or
+            /// `Error::IllegalStacking` if the layers can not be stacked without it
+            pub fn remove(&mut self, layer: &Layers) -> Result<LayerMut> {
+                match self {
+                    $(Self::$proto(a) => a.remove(layer)),*
+                }
+            }
+
+            /// Inserts a layer after the first upper layer of the requested type, see
+            /// `LayerMutable::insert_after`
+            ///
+            /// # Errors
+            /// Returns `Error::MissingLayer` if there is no such upper layer or
+            /// `Error::IllegalStacking` if the layers can not be stacked like this
+            pub fn insert_after(&mut self, layer: &Layers, new: LayerMut) -> Result<()> {
+                match self {
+                    $(Self::$proto(a) => a.insert_after(layer, new)),*
+                }
+            }
+
+            /// Inserts a layer before the first upper layer of the requested type, see
+            /// `LayerMutable::insert_before`
+            ///
+            /// # Errors
+            /// Returns `Error::MissingLayer` if there is no such upper layer or
+            /// `Error::IllegalStacking` if the layers can not be stacked like this
+            pub fn insert_before(&mut self, layer: &Layers, new: LayerMut) -> Result<()> {
+                match self {
+                    $(Self::$proto(a) => a.insert_before(layer, new)),*
+                }
+            }
+
+            /// Replaces the first upper layer of the requested type, see `LayerMutable::replace`
+            ///
+            /// # Errors
+            /// Returns `Error::MissingLayer` if there is no such upper layer or
+            /// `Error::IllegalStacking` if the layers can not be stacked like this
+            pub fn replace(&mut self, layer: &Layers, new: LayerMut) -> Result<LayerMut> {
+                match self {
+                    $(Self::$proto(a) => a.replace(layer, new)),*
+                }
+            }
+
+            /// Removes all layers above the first upper layer of the requested type, see
+            /// `LayerMutable::truncate_after`
+            ///
+            /// # Errors
+            /// Returns `Error::MissingLayer` if there is no such upper layer
+            pub fn truncate_after(&mut self, layer: &Layers) -> Result<Option<LayerMut>> {
+                match self {
+                    $(Self::$proto(a) => a.truncate_after(layer)),*
+                }
+            }
+
+            pub(crate) fn add(&mut self, layer: LayerMut) -> Result<()> {
                 match self {
                     $(Self::$proto(a) => a.add(layer)),*
                 }
@@ -224,6 +583,50 @@ macro_rules! layers {
             }
         }
 
+        $(
+            impl serde::Serialize for $mut_proto {
+                fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+                    $crate::serialize::serialize_layer(
+                        LayerMutable::fields(self),
+                        &self.buf,
+                        LayerMutable::upper_layer(self),
+                        LayerMutable::trailer(self),
+                        serializer,
+                    )
+                }
+            }
+
+            impl $crate::traits::Answers for $mut_proto {
+                fn answers(&self, request: &Self) -> bool {
+                    $crate::answers::answers(
+                        $crate::answers::View::new(Layers::$proto, &self.buf, LayerMutable::upper_layer(self)),
+                        $crate::answers::View::new(Layers::$proto, &request.buf, LayerMutable::upper_layer(request)),
+                    )
+                }
+
+                fn hashret(&self) -> u64 {
+                    $crate::answers::hashret($crate::answers::View::new(
+                        Layers::$proto,
+                        &self.buf,
+                        LayerMutable::upper_layer(self),
+                    ))
+                }
+            }
+
+            impl<'de> serde::Deserialize<'de> for $mut_proto {
+                fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+                    match $crate::serialize::deserialize_layer(Layers::$proto, deserializer)? {
+                        LayerMut::$proto(a) => Ok(a),
+                        other => Err(serde::de::Error::custom(format!(
+                            "expected {:?} but found {:?}",
+                            Layers::$proto,
+                            other.kind()
+                        ))),
+                    }
+                }
+            }
+        )*
+
         impl Display for LayerMut {
             fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
                 match self {
@@ -2,8 +2,39 @@
 //! will be set if the value is not defined. These values allows to identify the packet when it
 //! was send by `pnet_layers`.
 
+use crate::pcap::{self, LinkType};
+use crate::{Layer, LayerImmutable, LayerIter, Layers};
+
 /// Magic value for the TTL value int the IPv4 packet. Added by `pnet_layers` to make the packet identifiable.
 pub const MAGIC_IPV4_TTL: u8 = 170;
 
 /// Magic value for the vlan prio. Added by `pnet_layers` to make the packet identifiable.
 pub const MAGIC_VLAN_PRIO: u8 = 6;
+
+/// Returns the names of the magic values found in a packet of the link type. A found magic
+/// value indicates that the field was not set and filled in by `pnet_layers` when building
+/// the packet.
+#[must_use]
+pub fn find_magics(packet: &[u8], link_type: LinkType) -> Vec<&'static str> {
+    let Some((first, offset)) = pcap::first_layer(link_type, packet) else {
+        return vec![];
+    };
+    let layers =
+        || LayerIter::new(&packet[offset.min(packet.len())..], first).map(|span| span.layer);
+    let mut magics = vec![];
+    if let Some(Layer::Vlan(vlan)) = layers().find(|layer| layer.kind() == Layers::Vlan)
+        && vlan
+            .as_pnet()
+            .is_ok_and(|vlan| vlan.get_priority_code_point().0 == MAGIC_VLAN_PRIO)
+    {
+        magics.push("MAGIC_VLAN_PRIO");
+    }
+    if let Some(Layer::Ipv4(ipv4)) = layers().find(|layer| layer.kind() == Layers::Ipv4)
+        && ipv4
+            .as_pnet()
+            .is_ok_and(|ipv4| ipv4.get_ttl() == MAGIC_IPV4_TTL)
+    {
+        magics.push("MAGIC_IPV4_TTL");
+    }
+    magics
+}
@@ -0,0 +1,466 @@
+//! Control over the fields computed by `build`.
+//!
+//! `build` fills in the fields derived from the stack, e.g. the Ether and Vlan type, the
+//! version, header length, total length, protocol and checksum of Ipv4, the payload length
+//! and next header of Ipv6, the lengths and checksums of Icmp, Udp and Tcp. For negative
+//! tests `LayerMut::build_with` writes selected fields differently:
+//!
+//! ```
+//! use pnet_layers::overrides::{Override, Overrides};
+//! use pnet_layers::{LayerMut, Layers, Value};
+//!
+//! let mut overrides = Overrides::new();
+//! overrides.set(Layers::Ipv4, "chksum", Override::Wrong)?;
+//! overrides.set(Layers::Udp, "len", Override::Pin(Value::Int(1000)))?;
+//! let bytes = LayerMut::parse("Ether / Ipv4 / Udp")?.build_with(&overrides)?;
+//! assert_eq!(bytes[38..40], [0x03, 0xe8]);
+//! # Ok::<(), pnet_layers::Error>(())
+//! ```
+//!
+//! Checksums covering an overridden field are calculated again, so only the overridden field
+//! is wrong. Layers quoted by an ICMP error are written as they are and not affected.
+
+use crate::layers::{ipv6_ext, iter};
+use crate::{Error, Field, FieldKind, LayerMut, Layers, Result, Value};
+use pnet::packet::ip::{IpNextHeaderProtocol, IpNextHeaderProtocols};
+use pnet::packet::util;
+use std::net::IpAddr;
+
+/// How a field is written by `LayerMut::build_with`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Override {
+    /// The value set in the layer before building is kept, the field is not filled in
+    Keep,
+    /// The field is set to the value
+    Pin(Value),
+    /// The computed value with the lowest bit flipped, e.g. a checksum which is always invalid
+    Wrong,
+}
+
+/// Overrides of fields by the layer type, see the module documentation
+#[derive(Debug, Clone, Default)]
+pub struct Overrides {
+    entries: Vec<(Layers, &'static str, Override)>,
+}
+
+/// Lengths and checksums calculated by `build`
+const CHECKS: &[(Layers, &str)] = &[
+    (Layers::Ipv4, "ihl"),
+    (Layers::Ipv4, "len"),
+    (Layers::Ipv4, "chksum"),
+    (Layers::Ipv6, "plen"),
+    (Layers::HopByHop, "len"),
+    (Layers::Routing, "len"),
+    (Layers::DestOpts, "len"),
+    (Layers::Ah, "len"),
+    (Layers::Udp, "len"),
+    (Layers::Udp, "chksum"),
+    (Layers::Tcp, "dataofs"),
+    (Layers::Tcp, "chksum"),
+    (Layers::Icmp, "chksum"),
+    (Layers::Icmpv6, "chksum"),
+];
+
+/// Returns true if the field is a length or checksum calculated by `build`
+pub(crate) fn is_check(layer: Layers, field: &str) -> bool {
+    CHECKS.contains(&(layer, field))
+}
+
+fn invalid(message: String) -> Error {
+    Error::InvalidValue { field: "", message }
+}
+
+impl Overrides {
+    /// Creates an empty set of overrides, `build_with` then behaves like `build`
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates overrides writing every length and checksum calculated by `build` wrong
+    #[must_use]
+    pub fn broken_checks() -> Self {
+        Self {
+            entries: CHECKS
+                .iter()
+                .map(|&(layer, field)| (layer, field, Override::Wrong))
+                .collect(),
+        }
+    }
+
+    /// Overrides the field of every layer of the given type, replacing an earlier override of
+    /// the field
+    ///
+    /// # Errors
+    /// Returns `Error::InvalidValue` if the layer has no such field, the pinned value does
+    /// not match the field or a field which is no integer should be wrong
+    pub fn set(&mut self, layer: Layers, field: &str, value: Override) -> Result<()> {
+        let field = layer
+            .fields()
+            .iter()
+            .find(|f| f.name == field)
+            .ok_or_else(|| invalid(format!("{layer:?} has no field `{field}`")))?;
+        match &value {
+            Override::Pin(pinned) => {
+                // check the type and width of the value on an empty header
+                let len = (field.offset + field.bits().unwrap_or_default()).div_ceil(8);
+                field.set(&mut vec![0; len], pinned)?;
+            }
+            Override::Wrong if !matches!(field.kind, FieldKind::Int(_)) => {
+                return Err(invalid(format!(
+                    "the value of `{}` can not be wrong",
+                    field.name
+                )));
+            }
+            _ => {}
+        }
+        self.entries
+            .retain(|(l, name, _)| !(*l == layer && *name == field.name));
+        self.entries.push((layer, field.name, value));
+        Ok(())
+    }
+
+    /// Keeps every field of the layer type as it was set, nothing is filled in by `build`
+    pub fn keep(&mut self, layer: Layers) {
+        for field in layer.fields() {
+            let _ = self.set(layer, field.name, Override::Keep);
+        }
+    }
+
+    /// Returns true if no field is overridden
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn get(&self, layer: Layers, field: &str) -> Option<&Override> {
+        self.entries
+            .iter()
+            .find(|(l, name, _)| *l == layer && *name == field)
+            .map(|(_, _, value)| value)
+    }
+}
+
+/// Writes the override into the header, returns false if there was nothing to write
+fn apply(field: &Field, value: &Override, header: &mut Vec<u8>, original: &[u8]) -> Result<bool> {
+    let value = match value {
+        Override::Keep => match field.get(original) {
+            Some(value) => value,
+            None => return Ok(false),
+        },
+        Override::Pin(value) => value.clone(),
+        Override::Wrong => match field.get(header) {
+            Some(Value::Int(value)) => Value::Int(value ^ 1),
+            _ => return Ok(false),
+        },
+    };
+    let len = header.len();
+    field.set(header, &value)?;
+    if header.len() != len {
+        return Err(invalid(format!(
+            "`{}` can not change the length of the built header",
+            field.name
+        )));
+    }
+    Ok(true)
+}
+
+/// Calculates the checksum of a layer, the segment reaches from the layer to the end of its
+/// payload
+fn checksum(
+    kind: Layers,
+    segment: &[u8],
+    header_len: usize,
+    addrs: Option<(IpAddr, IpAddr)>,
+) -> Option<u16> {
+    let (skipword, protocol): (usize, IpNextHeaderProtocol) = match kind {
+        Layers::Ipv4 => return Some(util::checksum(&segment[..header_len], 5)),
+        Layers::Icmp => return Some(util::checksum(segment, 1)),
+        Layers::Udp => (3, IpNextHeaderProtocols::Udp),
+        Layers::Tcp => (8, IpNextHeaderProtocols::Tcp),
+        Layers::Icmpv6 => (1, IpNextHeaderProtocols::Icmpv6),
+        _ => return None,
+    };
+    match addrs? {
+        (IpAddr::V4(src), IpAddr::V4(dst)) => Some(util::ipv4_checksum(
+            segment,
+            skipword,
+            &[],
+            &src,
+            &dst,
+            protocol,
+        )),
+        (IpAddr::V6(src), IpAddr::V6(dst)) => Some(util::ipv6_checksum(
+            segment,
+            skipword,
+            &[],
+            &src,
+            &dst,
+            protocol,
+        )),
+        _ => None,
+    }
+}
+
+/// Position of a layer in the built packet
+struct Span {
+    kind: Layers,
+    start: usize,
+    header_end: usize,
+    /// End of the payload of the layer
+    end: usize,
+}
+
+/// Returns the type and header of each layer before building and its position in the built
+/// packet, stopping after an ICMP message
+fn spans(layer: &LayerMut, bytes: &[u8]) -> Vec<(Vec<u8>, Span)> {
+    let mut spans = vec![];
+    let (mut start, mut end) = (0, bytes.len());
+    let mut layer = Some(layer);
+    while let Some(current) = layer {
+        let Some((header_len, len)) = iter::bounds(current, &bytes[start..end]) else {
+            break;
+        };
+        let span = Span {
+            kind: current.kind(),
+            start,
+            header_end: start + header_len,
+            end: start + len,
+        };
+        spans.push((current.header().to_vec(), span));
+        if current.kind() == Layers::Icmp || header_len == len {
+            break;
+        }
+        (start, end) = (start + header_len, start + len);
+        layer = current.upper_layer();
+    }
+    spans
+}
+
+/// Returns the source and destination address of the IP header
+fn addrs(kind: Layers, header: &[u8]) -> Option<(IpAddr, IpAddr)> {
+    let addr = |name| {
+        let field = kind.fields().iter().find(|f| f.name == name)?;
+        match field.get(header)? {
+            Value::Ipv4(addr) => Some(IpAddr::V4(addr)),
+            Value::Ipv6(addr) => Some(IpAddr::V6(addr)),
+            _ => None,
+        }
+    };
+    Some((addr("src")?, addr("dst")?))
+}
+
+/// Returns the addresses of the pseudo-header of the layer following the spans like
+/// `Ipv6Mut::build`: the addresses of the closest IP header, the destination replaced by the
+/// final destination of the routing headers after it
+fn pseudo_header_addrs(spans: &[(Vec<u8>, Span)], bytes: &[u8]) -> Option<(IpAddr, IpAddr)> {
+    let header = |span: &Span| &bytes[span.start..span.header_end];
+    let ip = spans
+        .iter()
+        .rposition(|(_, span)| matches!(span.kind, Layers::Ipv4 | Layers::Ipv6))?;
+    let (src, dst) = addrs(spans[ip].1.kind, header(&spans[ip].1))?;
+    let dst = spans[ip + 1..]
+        .iter()
+        .rev()
+        .filter(|(_, span)| span.kind == Layers::Routing)
+        .find_map(|(_, span)| ipv6_ext::final_destination(header(span)))
+        .map_or(dst, IpAddr::V6);
+    Some((src, dst))
+}
+
+impl LayerMut {
+    /// Builds the layer like `build` and applies the overrides afterwards, see the
+    /// `overrides` module
+    ///
+    /// # Errors
+    /// Returns the error of `build` or `Error::InvalidValue` if an override changes the
+    /// length of a header
+    pub fn build_with(self, overrides: &Overrides) -> Result<Vec<u8>> {
+        if overrides.is_empty() {
+            return self.build();
+        }
+        let mut bytes = self.clone().build()?;
+        // the layers are located with the types of the unbuilt layers, so the original
+        // headers are known even if an override changes the type of an upper layer
+        let spans = spans(&self, &bytes);
+
+        let mut dirty = false;
+        for (original, span) in &spans {
+            let mut header = bytes[span.start..span.header_end].to_vec();
+            for field in span.kind.fields().iter().filter(|f| f.name != "chksum") {
+                if let Some(value) = overrides.get(span.kind, field.name) {
+                    dirty |= apply(field, value, &mut header, original)?;
+                }
+            }
+            bytes[span.start..span.header_end].copy_from_slice(&header);
+        }
+
+        // upper layers first, so the checksums of lower layers cover the overridden values
+        for (i, (original, span)) in spans.iter().enumerate().rev() {
+            let Some(field) = span.kind.fields().iter().find(|f| f.name == "chksum") else {
+                continue;
+            };
+            let mut header = bytes[span.start..span.header_end].to_vec();
+            if dirty {
+                let addrs = pseudo_header_addrs(&spans[..i], &bytes);
+                let segment = &bytes[span.start..span.end];
+                let header_len = span.header_end - span.start;
+                if let Some(sum) = checksum(span.kind, segment, header_len, addrs) {
+                    field.set(&mut header, &Value::Int(u64::from(sum)))?;
+                }
+            }
+            if let Some(value) = overrides.get(span.kind, field.name) {
+                dirty |= apply(field, value, &mut header, original)?;
+            }
+            bytes[span.start..span.header_end].copy_from_slice(&header);
+        }
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pnet::packet::ipv4::{self, Ipv4Packet};
+    use pnet::packet::udp::{self, UdpPacket};
+    use std::net::Ipv4Addr;
+
+    fn build(input: &str, overrides: &Overrides) -> Vec<u8> {
+        LayerMut::parse(input)
+            .and_then(|packet| packet.build_with(overrides))
+            .expect("could not build")
+    }
+
+    #[test]
+    fn test_overrides() {
+        let input = r#"Ether / Ipv4(src: 10.0.0.1, dst: 10.0.0.2, id: 1) / Udp / "payload""#;
+        let valid = build(input, &Overrides::new());
+
+        let mut overrides = Overrides::new();
+        overrides
+            .set(Layers::Udp, "len", Override::Pin(Value::Int(1000)))
+            .expect("could not set");
+        let bytes = build(input, &overrides);
+        let ip = Ipv4Packet::new(&bytes[14..]).expect("ipv4");
+        let udp = UdpPacket::new(&bytes[34..]).expect("udp");
+        assert_eq!(udp.get_length(), 1000);
+        // the checksum covers the pinned length
+        assert_ne!(bytes[40..42], valid[40..42]);
+        let (src, dst) = (Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::new(10, 0, 0, 2));
+        assert_eq!(udp.get_checksum(), udp::ipv4_checksum(&udp, &src, &dst));
+        assert_eq!(ip.get_checksum(), ipv4::checksum(&ip));
+
+        overrides
+            .set(Layers::Ipv4, "chksum", Override::Wrong)
+            .expect("could not set");
+        overrides
+            .set(Layers::Ether, "type", Override::Pin(Value::Int(0x1234)))
+            .expect("could not set");
+        let bytes = build(input, &overrides);
+        let ip = Ipv4Packet::new(&bytes[14..]).expect("ipv4");
+        assert_eq!(ip.get_checksum(), ipv4::checksum(&ip) ^ 1);
+        assert_eq!(bytes[12..14], [0x12, 0x34]);
+        assert_eq!(bytes[14..24], valid[14..24]);
+    }
+
+    #[test]
+    fn test_overrides_keep() {
+        let mut overrides = Overrides::new();
+        overrides.keep(Layers::Ipv4);
+        overrides
+            .set(Layers::Tcp, "chksum", Override::Keep)
+            .expect("could not set");
+        let bytes = build(
+            "Ether / Ipv4(ttl: 5, chksum: 7) / Tcp(chksum: 9)",
+            &overrides,
+        );
+        // the Ipv4 header is not filled in at all
+        assert_eq!(
+            bytes[14..34],
+            [0, 0, 0, 0, 0, 0, 0, 0, 5, 0, 0, 7, 0, 0, 0, 0, 0, 0, 0, 0]
+        );
+        assert_eq!(bytes[46], 5 << 4);
+        assert_eq!(bytes[50..52], [0, 9]);
+    }
+
+    #[test]
+    fn test_overrides_pin_address() {
+        let mut overrides = Overrides::new();
+        overrides
+            .set(
+                Layers::Ipv4,
+                "src",
+                Override::Pin(Value::Ipv4(Ipv4Addr::new(192, 0, 2, 1))),
+            )
+            .expect("could not set");
+        let bytes = build("Ether / Ipv4 / Udp / \"payload\"", &overrides);
+        assert_eq!(bytes[26..30], [192, 0, 2, 1]);
+        // the UDP checksum covers the pinned address in the pseudo header
+        let packet = LayerMut::from_buf(Layers::Ether, bytes).expect("could not parse");
+        assert_eq!(packet.validate(), []);
+    }
+
+    #[test]
+    fn test_overrides_keep_upper() {
+        // the pinned protocol makes the upper layer unknown, its checksum is kept anyway
+        let mut overrides = Overrides::new();
+        overrides
+            .set(Layers::Ipv4, "proto", Override::Pin(Value::Int(0xfd)))
+            .expect("could not set");
+        overrides
+            .set(Layers::Udp, "chksum", Override::Keep)
+            .expect("could not set");
+        let bytes = build("Ether / Ipv4 / Udp(chksum: 9)", &overrides);
+        assert_eq!(bytes[23], 0xfd);
+        assert_eq!(bytes[40..42], [0, 9]);
+    }
+
+    #[test]
+    fn test_overrides_routing() {
+        // the checksum of the upper layer uses the final destination of the routing header
+        let srh = "Routing(type: 4, segleft: 1, lastentry: 1, data: \
+            0x20010db800000000000000000000000320010db8000000000000000000000002)";
+        let input = format!(
+            r#"Ether / Ipv6(src: 2001:db8::1, dst: 2001:db8::2) / {srh} / Udp(dport: 53) / "q""#
+        );
+        let mut overrides = Overrides::new();
+        overrides
+            .set(Layers::Udp, "sport", Override::Pin(Value::Int(1234)))
+            .expect("could not set");
+        let bytes = build(&input, &overrides);
+        let valid = build(
+            &input.replace("Udp(", "Udp(sport: 1234, "),
+            &Overrides::new(),
+        );
+        assert_eq!(bytes, valid);
+    }
+
+    #[test]
+    fn test_overrides_errors() {
+        let mut overrides = Overrides::new();
+        assert!(overrides.set(Layers::Udp, "foo", Override::Keep).is_err());
+        assert!(
+            overrides
+                .set(Layers::Udp, "len", Override::Pin(Value::Int(1 << 16)))
+                .is_err()
+        );
+        assert!(overrides.set(Layers::Ipv4, "src", Override::Wrong).is_err());
+        overrides
+            .set(
+                Layers::Tcp,
+                "options",
+                Override::Pin(Value::Bytes(vec![1; 4])),
+            )
+            .expect("could not set");
+        assert!(
+            LayerMut::parse("Ether / Ipv4 / Tcp")
+                .and_then(|packet| packet.build_with(&overrides))
+                .is_err()
+        );
+        assert!(
+            overrides
+                .set(Layers::Tcp, "options", Override::Keep)
+                .is_ok()
+        );
+    }
+}
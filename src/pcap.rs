@@ -0,0 +1,738 @@
+//! Reader and writer for pcap and pcapng capture files.
+//!
+//! ```no_run
+//! use pnet_layers::pcap::PcapReader;
+//!
+//! for record in PcapReader::open("capture.pcapng")? {
+//!     let record = record?;
+//!     println!("{:?} {}", record.timestamp, record.layer_mut()?);
+//! }
+//! # Ok::<(), pnet_layers::Error>(())
+//! ```
+
+use crate::{
+    Arp, ArpMut, Error, Ether, EtherMut, Ipv4, Ipv4Mut, Ipv6, Ipv6Mut, Layer, LayerImmutable,
+    LayerMut, LayerMutable, Layers, Result, Vlan, VlanMut,
+};
+use pnet::packet::ethernet::{EtherType, EtherTypes};
+use std::fs::File;
+use std::io::{BufReader, ErrorKind, Read};
+use std::path::Path;
+use std::time::Duration;
+
+mod writer;
+pub use writer::{PcapFormat, PcapWriter};
+
+/// Magic of a classic pcap file with microsecond timestamps
+const PCAP_MAGIC_MICROS: u32 = 0xa1b2_c3d4;
+/// Magic of a classic pcap file with nanosecond timestamps
+const PCAP_MAGIC_NANOS: u32 = 0xa1b2_3c4d;
+/// Block type of the pcapng section header block
+const PCAPNG_SHB: u32 = 0x0a0d_0d0a;
+/// Byte-order magic of the pcapng section header block
+const PCAPNG_BYTE_ORDER: u32 = 0x1a2b_3c4d;
+const PCAPNG_IDB: u32 = 1;
+const PCAPNG_SPB: u32 = 3;
+const PCAPNG_EPB: u32 = 6;
+const OPT_ENDOFOPT: u16 = 0;
+const OPT_COMMENT: u16 = 1;
+/// Option code of the name of the application which wrote the section
+const SHB_USERAPPL: u16 = 4;
+/// Option code of the timestamp resolution of an interface
+const IF_TSRESOL: u16 = 9;
+/// Blocks larger than this are rejected instead of allocating the memory
+const MAX_BLOCK_LEN: usize = 64 * 1024 * 1024;
+
+/// Link type of the captured packets
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkType {
+    /// Ethernet (1)
+    Ethernet,
+    /// Raw IPv4 or IPv6 packets without a link layer header (101)
+    Raw,
+    /// Linux cooked capture v1 (113)
+    LinuxSll,
+    /// Raw IPv4 packets (228)
+    Ipv4,
+    /// Raw IPv6 packets (229)
+    Ipv6,
+    /// Linux cooked capture v2 (276)
+    LinuxSll2,
+    /// Link type not supported by `pnet_layers`
+    Other(u16),
+}
+
+impl From<LinkType> for u16 {
+    fn from(value: LinkType) -> Self {
+        match value {
+            LinkType::Ethernet => 1,
+            LinkType::Raw => 101,
+            LinkType::LinuxSll => 113,
+            LinkType::Ipv4 => 228,
+            LinkType::Ipv6 => 229,
+            LinkType::LinuxSll2 => 276,
+            LinkType::Other(other) => other,
+        }
+    }
+}
+
+impl From<u16> for LinkType {
+    fn from(value: u16) -> Self {
+        match value {
+            1 => Self::Ethernet,
+            101 => Self::Raw,
+            113 => Self::LinuxSll,
+            228 => Self::Ipv4,
+            229 => Self::Ipv6,
+            276 => Self::LinuxSll2,
+            other => Self::Other(other),
+        }
+    }
+}
+
+/// A captured packet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    /// Time the packet was captured since the unix epoch
+    pub timestamp: Duration,
+    /// Index of the interface the packet was captured on, always 0 for classic pcap files
+    pub interface: u32,
+    /// Link type of the interface
+    pub link_type: LinkType,
+    /// Length of the packet on the wire, `data` is shorter if the packet was truncated
+    pub original_len: u32,
+    /// The captured bytes
+    pub data: Vec<u8>,
+    /// Comments of the packet, only supported by pcapng
+    pub comments: Vec<String>,
+}
+
+/// Returns the first layer of a packet of the link type and the offset of it in `data`.
+/// Linux cooked captures have no layer for their header, so the network layer is returned.
+pub(crate) fn first_layer(link_type: LinkType, data: &[u8]) -> Option<(Layers, usize)> {
+    let by_ether_type = |ether_type: u16, offset| {
+        Some(match EtherType::new(ether_type) {
+            EtherTypes::Ipv4 => (Layers::Ipv4, offset),
+            EtherTypes::Ipv6 => (Layers::Ipv6, offset),
+            EtherTypes::Arp => (Layers::Arp, offset),
+            EtherTypes::Vlan => (Layers::Vlan, offset),
+            _ => return None,
+        })
+    };
+    let u16_at = |i: usize| Some(u16::from_be_bytes([*data.get(i)?, *data.get(i + 1)?]));
+    match link_type {
+        LinkType::Ethernet => Some((Layers::Ether, 0)),
+        LinkType::Raw => match data.first()? >> 4 {
+            4 => Some((Layers::Ipv4, 0)),
+            6 => Some((Layers::Ipv6, 0)),
+            _ => None,
+        },
+        LinkType::Ipv4 => Some((Layers::Ipv4, 0)),
+        LinkType::Ipv6 => Some((Layers::Ipv6, 0)),
+        LinkType::LinuxSll => by_ether_type(u16_at(14)?, 16),
+        LinkType::LinuxSll2 => by_ether_type(u16_at(0)?, 20),
+        LinkType::Other(_) => None,
+    }
+}
+
+impl Record {
+    /// Returns the first layer of the link type and the offset of it in `data`
+    fn first_layer(&self) -> Option<(Layers, usize)> {
+        first_layer(self.link_type, &self.data)
+    }
+
+    /// Returns a zero-copy view of the packet or `None` if the link type or the network
+    /// protocol of a Linux cooked capture is not supported
+    #[must_use]
+    pub fn layer(&self) -> Option<Layer<'_>> {
+        let (layer, offset) = self.first_layer()?;
+        let buf = self.data.get(offset..)?;
+        Some(match layer {
+            Layers::Ether => Layer::Ether(Ether::new(buf)),
+            Layers::Vlan => Layer::Vlan(Vlan::new(buf)),
+            Layers::Arp => Layer::Arp(Arp::new(buf)),
+            Layers::Ipv4 => Layer::Ipv4(Ipv4::new(buf)),
+            _ => Layer::Ipv6(Ipv6::new(buf)),
+        })
+    }
+
+    /// Parses the packet into mutable layers
+    ///
+    /// # Errors
+    /// Returns `Error::UnsupportedNextProtocol` if the link type is not supported or
+    /// `Error::Truncated` if the packet could not be parsed
+    pub fn layer_mut(&self) -> Result<LayerMut> {
+        let (layer, offset) = self.first_layer().ok_or_else(|| self.unsupported())?;
+        let buf = self
+            .data
+            .get(offset..)
+            .ok_or(Error::Truncated { layer, offset: 0 })?
+            .to_vec();
+        match layer {
+            Layers::Ether => EtherMut::from_buf(buf).map(LayerMut::Ether),
+            Layers::Vlan => VlanMut::from_buf(buf).map(LayerMut::Vlan),
+            Layers::Arp => ArpMut::from_buf(buf).map(LayerMut::Arp),
+            Layers::Ipv4 => Ipv4Mut::from_buf(buf).map(LayerMut::Ipv4),
+            _ => Ipv6Mut::from_buf(buf).map(LayerMut::Ipv6),
+        }
+        .map_err(|err| err.offset_by(offset))
+    }
+
+    /// Error for a link type or a protocol of a Linux cooked capture which is not supported
+    fn unsupported(&self) -> Error {
+        let protocol = match self.link_type {
+            LinkType::LinuxSll => self.data.get(14..16),
+            LinkType::LinuxSll2 => self.data.get(0..2),
+            LinkType::Other(link_type) => {
+                return Error::UnsupportedNextProtocol {
+                    layer: Layers::Ether,
+                    protocol: link_type,
+                };
+            }
+            _ => None,
+        };
+        Error::UnsupportedNextProtocol {
+            layer: Layers::Ether,
+            protocol: protocol.map_or(0, |p| u16::from_be_bytes([p[0], p[1]])),
+        }
+    }
+}
+
+/// Interface of a pcapng section
+#[derive(Debug, Clone, Copy)]
+struct Interface {
+    link_type: LinkType,
+    snap_len: u32,
+    /// Number of timestamp units per second
+    units_per_second: u64,
+}
+
+#[derive(Debug)]
+enum Format {
+    Pcap { interface: Interface },
+    PcapNg { interfaces: Vec<Interface> },
+}
+
+/// Reads the records of a pcap or pcapng file. The format and byte order are detected from
+/// the magic of the file.
+#[derive(Debug)]
+pub struct PcapReader<R> {
+    reader: R,
+    format: Format,
+    big_endian: bool,
+    /// Offset of the next block or record from the start of the file
+    offset: u64,
+    done: bool,
+}
+
+impl PcapReader<BufReader<File>> {
+    /// Opens the capture file at `path`
+    ///
+    /// # Errors
+    /// Returns `Error::Io` if the file could not be read or `Error::Capture` if it is not a
+    /// pcap or pcapng file
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read> PcapReader<R> {
+    /// Creates a reader and reads the file header
+    ///
+    /// # Errors
+    /// Returns `Error::Io` if the reader fails or `Error::Capture` if the data is not a pcap
+    /// or pcapng file
+    pub fn new(mut reader: R) -> Result<Self> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        let mut pcap = Self {
+            reader,
+            format: Format::PcapNg { interfaces: vec![] },
+            big_endian: false,
+            offset: 0,
+            done: false,
+        };
+        let units_per_second = match (u32::from_le_bytes(magic), u32::from_be_bytes(magic)) {
+            (PCAPNG_SHB, _) => {
+                pcap.read_section_header()?;
+                return Ok(pcap);
+            }
+            (PCAP_MAGIC_MICROS, _) => 1_000_000,
+            (PCAP_MAGIC_NANOS, _) => 1_000_000_000,
+            (_, PCAP_MAGIC_MICROS) => {
+                pcap.big_endian = true;
+                1_000_000
+            }
+            (_, PCAP_MAGIC_NANOS) => {
+                pcap.big_endian = true;
+                1_000_000_000
+            }
+            _ => return Err(pcap.invalid("unknown file magic")),
+        };
+
+        let mut header = [0; 20];
+        pcap.reader.read_exact(&mut header)?;
+        pcap.format = Format::Pcap {
+            interface: Interface {
+                // the upper bits of the link type field contain the FCS length
+                link_type: LinkType::from(
+                    u16::try_from(pcap.u32(&header[16..20]) & 0xffff).unwrap_or_default(),
+                ),
+                snap_len: pcap.u32(&header[12..16]),
+                units_per_second,
+            },
+        };
+        pcap.offset = 24;
+        Ok(pcap)
+    }
+
+    fn u16(&self, buf: &[u8]) -> u16 {
+        let buf = [buf[0], buf[1]];
+        if self.big_endian {
+            u16::from_be_bytes(buf)
+        } else {
+            u16::from_le_bytes(buf)
+        }
+    }
+
+    fn u32(&self, buf: &[u8]) -> u32 {
+        let buf = [buf[0], buf[1], buf[2], buf[3]];
+        if self.big_endian {
+            u32::from_be_bytes(buf)
+        } else {
+            u32::from_le_bytes(buf)
+        }
+    }
+
+    fn invalid(&self, message: &str) -> Error {
+        Error::Capture {
+            offset: self.offset,
+            message: message.to_string(),
+        }
+    }
+
+    /// Fills `buf` and returns `false` if the end of the file was reached before the first byte
+    fn read_or_eof(&mut self, buf: &mut [u8]) -> Result<bool> {
+        let mut read = 0;
+        while read < buf.len() {
+            match self.reader.read(&mut buf[read..]) {
+                Ok(0) if read == 0 => return Ok(false),
+                Ok(0) => return Err(self.invalid("unexpected end of file")),
+                Ok(n) => read += n,
+                Err(err) if err.kind() == ErrorKind::Interrupted => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(true)
+    }
+
+    fn read_vec(&mut self, len: usize) -> Result<Vec<u8>> {
+        if len > MAX_BLOCK_LEN {
+            return Err(self.invalid(&format!("length {len} too large")));
+        }
+        let mut buf = vec![0; len];
+        if !self.read_or_eof(&mut buf)? && len > 0 {
+            return Err(self.invalid("unexpected end of file"));
+        }
+        Ok(buf)
+    }
+
+    /// Reads the rest of a section header block after its block type and starts a new section
+    fn read_section_header(&mut self) -> Result<()> {
+        let mut header = [0; 8];
+        if !self.read_or_eof(&mut header)? {
+            return Err(self.invalid("unexpected end of file"));
+        }
+        let magic = [header[4], header[5], header[6], header[7]];
+        self.big_endian = match (u32::from_le_bytes(magic), u32::from_be_bytes(magic)) {
+            (PCAPNG_BYTE_ORDER, _) => false,
+            (_, PCAPNG_BYTE_ORDER) => true,
+            _ => return Err(self.invalid("unknown byte-order magic")),
+        };
+        let len = self.u32(&header[0..4]) as usize;
+        if len < 28 || !len.is_multiple_of(4) {
+            return Err(self.invalid(&format!("invalid block length {len}")));
+        }
+        self.read_vec(len - 12)?;
+        self.format = Format::PcapNg { interfaces: vec![] };
+        self.offset += len as u64;
+        Ok(())
+    }
+
+    fn next_pcap(&mut self, interface: Interface) -> Result<Option<Record>> {
+        let mut header = [0; 16];
+        if !self.read_or_eof(&mut header)? {
+            return Ok(None);
+        }
+        let seconds = u64::from(self.u32(&header[0..4]));
+        let fraction = u64::from(self.u32(&header[4..8]));
+        let captured_len = self.u32(&header[8..12]) as usize;
+        let original_len = self.u32(&header[12..16]);
+        let data = self.read_vec(captured_len)?;
+        self.offset += 16 + captured_len as u64;
+        Ok(Some(Record {
+            timestamp: Duration::from_secs(seconds)
+                + Duration::from_nanos(fraction * 1_000_000_000 / interface.units_per_second),
+            interface: 0,
+            link_type: interface.link_type,
+            original_len,
+            data,
+            comments: vec![],
+        }))
+    }
+
+    fn next_pcapng(&mut self) -> Result<Option<Record>> {
+        loop {
+            let mut header = [0; 4];
+            if !self.read_or_eof(&mut header)? {
+                return Ok(None);
+            }
+            if u32::from_le_bytes(header) == PCAPNG_SHB {
+                self.read_section_header()?;
+                continue;
+            }
+            let block_type = self.u32(&header);
+            let mut len = [0; 4];
+            if !self.read_or_eof(&mut len)? {
+                return Err(self.invalid("unexpected end of file"));
+            }
+            let len = self.u32(&len) as usize;
+            if len < 12 || !len.is_multiple_of(4) {
+                return Err(self.invalid(&format!("invalid block length {len}")));
+            }
+            // the body is followed by the repeated block length
+            let mut body = self.read_vec(len - 8)?;
+            body.truncate(len - 12);
+            let record = match block_type {
+                PCAPNG_IDB => {
+                    let interface = self.interface_description(&body)?;
+                    if let Format::PcapNg { interfaces } = &mut self.format {
+                        interfaces.push(interface);
+                    }
+                    None
+                }
+                PCAPNG_EPB => Some(self.enhanced_packet(&body)?),
+                PCAPNG_SPB => Some(self.simple_packet(body)?),
+                _ => None,
+            };
+            self.offset += len as u64;
+            if record.is_some() {
+                return Ok(record);
+            }
+        }
+    }
+
+    fn interface(&self, id: u32) -> Result<Interface> {
+        match &self.format {
+            Format::PcapNg { interfaces } => interfaces.get(id as usize).copied(),
+            Format::Pcap { interface } => Some(*interface),
+        }
+        .ok_or_else(|| self.invalid(&format!("unknown interface {id}")))
+    }
+
+    fn interface_description(&self, body: &[u8]) -> Result<Interface> {
+        if body.len() < 8 {
+            return Err(self.invalid("interface description block too short"));
+        }
+        let mut interface = Interface {
+            link_type: LinkType::from(self.u16(&body[0..2])),
+            snap_len: self.u32(&body[4..8]),
+            units_per_second: 1_000_000,
+        };
+        for (code, value) in self.options(&body[8..]) {
+            if code == IF_TSRESOL && value.len() == 1 {
+                let exponent = u32::from(value[0] & 0x7f);
+                let base: u64 = if value[0] & 0x80 == 0 { 10 } else { 2 };
+                interface.units_per_second = base
+                    .checked_pow(exponent)
+                    .ok_or_else(|| self.invalid("timestamp resolution too high"))?;
+            }
+        }
+        Ok(interface)
+    }
+
+    /// Returns the code and value of the options of a block until the end of options
+    fn options<'b>(&self, mut buf: &'b [u8]) -> Vec<(u16, &'b [u8])> {
+        let mut options = vec![];
+        while buf.len() >= 4 {
+            let code = self.u16(&buf[0..2]);
+            let len = usize::from(self.u16(&buf[2..4]));
+            let Some(value) = buf.get(4..4 + len) else {
+                break;
+            };
+            if code == OPT_ENDOFOPT {
+                break;
+            }
+            options.push((code, value));
+            buf = buf.get((4 + len).next_multiple_of(4)..).unwrap_or_default();
+        }
+        options
+    }
+
+    fn enhanced_packet(&self, body: &[u8]) -> Result<Record> {
+        if body.len() < 20 {
+            return Err(self.invalid("enhanced packet block too short"));
+        }
+        let id = self.u32(&body[0..4]);
+        let interface = self.interface(id)?;
+        let units = (u64::from(self.u32(&body[4..8])) << 32) | u64::from(self.u32(&body[8..12]));
+        let captured_len = self.u32(&body[12..16]) as usize;
+        let data = body
+            .get(20..20 + captured_len)
+            .ok_or_else(|| self.invalid("captured length exceeds block"))?;
+        let seconds = units / interface.units_per_second;
+        let fraction = units % interface.units_per_second;
+        Ok(Record {
+            timestamp: Duration::from_secs(seconds)
+                + Duration::from_nanos(
+                    u64::try_from(
+                        u128::from(fraction) * 1_000_000_000
+                            / u128::from(interface.units_per_second),
+                    )
+                    .unwrap_or_default(),
+                ),
+            interface: id,
+            link_type: interface.link_type,
+            original_len: self.u32(&body[16..20]),
+            data: data.to_vec(),
+            comments: self
+                .options(&body[(20 + captured_len).next_multiple_of(4).min(body.len())..])
+                .into_iter()
+                .filter(|(code, _)| *code == OPT_COMMENT)
+                .map(|(_, value)| String::from_utf8_lossy(value).into_owned())
+                .collect(),
+        })
+    }
+
+    fn simple_packet(&self, mut body: Vec<u8>) -> Result<Record> {
+        if body.len() < 4 {
+            return Err(self.invalid("simple packet block too short"));
+        }
+        let interface = self.interface(0)?;
+        let original_len = self.u32(&body[0..4]);
+        body.drain(..4);
+        let mut captured_len = original_len as usize;
+        if interface.snap_len != 0 {
+            captured_len = captured_len.min(interface.snap_len as usize);
+        }
+        body.truncate(captured_len);
+        Ok(Record {
+            timestamp: Duration::ZERO,
+            interface: 0,
+            link_type: interface.link_type,
+            original_len,
+            data: body,
+            comments: vec![],
+        })
+    }
+}
+
+impl<R: Read> Iterator for PcapReader<R> {
+    type Item = Result<Record>;
+
+    /// Returns the next record, after an error no further records are returned
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let record = match &self.format {
+            Format::Pcap { interface } => self.next_pcap(*interface),
+            Format::PcapNg { .. } => self.next_pcapng(),
+        };
+        if !matches!(record, Ok(Some(_))) {
+            self.done = true;
+        }
+        record.transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packet() -> Vec<u8> {
+        LayerMut::parse(r#"Ether / Ipv4(id: 1, src: 10.0.0.1) / Udp(dport: 53) / "dns""#)
+            .and_then(LayerMut::build)
+            .expect("could not build")
+    }
+
+    fn block(block_type: u32, body: &[u8], big_endian: bool) -> Vec<u8> {
+        let to_bytes = |v: u32| {
+            if big_endian {
+                v.to_be_bytes()
+            } else {
+                v.to_le_bytes()
+            }
+        };
+        let mut body = body.to_vec();
+        body.resize(body.len().next_multiple_of(4), 0);
+        let len = u32::try_from(body.len() + 12).expect("len");
+        [
+            &to_bytes(block_type)[..],
+            &to_bytes(len),
+            &body,
+            &to_bytes(len),
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn test_pcap() {
+        let packet = packet();
+        let len = u32::try_from(packet.len()).expect("len");
+
+        // little endian with microseconds and a record truncated to 20 bytes
+        let mut file = [0xa1b2_c3d4_u32.to_le_bytes(), [2, 0, 4, 0], [0; 4], [0; 4]].concat();
+        file.extend_from_slice(&65535_u32.to_le_bytes());
+        file.extend_from_slice(&1_u32.to_le_bytes());
+        for (seconds, captured) in [(10_u32, &packet[..]), (11, &packet[..20])] {
+            file.extend_from_slice(&seconds.to_le_bytes());
+            file.extend_from_slice(&500_u32.to_le_bytes());
+            file.extend_from_slice(&u32::try_from(captured.len()).expect("len").to_le_bytes());
+            file.extend_from_slice(&len.to_le_bytes());
+            file.extend_from_slice(captured);
+        }
+        let records: Vec<Record> = PcapReader::new(&file[..])
+            .expect("could not read")
+            .collect::<Result<_>>()
+            .expect("could not read");
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].timestamp, Duration::from_micros(10_000_500));
+        assert_eq!(records[1].original_len, len);
+        assert!(matches!(records[0].layer(), Some(Layer::Ether(_))));
+        let Ok(LayerMut::Ether(mut ether)) = records[0].layer_mut() else {
+            panic!("no ether layer");
+        };
+        assert!(matches!(
+            ether.get_layer(&Layers::Udp),
+            Some(LayerMut::Udp(_))
+        ));
+        assert_eq!(ether.build().expect("could not build"), packet);
+        assert!(matches!(
+            records[1].layer_mut(),
+            Err(Error::Truncated {
+                layer: Layers::Ipv4,
+                offset: 14
+            })
+        ));
+
+        // big endian with nanoseconds and raw IP packets
+        let mut file = [0xa1b2_3c4d_u32.to_be_bytes(), [0, 2, 0, 4], [0; 4], [0; 4]].concat();
+        file.extend_from_slice(&65535_u32.to_be_bytes());
+        file.extend_from_slice(&101_u32.to_be_bytes());
+        for value in [1, 7, len - 14, len - 14] {
+            file.extend_from_slice(&u32::to_be_bytes(value));
+        }
+        file.extend_from_slice(&packet[14..]);
+        let mut reader = PcapReader::new(&file[..]).expect("could not read");
+        let record = reader.next().expect("record").expect("could not read");
+        assert_eq!(record.timestamp, Duration::new(1, 7));
+        assert_eq!(record.link_type, LinkType::Raw);
+        assert!(matches!(record.layer_mut(), Ok(LayerMut::Ipv4(_))));
+        assert!(reader.next().is_none());
+
+        // a truncated record returns an error and ends the iteration
+        let mut reader = PcapReader::new(&file[..file.len() - 1]).expect("could not read");
+        assert!(matches!(
+            reader.next(),
+            Some(Err(Error::Capture { offset: 24, .. }))
+        ));
+        assert!(reader.next().is_none());
+        assert!(PcapReader::new(&[0_u8; 24][..]).is_err());
+    }
+
+    #[test]
+    fn test_pcapng() {
+        let packet = packet();
+        let len = u32::try_from(packet.len()).expect("len");
+        let shb = |big_endian| {
+            let magic = if big_endian {
+                PCAPNG_BYTE_ORDER.to_be_bytes()
+            } else {
+                PCAPNG_BYTE_ORDER.to_le_bytes()
+            };
+            block(
+                PCAPNG_SHB,
+                &[&magic[..], &[1, 0, 0, 0], &[0xff; 8]].concat(),
+                big_endian,
+            )
+        };
+        // Linux cooked capture header with the IPv4 protocol
+        let mut sll = vec![0; 14];
+        sll.extend_from_slice(&[0x08, 0x00]);
+        sll.extend_from_slice(&packet[14..]);
+        let sll_len = u32::try_from(sll.len()).expect("len");
+
+        let mut file = shb(false);
+        // interface 0: ethernet with the default resolution of microseconds
+        file.extend(block(PCAPNG_IDB, &[1, 0, 0, 0, 0, 0, 0, 0], false));
+        // interface 1: linux cooked capture with a nanosecond resolution
+        file.extend(block(
+            PCAPNG_IDB,
+            &[113, 0, 0, 0, 0, 0, 0, 0, 9, 0, 1, 0, 9, 0, 0, 0, 0, 0, 0, 0],
+            false,
+        ));
+        let epb = |interface: u32, units: u64, data: &[u8], len: u32, big_endian: bool| {
+            let to_bytes = |v: u32| {
+                if big_endian {
+                    v.to_be_bytes()
+                } else {
+                    v.to_le_bytes()
+                }
+            };
+            #[allow(clippy::cast_possible_truncation)]
+            let body = [
+                &to_bytes(interface)[..],
+                &to_bytes((units >> 32) as u32),
+                &to_bytes(units as u32),
+                &to_bytes(u32::try_from(data.len()).expect("len")),
+                &to_bytes(len),
+                data,
+            ]
+            .concat();
+            block(PCAPNG_EPB, &body, big_endian)
+        };
+        file.extend(epb(1, 5_000_000_123, &sll, sll_len, false));
+        // unknown blocks are skipped
+        file.extend(block(0x0bad, &[1, 2, 3], false));
+        file.extend(block(
+            PCAPNG_SPB,
+            &[&len.to_le_bytes()[..], &packet].concat(),
+            false,
+        ));
+        file.extend(epb(0, 2_000_001, &packet, len, false));
+        // a second section in big endian with raw IPv4 packets
+        file.extend(shb(true));
+        file.extend(block(PCAPNG_IDB, &[0, 228, 0, 0, 0, 0, 0, 0], true));
+        file.extend(epb(0, 1, &packet[14..], len - 14, true));
+
+        let records: Vec<Record> = PcapReader::new(&file[..])
+            .expect("could not read")
+            .collect::<Result<_>>()
+            .expect("could not read");
+        assert_eq!(records.len(), 4);
+
+        assert_eq!(records[0].interface, 1);
+        assert_eq!(records[0].link_type, LinkType::LinuxSll);
+        assert_eq!(records[0].timestamp, Duration::new(5, 123));
+        assert!(matches!(records[0].layer(), Some(Layer::Ipv4(_))));
+        assert!(matches!(records[0].layer_mut(), Ok(LayerMut::Ipv4(_))));
+
+        assert_eq!(records[1].link_type, LinkType::Ethernet);
+        assert_eq!(records[1].data, packet);
+        assert_eq!(records[2].timestamp, Duration::from_micros(2_000_001));
+        assert!(matches!(records[2].layer_mut(), Ok(LayerMut::Ether(_))));
+
+        assert_eq!(records[3].link_type, LinkType::Ipv4);
+        assert_eq!(records[3].data, packet[14..]);
+
+        // packets of an unknown interface are an error
+        let mut file = shb(false);
+        file.extend(epb(0, 0, &packet, len, false));
+        let mut reader = PcapReader::new(&file[..]).expect("could not read");
+        assert!(matches!(
+            reader.next(),
+            Some(Err(Error::Capture { offset: 28, .. }))
+        ));
+    }
+}
@@ -0,0 +1,274 @@
+use super::{
+    IF_TSRESOL, LinkType, OPT_COMMENT, OPT_ENDOFOPT, PCAP_MAGIC_NANOS, PCAPNG_BYTE_ORDER,
+    PCAPNG_EPB, PCAPNG_IDB, PCAPNG_SHB, SHB_USERAPPL,
+};
+use crate::magics::find_magics;
+use crate::{Error, LayerMut, Result};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::Duration;
+
+/// Format of the written capture file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PcapFormat {
+    /// Classic pcap with nanosecond timestamps, comments are not supported
+    Pcap,
+    /// pcapng with a single interface and nanosecond timestamps
+    PcapNg,
+}
+
+/// Writes packets into a pcap or pcapng file, e.g. to inspect the crafted packets with
+/// Wireshark. All values are written in little endian.
+#[derive(Debug)]
+pub struct PcapWriter<W: Write> {
+    writer: W,
+    format: PcapFormat,
+    link_type: LinkType,
+    magic_comments: bool,
+}
+
+impl PcapWriter<BufWriter<File>> {
+    /// Creates the capture file at `path`
+    ///
+    /// # Errors
+    /// Returns `Error::Io` if the file could not be written
+    pub fn create(path: impl AsRef<Path>, format: PcapFormat, link_type: LinkType) -> Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?), format, link_type)
+    }
+}
+
+fn u32_len(len: usize) -> Result<u32> {
+    u32::try_from(len).map_err(|_| Error::Capture {
+        offset: 0,
+        message: format!("length {len} too large"),
+    })
+}
+
+/// Appends a pcapng option padded to a multiple of 4 bytes
+fn push_option(buf: &mut Vec<u8>, code: u16, value: &[u8]) -> Result<()> {
+    let len = u16::try_from(value.len()).map_err(|_| Error::Capture {
+        offset: 0,
+        message: format!("option length {} too large", value.len()),
+    })?;
+    buf.extend_from_slice(&code.to_le_bytes());
+    buf.extend_from_slice(&len.to_le_bytes());
+    buf.extend_from_slice(value);
+    buf.resize(buf.len().next_multiple_of(4), 0);
+    Ok(())
+}
+
+impl<W: Write> PcapWriter<W> {
+    /// Creates a writer and writes the file header. For pcapng the section header and the
+    /// description of the interface with the link type are written.
+    ///
+    /// # Errors
+    /// Returns `Error::Io` if the writer fails
+    pub fn new(writer: W, format: PcapFormat, link_type: LinkType) -> Result<Self> {
+        let mut pcap = Self {
+            writer,
+            format,
+            link_type,
+            magic_comments: false,
+        };
+        let link_type = u16::from(link_type);
+        match format {
+            PcapFormat::Pcap => {
+                let mut header = PCAP_MAGIC_NANOS.to_le_bytes().to_vec();
+                header.extend_from_slice(&[2, 0, 4, 0]);
+                // time zone, timestamp accuracy and a snap length of 65535
+                header.extend_from_slice(&[0; 8]);
+                header.extend_from_slice(&65535_u32.to_le_bytes());
+                header.extend_from_slice(&u32::from(link_type).to_le_bytes());
+                pcap.writer.write_all(&header)?;
+            }
+            PcapFormat::PcapNg => {
+                let mut body = PCAPNG_BYTE_ORDER.to_le_bytes().to_vec();
+                body.extend_from_slice(&[1, 0, 0, 0]);
+                // the section length is not known
+                body.extend_from_slice(&[0xff; 8]);
+                push_option(&mut body, SHB_USERAPPL, b"pnet_layers")?;
+                push_option(&mut body, OPT_ENDOFOPT, &[])?;
+                pcap.write_block(PCAPNG_SHB, &body)?;
+
+                let mut body = link_type.to_le_bytes().to_vec();
+                body.extend_from_slice(&[0; 6]);
+                push_option(&mut body, IF_TSRESOL, &[9])?;
+                push_option(&mut body, OPT_ENDOFOPT, &[])?;
+                pcap.write_block(PCAPNG_IDB, &body)?;
+            }
+        }
+        Ok(pcap)
+    }
+
+    /// If enabled, a comment naming the magic values found in the packet is added to each
+    /// packet, see `magics::find_magics`. Only supported by pcapng.
+    pub fn set_magic_comments(&mut self, enabled: bool) {
+        self.magic_comments = enabled;
+    }
+
+    fn write_block(&mut self, block_type: u32, body: &[u8]) -> Result<()> {
+        let len = u32_len(body.len() + 12)?.to_le_bytes();
+        self.writer.write_all(&block_type.to_le_bytes())?;
+        self.writer.write_all(&len)?;
+        self.writer.write_all(body)?;
+        self.writer.write_all(&len)?;
+        Ok(())
+    }
+
+    /// Writes a built packet captured at `timestamp` since the unix epoch
+    ///
+    /// # Errors
+    /// Returns `Error::Io` if the writer fails
+    pub fn write(&mut self, timestamp: Duration, packet: &[u8]) -> Result<()> {
+        self.write_with_comments(timestamp, packet, &[])
+    }
+
+    /// Writes a built packet with comments, the comments are dropped for pcap files
+    ///
+    /// # Errors
+    /// Returns `Error::Io` if the writer fails or `Error::Capture` if the packet, a comment or
+    /// the timestamp is too large for the format
+    pub fn write_with_comments(
+        &mut self,
+        timestamp: Duration,
+        packet: &[u8],
+        comments: &[&str],
+    ) -> Result<()> {
+        let len = u32_len(packet.len())?;
+        let too_large = || Error::Capture {
+            offset: 0,
+            message: format!("timestamp {timestamp:?} too large"),
+        };
+        match self.format {
+            PcapFormat::Pcap => {
+                let seconds = u32::try_from(timestamp.as_secs()).map_err(|_| too_large())?;
+                self.writer.write_all(&seconds.to_le_bytes())?;
+                self.writer
+                    .write_all(&timestamp.subsec_nanos().to_le_bytes())?;
+                self.writer.write_all(&len.to_le_bytes())?;
+                self.writer.write_all(&len.to_le_bytes())?;
+                self.writer.write_all(packet)?;
+            }
+            PcapFormat::PcapNg => {
+                let nanos = u64::try_from(timestamp.as_nanos()).map_err(|_| too_large())?;
+                let mut body = vec![0; 4];
+                #[allow(clippy::cast_possible_truncation)]
+                {
+                    body.extend_from_slice(&((nanos >> 32) as u32).to_le_bytes());
+                    body.extend_from_slice(&(nanos as u32).to_le_bytes());
+                }
+                body.extend_from_slice(&len.to_le_bytes());
+                body.extend_from_slice(&len.to_le_bytes());
+                body.extend_from_slice(packet);
+                body.resize(body.len().next_multiple_of(4), 0);
+                let magics = if self.magic_comments {
+                    find_magics(packet, self.link_type)
+                } else {
+                    vec![]
+                };
+                for comment in comments {
+                    push_option(&mut body, OPT_COMMENT, comment.as_bytes())?;
+                }
+                if !magics.is_empty() {
+                    let comment = format!("pnet_layers magics: {}", magics.join(", "));
+                    push_option(&mut body, OPT_COMMENT, comment.as_bytes())?;
+                }
+                if !comments.is_empty() || !magics.is_empty() {
+                    push_option(&mut body, OPT_ENDOFOPT, &[])?;
+                }
+                self.write_block(PCAPNG_EPB, &body)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Builds the layer stack and writes it
+    ///
+    /// # Errors
+    /// Returns the error of `LayerMut::build` or `Error::Io` if the writer fails
+    pub fn write_layer(&mut self, timestamp: Duration, layer: LayerMut) -> Result<()> {
+        self.write(timestamp, &layer.build()?)
+    }
+
+    /// Flushes the writer
+    ///
+    /// # Errors
+    /// Returns `Error::Io` if the writer fails
+    pub fn flush(&mut self) -> Result<()> {
+        Ok(self.writer.flush()?)
+    }
+
+    /// Flushes and returns the underlying writer
+    ///
+    /// # Errors
+    /// Returns `Error::Io` if the writer fails
+    pub fn into_inner(mut self) -> Result<W> {
+        self.flush()?;
+        Ok(self.writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pcap::{PcapReader, Record};
+
+    #[test]
+    fn test_pcap_writer() {
+        let packet = LayerMut::parse("Ether / Vlan(id: 2) / Ipv4(id: 1, ttl: 64) / Udp")
+            .and_then(LayerMut::build)
+            .expect("could not build");
+        let magic = LayerMut::parse("Ether / Ipv4 / Udp").expect("could not parse");
+        let timestamp = Duration::new(1_700_000_000, 123_456_789);
+
+        for format in [PcapFormat::Pcap, PcapFormat::PcapNg] {
+            let mut writer =
+                PcapWriter::new(vec![], format, LinkType::Ethernet).expect("could not write");
+            writer.set_magic_comments(true);
+            writer
+                .write_with_comments(timestamp, &packet, &["probe 1"])
+                .expect("could not write");
+            writer
+                .write_layer(Duration::from_secs(1), magic.clone())
+                .expect("could not write");
+            let file = writer.into_inner().expect("could not write");
+
+            let records: Vec<Record> = PcapReader::new(&file[..])
+                .expect("could not read")
+                .collect::<Result<_>>()
+                .expect("could not read");
+            assert_eq!(records.len(), 2);
+            assert_eq!(records[0].timestamp, timestamp);
+            assert_eq!(records[0].data, packet);
+            assert_eq!(records[0].link_type, LinkType::Ethernet);
+            assert_eq!(records[1].timestamp, Duration::from_secs(1));
+            if format == PcapFormat::PcapNg {
+                assert_eq!(records[0].comments, ["probe 1"]);
+                assert_eq!(records[1].comments, ["pnet_layers magics: MAGIC_IPV4_TTL"]);
+            } else {
+                assert!(records[0].comments.is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn test_pcap_writer_link_types() {
+        let magic = LayerMut::parse("Ipv4 / Udp").expect("could not parse");
+        for link_type in [LinkType::Raw, LinkType::Ipv4] {
+            let mut writer =
+                PcapWriter::new(vec![], PcapFormat::PcapNg, link_type).expect("could not write");
+            writer.set_magic_comments(true);
+            writer
+                .write_layer(Duration::from_secs(1), magic.clone())
+                .expect("could not write");
+            let file = writer.into_inner().expect("could not write");
+            let records: Vec<Record> = PcapReader::new(&file[..])
+                .expect("could not read")
+                .collect::<Result<_>>()
+                .expect("could not read");
+            assert_eq!(records[0].link_type, link_type);
+            assert_eq!(records[0].comments, ["pnet_layers magics: MAGIC_IPV4_TTL"]);
+        }
+    }
+}
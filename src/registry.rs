@@ -0,0 +1,402 @@
+//! Runtime registry binding custom protocols to the layers below them.
+//!
+//! A downstream crate describes its protocol with the `Protocol` trait and binds it to an
+//! `EtherType`, an IP protocol number or a UDP or TCP port. Parsing dispatches to a bound
+//! protocol before the built-in layers, so a binding can also override a built-in protocol.
+//! The parsed or crafted layer is a `LayerMut::Custom` which is built like any other layer.
+//!
+//! ```
+//! use pnet_layers::registry::{self, Binding, Protocol};
+//! use pnet_layers::{EtherMut, Field, LayerMut, LayerMutable, Result};
+//! use std::sync::Arc;
+//!
+//! /// Header with a 16 bit sequence number followed by an IPv4 packet
+//! struct Tunnel;
+//!
+//! impl Protocol for Tunnel {
+//!     fn name(&self) -> &'static str {
+//!         "Tunnel"
+//!     }
+//!     fn fields(&self) -> &'static [Field] {
+//!         const FIELDS: &[Field] = &[Field::int("seq", 0, 16)];
+//!         FIELDS
+//!     }
+//!     fn new_header(&self) -> Vec<u8> {
+//!         vec![0; 2]
+//!     }
+//!     fn header_len(&self, _buf: &[u8]) -> Result<usize> {
+//!         Ok(2)
+//!     }
+//!     fn next(&self, _header: &[u8]) -> Option<Binding> {
+//!         Some(Binding::EtherType(0x0800))
+//!     }
+//! }
+//!
+//! registry::bind(Binding::EtherType(0x88b5), Arc::new(Tunnel));
+//! let packet = LayerMut::parse("Ether / Tunnel(seq: 7) / Ipv4 / Udp")?;
+//! let ether = EtherMut::from_buf(packet.build()?)?;
+//! assert!(matches!(ether.upper_layer.as_deref(), Some(LayerMut::Custom(_))));
+//! # registry::unbind(Binding::EtherType(0x88b5));
+//! # Ok::<(), pnet_layers::Error>(())
+//! ```
+
+use crate::{CustomMut, Field, LayerMut, Layers, PayloadMut, RawMut, Result};
+use pnet::packet::ethernet::EtherType;
+use pnet::packet::ip::IpNextHeaderProtocol;
+use std::collections::BTreeMap;
+use std::sync::{Arc, PoisonError, RwLock};
+
+/// The field of a lower layer announcing the protocol of its payload
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Binding {
+    /// `EtherType` of an Ethernet or VLAN header
+    EtherType(u16),
+    /// Protocol of an IPv4 header or next header of an IPv6 header or extension header
+    IpProto(u8),
+    /// Source or destination port of an UDP header
+    UdpPort(u16),
+    /// Source or destination port of a TCP header
+    TcpPort(u16),
+}
+
+impl From<EtherType> for Binding {
+    fn from(ether_type: EtherType) -> Self {
+        Self::EtherType(ether_type.0)
+    }
+}
+
+impl From<IpNextHeaderProtocol> for Binding {
+    fn from(proto: IpNextHeaderProtocol) -> Self {
+        Self::IpProto(proto.0)
+    }
+}
+
+/// Description of a custom protocol which can be bound to a lower layer
+pub trait Protocol: Send + Sync {
+    /// Name of the layer, used e.g. to craft the layer from a string
+    fn name(&self) -> &'static str;
+
+    /// Fields of the header which can be accessed by their name
+    fn fields(&self) -> &'static [Field] {
+        &[]
+    }
+
+    /// Returns a header with default values for crafting the layer
+    fn new_header(&self) -> Vec<u8>;
+
+    /// Returns the length of the header at the start of `buf`
+    ///
+    /// # Errors
+    /// Returns `Error::Truncated` if `buf` is too short for the header
+    fn header_len(&self, buf: &[u8]) -> Result<usize>;
+
+    /// Returns the binding announcing the payload following the header. Without a binding the
+    /// payload is kept as `Payload`.
+    fn next(&self, _header: &[u8]) -> Option<Binding> {
+        None
+    }
+
+    /// Fills in the header before it is written in front of the built payload, e.g. a length
+    /// or checksum or the type of the upper layer
+    ///
+    /// # Errors
+    /// Returns an error if the header can not be built
+    fn build(
+        &self,
+        _header: &mut Vec<u8>,
+        _upper_layer: Option<&LayerMut>,
+        _payload: &[u8],
+    ) -> Result<()> {
+        Ok(())
+    }
+}
+
+static REGISTRY: RwLock<BTreeMap<Binding, Arc<dyn Protocol>>> = RwLock::new(BTreeMap::new());
+
+/// Binds the protocol to the lower layer field and returns the protocol bound before
+pub fn bind(binding: Binding, protocol: Arc<dyn Protocol>) -> Option<Arc<dyn Protocol>> {
+    REGISTRY
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .insert(binding, protocol)
+}
+
+/// Removes the binding and returns the protocol bound before
+pub fn unbind(binding: Binding) -> Option<Arc<dyn Protocol>> {
+    REGISTRY
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .remove(&binding)
+}
+
+/// Returns the protocol bound to the lower layer field
+#[must_use]
+pub fn lookup(binding: Binding) -> Option<Arc<dyn Protocol>> {
+    REGISTRY
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get(&binding)
+        .cloned()
+}
+
+/// Returns a bound protocol with the given name, the name is case insensitive
+#[must_use]
+pub fn by_name(name: &str) -> Option<Arc<dyn Protocol>> {
+    REGISTRY
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .values()
+        .find(|protocol| protocol.name().eq_ignore_ascii_case(name))
+        .cloned()
+}
+
+/// The built-in layers and the bindings announcing them
+const BUILTIN: &[(Binding, Layers)] = &[
+    (Binding::EtherType(0x8100), Layers::Vlan),
+    (Binding::EtherType(0x0800), Layers::Ipv4),
+    (Binding::EtherType(0x86dd), Layers::Ipv6),
+    (Binding::EtherType(0x0806), Layers::Arp),
+    (Binding::IpProto(0), Layers::HopByHop),
+    (Binding::IpProto(1), Layers::Icmp),
+    (Binding::IpProto(4), Layers::Ipv4),
+    (Binding::IpProto(6), Layers::Tcp),
+    (Binding::IpProto(17), Layers::Udp),
+    (Binding::IpProto(41), Layers::Ipv6),
+    (Binding::IpProto(43), Layers::Routing),
+    (Binding::IpProto(44), Layers::Fragment),
+    (Binding::IpProto(51), Layers::Ah),
+    (Binding::IpProto(58), Layers::Icmpv6),
+    (Binding::IpProto(60), Layers::DestOpts),
+];
+
+/// Returns the first binding of the layer matching `filter`, for a custom layer the binding
+/// of its protocol and for a raw layer its protocol number
+fn binding<T>(layer: &LayerMut, filter: impl Fn(Binding) -> Option<T>) -> Option<T> {
+    match layer {
+        LayerMut::Custom(custom) => binding_of(custom.protocol(), filter),
+        LayerMut::Raw(raw) if raw.proto != 0 => filter(Binding::EtherType(raw.proto))
+            .or_else(|| filter(Binding::IpProto(u8::try_from(raw.proto).ok()?))),
+        other => BUILTIN
+            .iter()
+            .filter(|(_, kind)| *kind == other.kind())
+            .find_map(|(binding, _)| filter(*binding)),
+    }
+}
+
+/// Returns the `EtherType` announcing the layer in an Ethernet or VLAN header
+#[must_use]
+pub fn ether_type(layer: &LayerMut) -> Option<u16> {
+    binding(layer, |binding| match binding {
+        Binding::EtherType(ether_type) => Some(ether_type),
+        _ => None,
+    })
+}
+
+/// Returns the IP protocol number announcing the layer in an IPv4 or IPv6 header
+#[must_use]
+pub fn ip_proto(layer: &LayerMut) -> Option<u8> {
+    binding(layer, |binding| match binding {
+        Binding::IpProto(proto) => Some(proto),
+        _ => None,
+    })
+}
+
+/// Returns the first binding of the protocol matching `filter`, used to set the type field of
+/// the lower layer on build
+pub(crate) fn binding_of<T>(
+    protocol: &Arc<dyn Protocol>,
+    filter: impl Fn(Binding) -> Option<T>,
+) -> Option<T> {
+    REGISTRY
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .iter()
+        .filter(|(_, bound)| Arc::ptr_eq(bound, protocol))
+        .find_map(|(binding, _)| filter(*binding))
+}
+
+/// Returns the built-in layer announced by the binding
+pub(crate) fn builtin(binding: Binding) -> Option<Layers> {
+    BUILTIN
+        .iter()
+        .find(|(bound, _)| *bound == binding)
+        .map(|(_, kind)| *kind)
+}
+
+/// Layer announced by a binding
+pub(crate) enum Next {
+    Builtin(Layers),
+    Custom(Arc<dyn Protocol>),
+}
+
+/// Returns the layer announced by the binding, a bound protocol is preferred over a built-in
+/// layer in `allowed`. Payloads of other protocols are kept as `Raw`, the payload of a port or
+/// without a binding as `Payload`.
+pub(crate) fn next(binding: Option<Binding>, allowed: &[Layers]) -> Next {
+    let Some(binding) = binding else {
+        return Next::Builtin(Layers::Payload);
+    };
+    if let Some(protocol) = lookup(binding) {
+        return Next::Custom(protocol);
+    }
+    match builtin(binding) {
+        Some(kind) if allowed.contains(&kind) => Next::Builtin(kind),
+        _ => match binding {
+            Binding::EtherType(_) | Binding::IpProto(_) => Next::Builtin(Layers::Raw),
+            Binding::UdpPort(_) | Binding::TcpPort(_) => Next::Builtin(Layers::Payload),
+        },
+    }
+}
+
+/// Returns the protocol bound to the destination or source port of an UDP or TCP header or
+/// `Payload`
+pub(crate) fn next_ports(binding: fn(u16) -> Binding, sport: u16, dport: u16) -> Next {
+    lookup(binding(dport))
+        .or_else(|| lookup(binding(sport)))
+        .map_or(Next::Builtin(Layers::Payload), Next::Custom)
+}
+
+/// Parses the payload as the layer returned by `next`
+fn parse_as(next: Next, binding: Option<Binding>, payload: Vec<u8>) -> Result<Option<LayerMut>> {
+    if payload.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(match next {
+        Next::Custom(protocol) => LayerMut::Custom(CustomMut::parse(protocol, payload)?),
+        Next::Builtin(Layers::Payload) => LayerMut::Payload(PayloadMut { buf: payload }),
+        Next::Builtin(Layers::Raw) => LayerMut::Raw(RawMut {
+            buf: payload,
+            proto: match binding {
+                Some(Binding::EtherType(proto)) => proto,
+                Some(Binding::IpProto(proto)) => u16::from(proto),
+                _ => 0,
+            },
+        }),
+        Next::Builtin(kind) => LayerMut::from_buf(kind, payload)?,
+    }))
+}
+
+/// Parses the payload announced by the binding like returned by `next`
+pub(crate) fn parse_next(
+    binding: Option<Binding>,
+    payload: Vec<u8>,
+    allowed: &[Layers],
+) -> Result<Option<LayerMut>> {
+    parse_as(next(binding, allowed), binding, payload)
+}
+
+/// Parses the payload of an UDP or TCP header like returned by `next_ports`
+pub(crate) fn parse_ports(
+    binding: fn(u16) -> Binding,
+    sport: u16,
+    dport: u16,
+    payload: &[u8],
+) -> Result<Option<LayerMut>> {
+    parse_as(next_ports(binding, sport, dport), None, payload.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EtherMut, LayerMutable, Value};
+
+    /// Header with a type and length field followed by the announced protocol
+    struct Tlv;
+
+    impl Protocol for Tlv {
+        fn name(&self) -> &'static str {
+            "Tlv"
+        }
+        fn fields(&self) -> &'static [Field] {
+            const FIELDS: &[Field] = &[Field::int("type", 0, 16), Field::int("len", 16, 16)];
+            FIELDS
+        }
+        fn new_header(&self) -> Vec<u8> {
+            vec![0; 4]
+        }
+        fn header_len(&self, buf: &[u8]) -> Result<usize> {
+            if buf.len() < 4 {
+                return Err(crate::Error::Truncated {
+                    layer: Layers::Custom,
+                    offset: 0,
+                });
+            }
+            Ok(4)
+        }
+        fn next(&self, header: &[u8]) -> Option<Binding> {
+            Some(Binding::EtherType(u16::from_be_bytes([
+                header[0], header[1],
+            ])))
+        }
+        fn build(
+            &self,
+            header: &mut Vec<u8>,
+            upper_layer: Option<&LayerMut>,
+            payload: &[u8],
+        ) -> Result<()> {
+            if let Some(LayerMut::Ipv4(_)) = upper_layer {
+                header[0..2].copy_from_slice(&0x0800_u16.to_be_bytes());
+            }
+            let len = u16::try_from(payload.len()).unwrap_or(u16::MAX);
+            header[2..4].copy_from_slice(&len.to_be_bytes());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_registry() {
+        let tlv: Arc<dyn Protocol> = Arc::new(Tlv);
+        assert!(bind(Binding::EtherType(0x88b6), tlv.clone()).is_none());
+        assert!(bind(Binding::UdpPort(61234), tlv.clone()).is_none());
+        assert!(by_name("tlv").is_some());
+
+        let packet = LayerMut::parse(
+            "Ether / Tlv / Ipv4(src: 10.0.0.1) / Udp(dport: 61234) / Tlv(type: 1) / \"abc\"",
+        )
+        .expect("could not parse");
+        let bytes = packet.build().expect("could not build");
+        assert_eq!(bytes[12..18], [0x88, 0xb6, 0x08, 0x00, 0, 20 + 8 + 4 + 3]);
+
+        let ether = LayerMut::Ether(EtherMut::from_buf(bytes.clone()).expect("could not parse"));
+        let kinds: Vec<Layers> = std::iter::successors(Some(&ether), |l| l.upper_layer())
+            .map(LayerMut::kind)
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                Layers::Ether,
+                Layers::Custom,
+                Layers::Ipv4,
+                Layers::Udp,
+                Layers::Custom,
+                Layers::Raw
+            ]
+        );
+        let tlv_layer = ether.upper_layer().expect("no custom layer");
+        assert_eq!(tlv_layer.name(), "Tlv");
+        assert_eq!(tlv_layer.get_field("len"), Some(Value::Int(20 + 8 + 4 + 3)));
+        assert_eq!(ether.clone().build().expect("could not build"), bytes);
+        assert_eq!(
+            LayerMut::parse(&ether.canonical())
+                .and_then(LayerMut::build)
+                .expect("could not build"),
+            bytes
+        );
+
+        let json = serde_json::to_string(&ether).expect("could not serialize");
+        assert!(json.contains(r#"{"Tlv":{"type":2048"#));
+        let parsed: LayerMut = serde_json::from_str(&json).expect("could not deserialize");
+        assert_eq!(parsed.build().expect("could not build"), bytes);
+
+        // without the bindings the payloads are kept as they are
+        assert!(unbind(Binding::EtherType(0x88b6)).is_some());
+        assert!(unbind(Binding::UdpPort(61234)).is_some());
+        assert!(by_name("tlv").is_none());
+        let ether = EtherMut::from_buf(bytes.clone()).expect("could not parse");
+        assert!(matches!(
+            ether.upper_layer.as_deref(),
+            Some(LayerMut::Raw(_))
+        ));
+        assert_eq!(ether.build().expect("could not build"), bytes);
+    }
+}
@@ -0,0 +1,214 @@
+use crate::registry;
+use crate::{CustomMut, Field, FieldKind, LayerMut, Layers, TrailerMut, Value};
+use serde::de::{self, DeserializeSeed, MapAccess, Visitor};
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+
+/// Key of the upper layer in the map of a layer
+const UPPER_LAYER: &str = "upper_layer";
+
+/// Key of the trailer in the map of an Ethernet layer
+const TRAILER: &str = "trailer";
+
+/// Serializes the header fields of a layer as map. Integers are written as numbers, flags by
+/// their names, addresses as strings and bytes as hex string with a `0x` prefix. The upper
+/// layer is written as `upper_layer` entry and a trailer as hex string in the `trailer` entry.
+pub(crate) fn serialize_layer<S: Serializer>(
+    fields: &[Field],
+    header: &[u8],
+    upper_layer: Option<&LayerMut>,
+    trailer: Option<&TrailerMut>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let mut map = serializer.serialize_map(None)?;
+    for field in fields {
+        match (field.kind, field.get(header)) {
+            (_, None) => {}
+            (FieldKind::Int(_), Some(Value::Int(value))) => {
+                map.serialize_entry(field.name, &value)?;
+            }
+            (_, Some(Value::Bytes(bytes))) => {
+                map.serialize_entry(field.name, &format!("0x{}", hex::encode(bytes)))?;
+            }
+            (_, Some(value)) => map.serialize_entry(field.name, &field.format(&value))?,
+        }
+    }
+    if let Some(upper_layer) = upper_layer {
+        map.serialize_entry(UPPER_LAYER, upper_layer)?;
+    }
+    if let Some(trailer) = trailer {
+        map.serialize_entry(TRAILER, &format!("0x{}", hex::encode(&trailer.buf)))?;
+    }
+    map.end()
+}
+
+/// Deserializes a layer of the given type from the map written by `serialize_layer`. Fields
+/// which are missing keep their default value.
+pub(crate) fn deserialize_layer<'de, D: Deserializer<'de>>(
+    kind: Layers,
+    deserializer: D,
+) -> Result<LayerMut, D::Error> {
+    deserializer.deserialize_map(LayerVisitor(LayerMut::new(kind)))
+}
+
+impl Serialize for LayerMut {
+    /// A layer is written as map with the layer name as single key
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(1))?;
+        map.serialize_entry(self.name(), &Fields(self))?;
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for LayerMut {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(NamedLayerVisitor)
+    }
+}
+
+/// Serializes only the fields of the layer without its name
+struct Fields<'a>(&'a LayerMut);
+
+impl Serialize for Fields<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_layer(
+            self.0.fields(),
+            self.0.header(),
+            self.0.upper_layer(),
+            self.0.trailer(),
+            serializer,
+        )
+    }
+}
+
+struct NamedLayerVisitor;
+
+impl<'de> Visitor<'de> for NamedLayerVisitor {
+    type Value = LayerMut;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a map with a layer name as single key")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<LayerMut, A::Error> {
+        let name: String = map
+            .next_key()?
+            .ok_or_else(|| de::Error::custom("missing layer name"))?;
+        let layer = Layers::from_name(&name)
+            .map(LayerMut::new)
+            .or_else(|| {
+                registry::by_name(&name).map(|p| LayerMut::Custom(CustomMut::with_protocol(p)))
+            })
+            .ok_or_else(|| de::Error::custom(format!("unknown layer `{name}`")))?;
+        let layer = map.next_value_seed(LayerSeed(layer))?;
+        if map.next_key::<String>()?.is_some() {
+            return Err(de::Error::custom("expected a single layer"));
+        }
+        Ok(layer)
+    }
+}
+
+/// Deserializes the fields into the given layer
+struct LayerSeed(LayerMut);
+
+impl<'de> DeserializeSeed<'de> for LayerSeed {
+    type Value = LayerMut;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<LayerMut, D::Error> {
+        deserializer.deserialize_map(LayerVisitor(self.0))
+    }
+}
+
+struct LayerVisitor(LayerMut);
+
+impl<'de> Visitor<'de> for LayerVisitor {
+    type Value = LayerMut;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a map with the fields of the {} layer", self.0.name())
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<LayerMut, A::Error> {
+        let mut layer = self.0;
+        let mut upper_layer = None;
+        let mut values = vec![];
+        while let Some(name) = map.next_key::<String>()? {
+            if name == UPPER_LAYER {
+                upper_layer = Some(map.next_value::<LayerMut>()?);
+                continue;
+            }
+            if name == TRAILER {
+                let text = map.next_value::<String>()?;
+                let buf = hex::decode(text.strip_prefix("0x").unwrap_or(&text))
+                    .map_err(de::Error::custom)?;
+                layer
+                    .add(LayerMut::Trailer(TrailerMut { buf }))
+                    .map_err(de::Error::custom)?;
+                continue;
+            }
+            let index = layer
+                .fields()
+                .iter()
+                .position(|field| field.name == name)
+                .ok_or_else(|| {
+                    de::Error::custom(format!("{} has no field `{name}`", layer.name()))
+                })?;
+            let value = match map.next_value::<RawValue>()? {
+                RawValue::Int(value) => Value::Int(value),
+                RawValue::Text(text) => layer.fields()[index]
+                    .parse(&text)
+                    .map_err(de::Error::custom)?,
+            };
+            values.push((index, value));
+        }
+        // the fields are set in the order of the header like they are serialized, so a field
+        // sizing the header, e.g. the ICMP type, is set before the fields depending on it
+        values.sort_by_key(|(index, _)| *index);
+        for (index, value) in values {
+            layer
+                .set_field(layer.fields()[index].name, &value)
+                .map_err(de::Error::custom)?;
+        }
+        if let Some(upper_layer) = upper_layer {
+            layer.add(upper_layer).map_err(de::Error::custom)?;
+        }
+        Ok(layer)
+    }
+}
+
+/// Field value as written by the serializer, text is parsed by the field
+enum RawValue {
+    Int(u64),
+    Text(String),
+}
+
+impl<'de> Deserialize<'de> for RawValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(RawValueVisitor)
+    }
+}
+
+struct RawValueVisitor;
+
+impl Visitor<'_> for RawValueVisitor {
+    type Value = RawValue;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an unsigned integer or a string")
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<RawValue, E> {
+        Ok(RawValue::Int(value))
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<RawValue, E> {
+        u64::try_from(value)
+            .map(RawValue::Int)
+            .map_err(|_| E::custom(format!("negative value {value}")))
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<RawValue, E> {
+        Ok(RawValue::Text(value.to_string()))
+    }
+}
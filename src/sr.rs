@@ -0,0 +1,265 @@
+//! Sending probes and matching the received responses, similar to `sr` and `sr1` of scapy.
+//!
+//! A received packet is matched to a probe with the `Answers` relation of the layers, see
+//! the `answers` module.
+
+use crate::io::PacketIo;
+use crate::traits::Answers;
+use crate::{Error, LayerMut, Result};
+use std::fmt::Display;
+use std::time::{Duration, Instant};
+
+/// Options of `sr` and `sr1`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SrOptions {
+    /// How long to wait for responses after the probes were sent
+    pub timeout: Duration,
+    /// How often the unanswered probes are sent again after the timeout
+    pub retries: usize,
+}
+
+impl Default for SrOptions {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(2),
+            retries: 0,
+        }
+    }
+}
+
+/// Result of `sr`
+#[derive(Debug, Clone, Default)]
+pub struct SrResult {
+    /// The probes as they were sent with their response
+    pub answered: Vec<(LayerMut, LayerMut)>,
+    /// The probes as they were sent which were not answered
+    pub unanswered: Vec<LayerMut>,
+    /// Number of received packets including the ones which did not answer a probe
+    pub received: usize,
+}
+
+impl Display for SrResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Received {} packets, got {} answers, remaining {} packets",
+            self.received,
+            self.answered.len(),
+            self.unanswered.len()
+        )
+    }
+}
+
+/// Sends the probes and collects their responses. The probes are built once, so the
+/// answered and unanswered probes contain the values filled in by `build`. After each
+/// `timeout` the unanswered probes are sent again until the retries are used up.
+///
+/// # Errors
+/// Returns the error of building a probe or the errors of the `PacketIo`. Received packets
+/// which can not be parsed are ignored.
+pub fn sr<I: PacketIo + ?Sized>(
+    io: &mut I,
+    probes: Vec<LayerMut>,
+    options: &SrOptions,
+) -> Result<SrResult> {
+    let mut unanswered = vec![];
+    for probe in probes {
+        let kind = probe.kind();
+        let bytes = probe.build()?;
+        let sent = LayerMut::from_buf(kind, bytes.clone())?;
+        unanswered.push((bytes, sent));
+    }
+
+    let mut result = SrResult::default();
+    for _ in 0..=options.retries {
+        if unanswered.is_empty() {
+            break;
+        }
+        for (bytes, _) in &unanswered {
+            io.send_bytes(bytes)?;
+        }
+        let deadline = Instant::now() + options.timeout;
+        while !unanswered.is_empty() {
+            let Some(timeout) = deadline.checked_duration_since(Instant::now()) else {
+                break;
+            };
+            let response = match io.recv(Some(timeout)) {
+                Ok(Some(response)) => response,
+                // the timeout expired or the backend has no more packets
+                Ok(None) => break,
+                Err(Error::Io(err)) => return Err(Error::Io(err)),
+                // packets which can not be parsed do not answer a probe
+                Err(_) => {
+                    result.received += 1;
+                    continue;
+                }
+            };
+            result.received += 1;
+            if let Some(i) = unanswered
+                .iter()
+                .position(|(_, probe)| response.answers(probe))
+            {
+                let (_, probe) = unanswered.remove(i);
+                result.answered.push((probe, response));
+            }
+        }
+    }
+    result.unanswered = unanswered.into_iter().map(|(_, probe)| probe).collect();
+    Ok(result)
+}
+
+/// Sends the probe and returns the first response
+///
+/// # Errors
+/// Returns the errors of `sr`
+pub fn sr1<I: PacketIo + ?Sized>(
+    io: &mut I,
+    probe: LayerMut,
+    options: &SrOptions,
+) -> Result<Option<LayerMut>> {
+    Ok(sr(io, vec![probe], options)?
+        .answered
+        .pop()
+        .map(|(_, response)| response))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Value;
+    use crate::helper::tcp::TcpPacket;
+    use crate::io::Loopback;
+    use crate::pcap::Record;
+    use crate::{LayerMutable, Layers};
+    use pnet::util::MacAddr;
+    use std::net::Ipv4Addr;
+
+    fn parse(input: &str) -> LayerMut {
+        LayerMut::parse(input).expect("could not parse")
+    }
+
+    /// Link answering each probe as soon as it is sent, so the test does not depend on timing
+    struct Responder {
+        io: Loopback,
+        peer: Loopback,
+        answered_echo: bool,
+    }
+
+    impl Responder {
+        fn respond(&mut self, request: LayerMut) -> Result<()> {
+            let LayerMut::Ether(mut ether) = request else {
+                return Ok(());
+            };
+            // unrelated traffic
+            self.peer.send(parse("Ether / Ipv4 / Udp(sport: 9)"))?;
+            if let Some(LayerMut::Tcp(tcp)) = ether.get_layer(&Layers::Tcp) {
+                let pkt = tcp.modify()?;
+                let (seq, sport, dport) =
+                    (pkt.get_sequence(), pkt.get_source(), pkt.get_destination());
+                self.peer.send(parse(&format!(
+                    "Ether / Ipv4(src: 10.0.0.2, dst: 10.0.0.1) / Tcp(sport: {dport}, dport: {sport}, ack: {}, flags: SA)",
+                    seq + 1
+                )))?;
+            } else if ether.get_layer(&Layers::Icmp).is_some() {
+                // the echo request is only answered when it is sent again
+                if self.answered_echo {
+                    self.peer.send(parse(
+                        "Ether / Ipv4(src: 10.0.0.2) / Icmp(type: 0, id: 7, seq: 1)",
+                    ))?;
+                }
+                self.answered_echo = true;
+            }
+            Ok(())
+        }
+    }
+
+    impl PacketIo for Responder {
+        fn send_bytes(&mut self, packet: &[u8]) -> Result<()> {
+            self.io.send_bytes(packet)?;
+            match self.peer.recv(None)? {
+                Some(request) => self.respond(request),
+                None => Ok(()),
+            }
+        }
+
+        /// The responses are queued when the probe is sent, so there is nothing to wait for
+        fn recv_record(&mut self, _timeout: Option<Duration>) -> Result<Option<Record>> {
+            self.io.recv_record(Some(Duration::ZERO))
+        }
+    }
+
+    #[test]
+    fn test_sr() {
+        let (io, peer) = Loopback::pair();
+        let mut io = Responder {
+            io,
+            peer,
+            answered_echo: false,
+        };
+
+        let syn = TcpPacket {
+            eth_src: MacAddr::zero(),
+            eth_dst: MacAddr::broadcast(),
+            vlan_id: None,
+            ipv4_src: Ipv4Addr::new(10, 0, 0, 1),
+            ipv4_dst: Ipv4Addr::new(10, 0, 0, 2),
+            dport: 80,
+            sport: 4000,
+        }
+        .syn(100)
+        .expect("could not craft");
+        let echo = parse("Ether / Ipv4(dst: 10.0.0.2) / Icmp(type: 8, id: 7, seq: 1)");
+        let unanswered = parse("Ether / Ipv4(dst: 10.0.0.3) / Udp(dport: 1)");
+
+        // the timeout is never reached, the responses are received right away
+        let options = SrOptions {
+            timeout: Duration::from_secs(30),
+            retries: 1,
+        };
+        let result = sr(
+            &mut io,
+            vec![LayerMut::Ether(syn.clone()), echo, unanswered],
+            &options,
+        )
+        .expect("sr failed");
+        assert_eq!(result.answered.len(), 2);
+        assert_eq!(result.unanswered.len(), 1);
+        assert!(matches!(
+            result.answered[0].0.upper_layer(),
+            Some(LayerMut::Ipv4(_))
+        ));
+        assert_eq!(
+            result.to_string(),
+            format!(
+                "Received {} packets, got 2 answers, remaining 1 packets",
+                result.received
+            )
+        );
+
+        let options = SrOptions {
+            timeout: Duration::from_secs(30),
+            retries: 0,
+        };
+        let probe = LayerMut::Ether(syn);
+        let mut response = sr1(&mut io, probe.clone(), &options)
+            .expect("sr1 failed")
+            .expect("no response");
+        assert!(response.answers(&probe));
+        let mut field = |layer, name| {
+            response
+                .get_layer_nth(&layer, 0)
+                .and_then(|layer| layer.get_field(name))
+        };
+        // the addresses and ports of the probe are swapped
+        assert_eq!(
+            field(Layers::Ipv4, "src"),
+            Some(Value::Ipv4(Ipv4Addr::new(10, 0, 0, 2)))
+        );
+        assert_eq!(
+            field(Layers::Ipv4, "dst"),
+            Some(Value::Ipv4(Ipv4Addr::new(10, 0, 0, 1)))
+        );
+        assert_eq!(field(Layers::Tcp, "sport"), Some(Value::Int(80)));
+        assert_eq!(field(Layers::Tcp, "dport"), Some(Value::Int(4000)));
+    }
+}
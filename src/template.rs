@@ -0,0 +1,490 @@
+//! Templates expanding into many packets.
+//!
+//! A `Template` is an Ethernet stack where fields of any layer hold a set of values instead of
+//! a single one. Iterating the template lazily yields every combination of the values as a
+//! concrete `EtherMut`, or the error of a value which could not be set.
+//!
+//! ```
+//! use pnet_layers::template::{Template, Values};
+//! use pnet_layers::{LayerMut, LayerMutable, Layers};
+//! use std::net::Ipv4Addr;
+//!
+//! let LayerMut::Ether(ether) = LayerMut::parse("Ether / Vlan / Ipv4 / Tcp")? else {
+//!     unreachable!()
+//! };
+//! let mut template = Template::new(ether);
+//! template.set(Layers::Vlan, "id", Values::Range(1..=4))?;
+//! template.set(Layers::Ipv4, "dst", Values::Ipv4Net(Ipv4Addr::new(10, 0, 0, 0), 30))?;
+//! template.set(Layers::Tcp, "flags", Values::Flags(u64::MAX))?;
+//! assert_eq!(template.len(), Some(4 * 4 * 256));
+//! for ether in template.iter().take(3) {
+//!     let _bytes = ether?.build()?;
+//! }
+//! # Ok::<(), pnet_layers::Error>(())
+//! ```
+
+use crate::{Error, EtherMut, LayerMut, Layers, Result, Value};
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::ops::RangeInclusive;
+
+/// The values a field of a template takes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Values {
+    /// Each value of the list
+    List(Vec<Value>),
+    /// Each integer of the range
+    Range(RangeInclusive<u64>),
+    /// Each address of the network with the given prefix length, including the network and
+    /// broadcast address
+    Ipv4Net(Ipv4Addr, u8),
+    /// Each address of the network with the given prefix length, only the first `u64::MAX`
+    /// addresses of a network larger than /64
+    Ipv6Net(Ipv6Addr, u8),
+    /// Each combination of the bits set in the mask. Bits outside of the field are ignored,
+    /// so `u64::MAX` yields all combinations of the flags of a field.
+    Flags(u64),
+}
+
+impl Values {
+    /// Returns the number of values, saturating at `u64::MAX`
+    #[must_use]
+    pub fn len(&self) -> u64 {
+        match self {
+            Self::List(values) => values.len() as u64,
+            Self::Range(range) if range.is_empty() => 0,
+            Self::Range(range) => (range.end() - range.start()).saturating_add(1),
+            Self::Ipv4Net(_, prefix) => 1 << (32 - u32::from((*prefix).min(32))),
+            Self::Ipv6Net(_, prefix) => 1_u64
+                .checked_shl(128 - u32::from((*prefix).min(128)))
+                .unwrap_or(u64::MAX),
+            Self::Flags(mask) => 1_u64.checked_shl(mask.count_ones()).unwrap_or(u64::MAX),
+        }
+    }
+
+    /// Returns true if there are no values
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the value with the given index, the index must be smaller than `len`
+    fn get(&self, index: u64) -> Value {
+        match self {
+            Self::List(values) => values[usize::try_from(index).unwrap_or(usize::MAX)].clone(),
+            Self::Range(range) => Value::Int(range.start() + index),
+            Self::Ipv4Net(addr, prefix) => {
+                let mask = u32::MAX.checked_shl(32 - u32::from(*prefix)).unwrap_or(0);
+                #[allow(clippy::cast_possible_truncation)]
+                Value::Ipv4(Ipv4Addr::from((u32::from(*addr) & mask) | index as u32))
+            }
+            Self::Ipv6Net(addr, prefix) => {
+                let mask = u128::MAX.checked_shl(128 - u32::from(*prefix)).unwrap_or(0);
+                Value::Ipv6(Ipv6Addr::from(
+                    (u128::from(*addr) & mask) | u128::from(index),
+                ))
+            }
+            Self::Flags(mask) => {
+                // deposit the bits of the index into the bits of the mask
+                let mut value = 0;
+                let mut bits = index;
+                for bit in (0..64).filter(|bit| (mask >> bit) & 1 == 1) {
+                    value |= (bits & 1) << bit;
+                    bits >>= 1;
+                }
+                Value::Int(value)
+            }
+        }
+    }
+}
+
+/// A field of the template and its values
+#[derive(Debug, Clone)]
+struct TemplateField {
+    layer: Layers,
+    /// Index of the layer among the layers of its type
+    nth: usize,
+    name: String,
+    values: Values,
+}
+
+/// An Ethernet stack where fields hold a set of values, see the module documentation
+#[derive(Debug, Clone)]
+pub struct Template {
+    base: EtherMut,
+    fields: Vec<TemplateField>,
+}
+
+/// Returns the `n`th layer of the given type in the stack counting from 0
+fn find_layer(packet: &mut LayerMut, layer: Layers, n: usize) -> Option<&mut LayerMut> {
+    match (packet.kind() == layer, n) {
+        (true, 0) => Some(packet),
+        (true, n) => packet.get_layer_nth(&layer, n - 1),
+        (false, n) => packet.get_layer_nth(&layer, n),
+    }
+}
+
+/// Returns the error of a missing layer
+fn missing_layer(layer: Layers, n: usize) -> Error {
+    Error::InvalidValue {
+        field: "",
+        message: match n {
+            0 => format!("the template has no {layer:?} layer"),
+            n => format!("the template has no {layer:?} layer with index {n}"),
+        },
+    }
+}
+
+impl Template {
+    /// Creates a template yielding only the given stack until values are set
+    #[must_use]
+    pub fn new(base: EtherMut) -> Self {
+        Self {
+            base,
+            fields: vec![],
+        }
+    }
+
+    /// Sets the values of a field of the first layer of the given type, replacing earlier
+    /// values of the field. Fields set first vary slowest when iterating.
+    ///
+    /// # Errors
+    /// Returns `Error::InvalidValue` if the stack has no such layer or field, a value does not
+    /// match the field or the prefix length of a network is larger than the address
+    pub fn set(&mut self, layer: Layers, field: &str, values: Values) -> Result<()> {
+        self.set_nth(layer, 0, field, values)
+    }
+
+    /// Sets the values of a field of the `n`th layer of the given type counting from 0 like
+    /// `set`, e.g. of the inner tag of a double tagged frame with `set_nth(Layers::Vlan, 1, ..)`
+    ///
+    /// # Errors
+    /// Returns the errors of `set`
+    pub fn set_nth(&mut self, layer: Layers, n: usize, field: &str, values: Values) -> Result<()> {
+        let invalid = |message: String| Error::InvalidValue { field: "", message };
+        let mut packet = LayerMut::Ether(self.base.clone());
+        let target = find_layer(&mut packet, layer, n).ok_or_else(|| missing_layer(layer, n))?;
+        let bits = layer
+            .fields()
+            .iter()
+            .find(|f| f.name == field)
+            .ok_or_else(|| invalid(format!("{layer:?} has no field `{field}`")))?
+            .bits()
+            .unwrap_or_default();
+        let values = match values {
+            Values::Flags(mask) if bits < 64 => Values::Flags(mask & ((1 << bits) - 1)),
+            Values::Ipv4Net(_, prefix) if prefix > 32 => {
+                return Err(invalid(format!("invalid prefix length {prefix}")));
+            }
+            Values::Ipv6Net(_, prefix) if prefix > 128 => {
+                return Err(invalid(format!("invalid prefix length {prefix}")));
+            }
+            values => values,
+        };
+        // the values grow with the index except for lists, so the first and last value catch
+        // most invalid values early, combinations are only checked while iterating
+        let checked: Vec<u64> = match &values {
+            Values::List(list) => (0..list.len() as u64).collect(),
+            values if values.is_empty() => vec![],
+            values => vec![0, values.len() - 1],
+        };
+        for index in checked {
+            target.set_field(field, &values.get(index))?;
+        }
+
+        self.fields
+            .retain(|f| !(f.layer == layer && f.nth == n && f.name == field));
+        self.fields.push(TemplateField {
+            layer,
+            nth: n,
+            name: field.to_string(),
+            values,
+        });
+        Ok(())
+    }
+
+    /// Returns the number of items yielded by the template or `None` if the number does
+    /// not fit into an `u64`
+    #[must_use]
+    pub fn len(&self) -> Option<u64> {
+        self.fields
+            .iter()
+            .try_fold(1_u64, |len, field| len.checked_mul(field.values.len()))
+    }
+
+    /// Returns true if the template yields no items because a field has no values
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.fields.iter().any(|field| field.values.is_empty())
+    }
+
+    /// Returns an iterator lazily yielding all combinations of the values
+    #[must_use]
+    pub fn iter(&self) -> TemplateIter<'_> {
+        TemplateIter {
+            template: self,
+            indices: vec![0; self.fields.len()],
+            done: self.is_empty(),
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a Template {
+    type Item = Result<EtherMut>;
+    type IntoIter = TemplateIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Iterator over the packets of a `Template`
+#[derive(Debug, Clone)]
+pub struct TemplateIter<'a> {
+    template: &'a Template,
+    /// Index into the values of each field
+    indices: Vec<u64>,
+    done: bool,
+}
+
+impl Iterator for TemplateIter<'_> {
+    type Item = Result<EtherMut>;
+
+    /// Returns the next combination of the values or the first error setting one of them,
+    /// e.g. a field removed by the value of another field
+    fn next(&mut self) -> Option<Result<EtherMut>> {
+        if self.done {
+            return None;
+        }
+        let mut packet = LayerMut::Ether(self.template.base.clone());
+        let result =
+            self.template
+                .fields
+                .iter()
+                .zip(&self.indices)
+                .try_for_each(|(field, index)| {
+                    find_layer(&mut packet, field.layer, field.nth)
+                        .ok_or_else(|| missing_layer(field.layer, field.nth))?
+                        .set_field(&field.name, &field.values.get(*index))
+                });
+
+        // advance the indices like an odometer, the last field varies fastest
+        self.done = true;
+        for (field, index) in self.template.fields.iter().zip(&mut self.indices).rev() {
+            *index += 1;
+            if *index < field.values.len() {
+                self.done = false;
+                break;
+            }
+            *index = 0;
+        }
+
+        match (result, packet) {
+            (Err(err), _) => Some(Err(err)),
+            (Ok(()), LayerMut::Ether(ether)) => Some(Ok(ether)),
+            (Ok(()), _) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LayerMutable;
+
+    fn template(input: &str) -> Template {
+        match LayerMut::parse(input).expect("could not parse") {
+            LayerMut::Ether(ether) => Template::new(ether),
+            _ => panic!("no ether layer"),
+        }
+    }
+
+    fn field(ether: &EtherMut, layer: Layers, name: &str) -> Option<Value> {
+        find_layer(&mut LayerMut::Ether(ether.clone()), layer, 0)?.get_field(name)
+    }
+
+    #[test]
+    fn test_template() {
+        let mut template = template("Ether / Vlan / Ipv4 / Tcp");
+        assert_eq!(template.iter().count(), 1);
+
+        template
+            .set(Layers::Vlan, "id", Values::Range(1..=3))
+            .expect("could not set");
+        template
+            .set(
+                Layers::Ipv4,
+                "dst",
+                Values::Ipv4Net(Ipv4Addr::new(10, 1, 2, 3), 31),
+            )
+            .expect("could not set");
+        template
+            .set(Layers::Tcp, "flags", Values::Flags(u64::MAX))
+            .expect("could not set");
+        template
+            .set(
+                Layers::Ether,
+                "src",
+                Values::List(vec![Value::Mac(pnet::util::MacAddr::zero())]),
+            )
+            .expect("could not set");
+        assert_eq!(template.len(), Some(3 * 2 * 256));
+
+        let packets: Vec<EtherMut> = template
+            .iter()
+            .collect::<Result<_>>()
+            .expect("could not expand");
+        assert_eq!(packets.len(), 3 * 2 * 256);
+        assert_eq!(field(&packets[0], Layers::Vlan, "id"), Some(Value::Int(1)));
+        assert_eq!(
+            field(&packets[0], Layers::Ipv4, "dst"),
+            Some(Value::Ipv4(Ipv4Addr::new(10, 1, 2, 2)))
+        );
+        assert_eq!(
+            field(&packets[1], Layers::Tcp, "flags"),
+            Some(Value::Int(1))
+        );
+        assert_eq!(
+            field(&packets[255], Layers::Tcp, "flags"),
+            Some(Value::Int(255))
+        );
+        assert_eq!(
+            field(&packets[256], Layers::Ipv4, "dst"),
+            Some(Value::Ipv4(Ipv4Addr::new(10, 1, 2, 3)))
+        );
+        assert_eq!(
+            field(&packets[1535], Layers::Vlan, "id"),
+            Some(Value::Int(3))
+        );
+        assert!(packets.into_iter().all(|ether| ether.build().is_ok()));
+
+        // later values replace earlier ones
+        template
+            .set(Layers::Tcp, "flags", Values::Flags(0b1010))
+            .expect("could not set");
+        let flags: Vec<Option<Value>> = template
+            .iter()
+            .take(4)
+            .map(|ether| field(&ether.expect("could not expand"), Layers::Tcp, "flags"))
+            .collect();
+        assert_eq!(flags, [0, 2, 8, 10].map(|flags| Some(Value::Int(flags))));
+
+        template
+            .set(Layers::Tcp, "dport", Values::List(vec![]))
+            .expect("could not set");
+        assert!(template.is_empty());
+        assert!(template.iter().next().is_none());
+    }
+
+    #[test]
+    fn test_template_nth() {
+        let mut template = template("Ether / Vlan(id: 1) / Vlan(id: 2) / Ipv6 / Udp");
+        template
+            .set_nth(Layers::Vlan, 1, "id", Values::Range(10..=11))
+            .expect("could not set");
+        template
+            .set(
+                Layers::Ipv6,
+                "dst",
+                Values::Ipv6Net("2001:db8::ff".parse().expect("address"), 126),
+            )
+            .expect("could not set");
+        assert_eq!(template.len(), Some(2 * 4));
+
+        let packets: Vec<EtherMut> = template
+            .iter()
+            .collect::<Result<_>>()
+            .expect("could not expand");
+        let inner = |ether: &EtherMut| {
+            find_layer(&mut LayerMut::Ether(ether.clone()), Layers::Vlan, 1)?.get_field("id")
+        };
+        assert_eq!(field(&packets[0], Layers::Vlan, "id"), Some(Value::Int(1)));
+        assert_eq!(inner(&packets[0]), Some(Value::Int(10)));
+        assert_eq!(inner(&packets[7]), Some(Value::Int(11)));
+        assert_eq!(
+            field(&packets[0], Layers::Ipv6, "dst"),
+            Some(Value::Ipv6("2001:db8::fc".parse().expect("address")))
+        );
+        assert_eq!(
+            field(&packets[3], Layers::Ipv6, "dst"),
+            Some(Value::Ipv6("2001:db8::ff".parse().expect("address")))
+        );
+
+        assert!(
+            template
+                .set_nth(Layers::Vlan, 2, "id", Values::Range(1..=2))
+                .is_err()
+        );
+        assert!(
+            template
+                .set(
+                    Layers::Ipv6,
+                    "src",
+                    Values::Ipv6Net(Ipv6Addr::LOCALHOST, 129)
+                )
+                .is_err()
+        );
+        assert_eq!(Values::Ipv6Net(Ipv6Addr::UNSPECIFIED, 32).len(), u64::MAX);
+    }
+
+    #[test]
+    fn test_template_errors() {
+        let mut template = template("Ether / Ipv4 / Udp");
+        assert!(
+            template
+                .set(Layers::Tcp, "dport", Values::Range(1..=2))
+                .is_err()
+        );
+        assert!(
+            template
+                .set(Layers::Udp, "foo", Values::Range(1..=2))
+                .is_err()
+        );
+        assert!(
+            template
+                .set(Layers::Udp, "dport", Values::Range(1..=65536))
+                .is_err()
+        );
+        assert!(
+            template
+                .set(
+                    Layers::Ipv4,
+                    "dst",
+                    Values::Ipv4Net(Ipv4Addr::LOCALHOST, 33)
+                )
+                .is_err()
+        );
+        assert!(
+            template
+                .set(
+                    Layers::Ipv4,
+                    "ttl",
+                    Values::List(vec![Value::Int(1), Value::Int(256)])
+                )
+                .is_err()
+        );
+        assert!(
+            template
+                .set(Layers::Ipv4, "src", Values::Range(1..=2))
+                .is_err()
+        );
+        assert_eq!(template.len(), Some(1));
+    }
+
+    #[test]
+    fn test_template_iter_errors() {
+        // the timestamps only exist while the type is a timestamp request
+        let mut template = template("Ether / Ipv4 / Icmp(type: 13)");
+        template
+            .set(
+                Layers::Icmp,
+                "type",
+                Values::List(vec![Value::Int(13), Value::Int(0)]),
+            )
+            .expect("could not set");
+        template
+            .set(Layers::Icmp, "ts_tx", Values::Range(1..=2))
+            .expect("could not set");
+        let results: Vec<bool> = template.iter().map(|ether| ether.is_ok()).collect();
+        assert_eq!(results, [true, true, false, false]);
+    }
+}
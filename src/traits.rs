@@ -3,3 +3,14 @@ pub trait PacketShow {
     /// Returns a string representation of the packet
     fn show(&self) -> String;
 }
+
+/// Relation between a request and its response, e.g. a TCP SYN-ACK answers a SYN
+pub trait Answers {
+    /// Returns true if `self` is a response to the `request`
+    fn answers(&self, request: &Self) -> bool;
+
+    /// Returns a hash key which is equal for a request and the responses answering it. Packets
+    /// with different keys never answer each other, so responses can be matched to requests
+    /// with a hash map, see `answers::match_responses`.
+    fn hashret(&self) -> u64;
+}
@@ -0,0 +1,631 @@
+//! Validation of the checksums and lengths of received packets.
+//!
+//! Parsing accepts packets with wrong checksums or inconsistent lengths, so the layers can be
+//! inspected anyway. `validate` walks the layers of a packet and reports each problem with the
+//! layer and its offset, e.g. to check that a device under test emits well-formed frames.
+//!
+//! ```
+//! use pnet_layers::validate::Problem;
+//! use pnet_layers::{LayerMut, Layers};
+//!
+//! let mut bytes = LayerMut::parse("Ether / Ipv4 / Udp(dport: 53)")?.build()?;
+//! assert!(LayerMut::from_buf(Layers::Ether, bytes.clone())?.validate().is_empty());
+//!
+//! bytes[24] ^= 0xff;
+//! let problems = LayerMut::from_buf(Layers::Ether, bytes)?.validate();
+//! assert!(matches!(problems[..], [Problem::BadChecksum { layer: Layers::Ipv4, offset: 14, .. }]));
+//! # Ok::<(), pnet_layers::Error>(())
+//! ```
+
+use crate::layers::ipv6_ext;
+use crate::registry::{self, Binding};
+use crate::{Layer, LayerMut, Layers};
+use pnet::packet::ip::{IpNextHeaderProtocol, IpNextHeaderProtocols};
+use pnet::packet::util;
+use std::fmt::Display;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// A problem of a layer found by `validate`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    /// The checksum does not match the content of the layer
+    BadChecksum {
+        /// The layer with the checksum
+        layer: Layers,
+        /// Offset of the layer from the start of the packet
+        offset: usize,
+        /// The checksum of the packet
+        found: u16,
+        /// The correct checksum
+        expected: u16,
+    },
+    /// A length field does not match the length of the layer
+    LengthMismatch {
+        /// The layer with the length field
+        layer: Layers,
+        /// Offset of the layer from the start of the packet
+        offset: usize,
+        /// Name of the length field
+        field: &'static str,
+        /// The value of the length field in bytes
+        found: usize,
+        /// The actual length in bytes
+        actual: usize,
+    },
+    /// The packet ends before the end of the header
+    Truncated {
+        /// The truncated layer
+        layer: Layers,
+        /// Offset of the layer from the start of the packet
+        offset: usize,
+        /// The number of bytes left for the header
+        len: usize,
+        /// The number of bytes needed for the header
+        needed: usize,
+    },
+    /// Bits which must be zero are set
+    ReservedBits {
+        /// The layer with the reserved bits
+        layer: Layers,
+        /// Offset of the layer from the start of the packet
+        offset: usize,
+        /// Name of the field with the reserved bits
+        field: &'static str,
+        /// Value of the field
+        value: u16,
+    },
+    /// A header length field is smaller than the minimum header
+    InvalidHeaderLength {
+        /// The layer with the header length field
+        layer: Layers,
+        /// Offset of the layer from the start of the packet
+        offset: usize,
+        /// Name of the header length field
+        field: &'static str,
+        /// Value of the field
+        value: u8,
+    },
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::BadChecksum {
+                layer,
+                offset,
+                found,
+                expected,
+            } => write!(
+                f,
+                "{layer:?} at {offset}: bad checksum {found:#06x}, expected {expected:#06x}"
+            ),
+            Self::LengthMismatch {
+                layer,
+                offset,
+                field,
+                found,
+                actual,
+            } => write!(
+                f,
+                "{layer:?} at {offset}: `{field}` is {found} but the length is {actual}"
+            ),
+            Self::Truncated {
+                layer,
+                offset,
+                len,
+                needed,
+            } => write!(
+                f,
+                "{layer:?} at {offset}: truncated to {len} bytes, the header needs {needed}"
+            ),
+            Self::ReservedBits {
+                layer,
+                offset,
+                field,
+                value,
+            } => write!(
+                f,
+                "{layer:?} at {offset}: reserved bits set in `{field}` ({value:#x})"
+            ),
+            Self::InvalidHeaderLength {
+                layer,
+                offset,
+                field,
+                value,
+            } => write!(
+                f,
+                "{layer:?} at {offset}: invalid header length `{field}` {value}"
+            ),
+        }
+    }
+}
+
+/// A layer which is validated, reaching from `start` to `end` of the packet
+#[derive(Clone, Copy)]
+struct Segment {
+    kind: Layers,
+    start: usize,
+    end: usize,
+    /// Source and destination address of the IP layer below
+    addrs: Option<(IpAddr, IpAddr)>,
+}
+
+impl Segment {
+    fn upper(self, kind: Layers, start: usize) -> Self {
+        Self {
+            kind,
+            start,
+            ..self
+        }
+    }
+}
+
+fn be16(data: &[u8], i: usize) -> u16 {
+    u16::from_be_bytes([data[i], data[i + 1]])
+}
+
+/// Returns the layer of an IP protocol number or IPv6 next header
+fn protocol_layer(protocol: u8) -> Option<Layers> {
+    registry::builtin(Binding::IpProto(protocol))
+}
+
+struct Validator<'a> {
+    buf: &'a [u8],
+    problems: Vec<Problem>,
+}
+
+impl<'a> Validator<'a> {
+    /// Returns the bytes of the segment or reports it as truncated if it is shorter than
+    /// `needed`
+    fn data(&mut self, segment: Segment, needed: usize) -> Option<&'a [u8]> {
+        let len = segment.end - segment.start;
+        if len < needed {
+            self.problems.push(Problem::Truncated {
+                layer: segment.kind,
+                offset: segment.start,
+                len,
+                needed,
+            });
+            return None;
+        }
+        Some(&self.buf[segment.start..segment.end])
+    }
+
+    fn check_sum(&mut self, segment: Segment, found: u16, expected: u16) {
+        if found != expected {
+            self.problems.push(Problem::BadChecksum {
+                layer: segment.kind,
+                offset: segment.start,
+                found,
+                expected,
+            });
+        }
+    }
+
+    fn length(&mut self, segment: Segment, field: &'static str, found: usize, actual: usize) {
+        if found != actual {
+            self.problems.push(Problem::LengthMismatch {
+                layer: segment.kind,
+                offset: segment.start,
+                field,
+                found,
+                actual,
+            });
+        }
+    }
+
+    fn ether(&mut self, segment: Segment, header_len: usize) -> Option<Segment> {
+        let data = self.data(segment, header_len)?;
+        let kind = registry::builtin(Binding::EtherType(be16(data, header_len - 2)))?;
+        Some(segment.upper(kind, segment.start + header_len))
+    }
+
+    fn arp(&mut self, segment: Segment) -> Option<Segment> {
+        let data = self.data(segment, 8)?;
+        let needed = 8 + 2 * usize::from(data[4]) + 2 * usize::from(data[5]);
+        self.data(segment, needed)?;
+        None
+    }
+
+    fn ipv4(&mut self, segment: Segment) -> Option<Segment> {
+        let data = self.data(segment, 20)?;
+        let ihl = data[0] & 0xf;
+        let header_len = usize::from(ihl) * 4;
+        let (total_len, flags, protocol) = (usize::from(be16(data, 2)), be16(data, 6), data[9]);
+        let src = Ipv4Addr::new(data[12], data[13], data[14], data[15]);
+        let dst = Ipv4Addr::new(data[16], data[17], data[18], data[19]);
+        if ihl < 5 {
+            self.problems.push(Problem::InvalidHeaderLength {
+                layer: Layers::Ipv4,
+                offset: segment.start,
+                field: "ihl",
+                value: ihl,
+            });
+            return None;
+        }
+        if flags & 0x8000 != 0 {
+            self.problems.push(Problem::ReservedBits {
+                layer: Layers::Ipv4,
+                offset: segment.start,
+                field: "flags",
+                value: flags >> 13,
+            });
+        }
+        let data = self.data(segment, header_len)?;
+        let (found, expected) = (be16(data, 10), util::checksum(&data[..header_len], 5));
+        let len = data.len();
+        self.check_sum(segment, found, expected);
+        // a shorter total length leaves room for the padding of the link layer
+        if total_len < header_len || total_len > len {
+            self.length(segment, "len", total_len, len);
+            return None;
+        }
+        // the transport header of fragments can not be validated
+        if flags & 0x3fff != 0 {
+            return None;
+        }
+        Some(Segment {
+            kind: protocol_layer(protocol)?,
+            start: segment.start + header_len,
+            end: segment.start + total_len,
+            addrs: Some((IpAddr::V4(src), IpAddr::V4(dst))),
+        })
+    }
+
+    fn ipv6(&mut self, segment: Segment) -> Option<Segment> {
+        let data = self.data(segment, 40)?;
+        let payload_len = usize::from(be16(data, 4));
+        let next_header = data[6];
+        let src: [u8; 16] = data[8..24].try_into().ok()?;
+        let dst: [u8; 16] = data[24..40].try_into().ok()?;
+        if 40 + payload_len > data.len() {
+            self.length(segment, "plen", payload_len, data.len() - 40);
+            return None;
+        }
+        Some(Segment {
+            kind: protocol_layer(next_header)?,
+            start: segment.start + 40,
+            end: segment.start + 40 + payload_len,
+            addrs: Some((
+                IpAddr::V6(Ipv6Addr::from(src)),
+                IpAddr::V6(Ipv6Addr::from(dst)),
+            )),
+        })
+    }
+
+    fn ipv6_ext(&mut self, segment: Segment) -> Option<Segment> {
+        let data = self.data(segment, 8)?;
+        let (next_header, len) = (data[0], usize::from(data[1]));
+        let header_len = match segment.kind {
+            Layers::Fragment => {
+                // the upper layer of fragments can not be validated
+                if be16(data, 2) & 0xfff9 != 0 {
+                    return None;
+                }
+                8
+            }
+            Layers::Ah => (len + 2) * 4,
+            _ => (len + 1) * 8,
+        };
+        let header = self.data(segment, header_len)?;
+        // the pseudo header of the upper layer uses the final destination of a routing header
+        let addrs = match (segment.kind, segment.addrs) {
+            (Layers::Routing, Some((src, dst))) => Some((
+                src,
+                ipv6_ext::final_destination(&header[..header_len]).map_or(dst, IpAddr::V6),
+            )),
+            (_, addrs) => addrs,
+        };
+        Some(Segment {
+            addrs,
+            ..segment.upper(protocol_layer(next_header)?, segment.start + header_len)
+        })
+    }
+
+    /// Validates the checksum including the pseudo header of the IP layer
+    fn transport_checksum(
+        &mut self,
+        segment: Segment,
+        skipword: usize,
+        protocol: IpNextHeaderProtocol,
+    ) {
+        let data = &self.buf[segment.start..segment.end];
+        let expected = match segment.addrs {
+            Some((IpAddr::V4(src), IpAddr::V4(dst))) => {
+                util::ipv4_checksum(data, skipword, &[], &src, &dst, protocol)
+            }
+            Some((IpAddr::V6(src), IpAddr::V6(dst))) => {
+                util::ipv6_checksum(data, skipword, &[], &src, &dst, protocol)
+            }
+            _ => return,
+        };
+        self.check_sum(segment, be16(data, skipword * 2), expected);
+    }
+
+    fn udp(&mut self, segment: Segment) {
+        let Some(data) = self.data(segment, 8) else {
+            return;
+        };
+        let (len, checksum) = (data.len(), be16(data, 6));
+        self.length(segment, "len", usize::from(be16(data, 4)), len);
+        // the checksum is optional for IPv4
+        if checksum != 0 || !matches!(segment.addrs, Some((IpAddr::V4(_), _))) {
+            self.transport_checksum(segment, 3, IpNextHeaderProtocols::Udp);
+        }
+    }
+
+    fn tcp(&mut self, segment: Segment) {
+        let Some(data) = self.data(segment, 20) else {
+            return;
+        };
+        let data_offset = data[12] >> 4;
+        let reserved = data[12] & 0xf;
+        if reserved != 0 {
+            self.problems.push(Problem::ReservedBits {
+                layer: Layers::Tcp,
+                offset: segment.start,
+                field: "reserved",
+                value: u16::from(reserved),
+            });
+        }
+        if data_offset < 5 {
+            self.problems.push(Problem::InvalidHeaderLength {
+                layer: Layers::Tcp,
+                offset: segment.start,
+                field: "dataofs",
+                value: data_offset,
+            });
+            return;
+        }
+        if self.data(segment, usize::from(data_offset) * 4).is_some() {
+            self.transport_checksum(segment, 8, IpNextHeaderProtocols::Tcp);
+        }
+    }
+
+    fn icmp(&mut self, segment: Segment) {
+        if let Some(data) = self.data(segment, 8) {
+            let (found, expected) = (be16(data, 2), util::checksum(data, 1));
+            self.check_sum(segment, found, expected);
+        }
+    }
+
+    fn icmpv6(&mut self, segment: Segment) {
+        if self.data(segment, 4).is_some() {
+            self.transport_checksum(segment, 1, IpNextHeaderProtocols::Icmpv6);
+        }
+    }
+}
+
+/// Validates the packet in `buf` starting with a layer of the given type
+fn validate(kind: Layers, buf: &[u8]) -> Vec<Problem> {
+    let mut validator = Validator {
+        buf,
+        problems: vec![],
+    };
+    let mut segment = Some(Segment {
+        kind,
+        start: 0,
+        end: buf.len(),
+        addrs: None,
+    });
+    while let Some(current) = segment {
+        segment = match current.kind {
+            Layers::Ether => validator.ether(current, 14),
+            Layers::Vlan => validator.ether(current, 4),
+            Layers::Arp => validator.arp(current),
+            Layers::Ipv4 => validator.ipv4(current),
+            Layers::Ipv6 => validator.ipv6(current),
+            Layers::HopByHop
+            | Layers::Routing
+            | Layers::Fragment
+            | Layers::DestOpts
+            | Layers::Ah => validator.ipv6_ext(current),
+            Layers::Udp => {
+                validator.udp(current);
+                None
+            }
+            Layers::Tcp => {
+                validator.tcp(current);
+                None
+            }
+            Layers::Icmp => {
+                validator.icmp(current);
+                None
+            }
+            Layers::Icmpv6 => {
+                validator.icmpv6(current);
+                None
+            }
+            Layers::Payload | Layers::Raw | Layers::Trailer | Layers::Custom => None,
+        };
+    }
+    validator.problems
+}
+
+impl LayerMut {
+    /// Validates the checksums and lengths of the layer and all upper layers as they are,
+    /// e.g. after parsing a received packet. Crafted layers should be built first, as the
+    /// checksums and lengths are only filled in by `build`.
+    #[must_use]
+    pub fn validate(&self) -> Vec<Problem> {
+        let mut buf = vec![];
+        let mut trailers = vec![];
+        let mut layer = Some(self);
+        while let Some(current) = layer {
+            buf.extend_from_slice(current.header());
+            trailers.extend(current.trailer());
+            layer = current.upper_layer();
+        }
+        // the trailer of an upper layer is written in front of the ones of the lower layers
+        for trailer in trailers.into_iter().rev() {
+            buf.extend_from_slice(&trailer.buf);
+        }
+        validate(self.kind(), &buf)
+    }
+}
+
+impl Layer<'_> {
+    /// Validates the checksums and lengths of the layer and all upper layers without copying
+    /// the packet, see `LayerMut::validate`
+    #[must_use]
+    pub fn validate(&self) -> Vec<Problem> {
+        validate(self.kind(), self.get_buf())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::overrides::{Override, Overrides};
+    use crate::{Ether, LayerImmutable, Value};
+
+    fn build(input: &str, overrides: &[(Layers, &str, Override)]) -> Vec<u8> {
+        let mut set = Overrides::new();
+        for (layer, field, value) in overrides {
+            set.set(*layer, field, value.clone())
+                .expect("could not set");
+        }
+        LayerMut::parse(input)
+            .and_then(|packet| packet.build_with(&set))
+            .expect("could not build")
+    }
+
+    fn problems(bytes: &[u8]) -> Vec<Problem> {
+        let problems = Layer::Ether(Ether::new(bytes)).validate();
+        if let Ok(layer) = LayerMut::from_buf(Layers::Ether, bytes.to_vec()) {
+            assert_eq!(layer.validate(), problems);
+        }
+        problems
+    }
+
+    #[test]
+    fn test_validate_valid() {
+        for input in [
+            r#"Ether / Vlan / Ipv4 / Tcp(flags: S) / "payload""#,
+            "Ether / Ipv4 / Udp",
+            "Ether / Ipv4 / Icmp(type: 8)",
+            "Ether / Ipv6 / HopByHop / Udp",
+            // the checksum uses the final destination of the segment routing header
+            "Ether / Ipv6(dst: 2001:db8::2) / Routing(type: 4, segleft: 1, lastentry: 1, \
+                data: 0x20010db800000000000000000000000320010db8000000000000000000000002) / Udp",
+            "Ether / Ipv6 / Icmpv6(type: 128, body: 0x00010002)",
+            "Ether / Arp",
+        ] {
+            assert_eq!(problems(&build(input, &[])), [], "{input}");
+        }
+    }
+
+    #[test]
+    fn test_validate_problems() {
+        let bytes = build(
+            "Ether / Ipv4 / Udp",
+            &[(Layers::Ipv4, "chksum", Override::Wrong)],
+        );
+        let expected = be16(&bytes, 24) ^ 1;
+        assert_eq!(
+            problems(&bytes),
+            [Problem::BadChecksum {
+                layer: Layers::Ipv4,
+                offset: 14,
+                found: expected ^ 1,
+                expected,
+            }]
+        );
+
+        let bytes = build(
+            "Ether / Ipv4 / Udp",
+            &[(Layers::Udp, "len", Override::Pin(Value::Int(9)))],
+        );
+        assert_eq!(
+            problems(&bytes),
+            [Problem::LengthMismatch {
+                layer: Layers::Udp,
+                offset: 34,
+                field: "len",
+                found: 9,
+                actual: 8,
+            }]
+        );
+
+        let bytes = build(
+            "Ether / Ipv6 / Tcp",
+            &[(Layers::Ipv6, "plen", Override::Pin(Value::Int(60)))],
+        );
+        assert!(matches!(
+            problems(&bytes)[..],
+            [Problem::LengthMismatch {
+                layer: Layers::Ipv6,
+                found: 60,
+                actual: 20,
+                ..
+            }]
+        ));
+
+        let bytes = build(
+            "Ether / Ipv4(flags: R) / Tcp(reserved: 1)",
+            &[(Layers::Tcp, "chksum", Override::Wrong)],
+        );
+        let found = problems(&bytes);
+        assert_eq!(found.len(), 3);
+        assert!(matches!(
+            found[0],
+            Problem::ReservedBits {
+                layer: Layers::Ipv4,
+                value: 4,
+                ..
+            }
+        ));
+        assert!(matches!(
+            found[1],
+            Problem::ReservedBits {
+                layer: Layers::Tcp,
+                value: 1,
+                ..
+            }
+        ));
+        assert!(matches!(
+            found[2],
+            Problem::BadChecksum {
+                layer: Layers::Tcp,
+                offset: 34,
+                ..
+            }
+        ));
+        assert_eq!(
+            found[1].to_string(),
+            "Tcp at 34: reserved bits set in `reserved` (0x1)"
+        );
+
+        let bytes = build(
+            "Ether / Ipv4 / Tcp",
+            &[(Layers::Tcp, "dataofs", Override::Pin(Value::Int(4)))],
+        );
+        assert!(matches!(
+            problems(&bytes)[..],
+            [Problem::InvalidHeaderLength {
+                layer: Layers::Tcp,
+                value: 4,
+                ..
+            }]
+        ));
+    }
+
+    #[test]
+    fn test_validate_truncated() {
+        // the total length leaves only 10 bytes for the TCP header
+        let mut bytes = build("Ether / Ipv4 / Tcp", &[]);
+        bytes.truncate(44);
+        bytes[17] = 30;
+        let checksum = util::checksum(&bytes[14..34], 5);
+        bytes[24..26].copy_from_slice(&checksum.to_be_bytes());
+        assert_eq!(
+            Layer::Ether(Ether::new(&bytes)).validate(),
+            [Problem::Truncated {
+                layer: Layers::Tcp,
+                offset: 34,
+                len: 10,
+                needed: 20,
+            }]
+        );
+    }
+}
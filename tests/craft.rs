@@ -1,12 +1,167 @@
-use pnet_layers::{LayerMut, LayerMutable};
+use pnet::util::MacAddr;
+use pnet_layers::{Error, EtherMut, LayerMut, LayerMutable, Layers, Value};
+use std::net::Ipv4Addr;
 
 #[test]
 fn test_craft() {
     let crafted = LayerMut::parse("Vlan(id: 10)").unwrap();
     assert!(matches!(crafted, LayerMut::Vlan(_)));
     if let LayerMut::Vlan(mut vlan) = crafted
-        && let Some(vlan) = vlan.modify()
+        && let Ok(vlan) = vlan.modify()
     {
         assert_eq!(vlan.get_vlan_identifier(), 10);
     }
 }
+
+#[test]
+fn test_craft_unknown_layer() {
+    let err = LayerMut::parse("Vlan(id: 10) / Foo(bar: 1)").unwrap_err();
+    assert!(matches!(err, Error::Parse { column: 16, .. }));
+}
+
+#[test]
+fn test_craft_stack() {
+    let crafted = LayerMut::parse(
+        r#"Ether(dst: ff:ff:ff:ff:ff:ff) / IPv4(src: 10.0.0.1, dst = 10.0.0.2, ttl: 0x10, flags: D)
+            / Tcp(sport: 1234, dport: 80, flags: SA) / "GET / HTTP/1.1\r\n""#,
+    )
+    .unwrap();
+    assert_eq!(
+        crafted.get_field("dst"),
+        Some(Value::Mac(MacAddr::broadcast()))
+    );
+    let bytes = crafted.build().unwrap();
+    let ether = EtherMut::from_buf(bytes).unwrap();
+    let mut ether = LayerMut::Ether(ether);
+    assert!(ether.to_string().contains("Tcp"));
+
+    let LayerMut::Ether(eth) = &mut ether else {
+        unreachable!()
+    };
+    let ipv4 = eth.get_layer(&Layers::Ipv4).unwrap();
+    assert_eq!(
+        ipv4.get_field("src"),
+        Some(Value::Ipv4(Ipv4Addr::new(10, 0, 0, 1)))
+    );
+    assert_eq!(ipv4.get_field("ttl"), Some(Value::Int(16)));
+    assert_eq!(ipv4.get_field("flags"), Some(Value::Int(2)));
+    let tcp = eth.get_layer(&Layers::Tcp).unwrap();
+    assert_eq!(tcp.get_field("flags"), Some(Value::Int(0x12)));
+    let payload = eth.get_layer(&Layers::Payload).unwrap();
+    assert_eq!(
+        payload.get_field("load"),
+        Some(Value::Bytes(b"GET / HTTP/1.1\r\n".to_vec()))
+    );
+}
+
+#[test]
+fn test_craft_fields() {
+    let crafted = LayerMut::parse(
+        "Ipv6(src: fe80::1, dst: ff02::1, hlim: 255) / Udp(dport: 53) / Payload(load: 0x00ff)",
+    )
+    .unwrap();
+    assert_eq!(
+        crafted.get_field("src"),
+        Some(Value::Ipv6("fe80::1".parse().unwrap()))
+    );
+    let bytes = crafted.build().unwrap();
+    assert_eq!(bytes.len(), 40 + 8 + 2);
+    assert_eq!(bytes[7], 255);
+    assert_eq!(&bytes[48..], &[0x00, 0xff]);
+
+    // raw options are padded to a multiple of 4 bytes
+    let crafted = LayerMut::parse(r#"Tcp(options: "\x01\x01\x01")"#).unwrap();
+    let bytes = crafted.build().unwrap();
+    assert_eq!(bytes.len(), 24);
+    assert_eq!(bytes[12] >> 4, 6);
+
+    // the type sizes the ICMP header, so the timestamps can be set in any order
+    let crafted = LayerMut::parse("Icmp(type: 13, ts_tx: 5)").unwrap();
+    assert_eq!(crafted.header().len(), 20);
+    assert_eq!(crafted.get_field("ts_tx"), Some(Value::Int(5)));
+    let crafted = LayerMut::parse("Icmp(type: 14, ts_rx: 5, ts_ori: 4)").unwrap();
+    assert_eq!(crafted.get_field("ts_ori"), Some(Value::Int(4)));
+    assert_eq!(crafted.get_field("ts_rx"), Some(Value::Int(5)));
+    assert_eq!(LayerMut::parse("Icmp(type: 8)").unwrap().header().len(), 8);
+}
+
+#[test]
+fn test_craft_errors() {
+    let column = |input| match LayerMut::parse(input) {
+        Err(Error::Parse { column, .. }) => column,
+        other => panic!("unexpected result {other:?}"),
+    };
+    // unknown field
+    assert_eq!(column("Ether / Tcp(foo: 1)"), 13);
+    // invalid value
+    assert_eq!(column("Ipv4(src: 10.0.0.256)"), 11);
+    // value too large for the field
+    assert_eq!(column("Vlan(id: 4096)"), 10);
+    // illegal stacking
+    assert_eq!(column("Udp / Ether"), 7);
+    // missing bracket
+    assert_eq!(column("Udp(sport: 1"), 13);
+    // unterminated string
+    assert_eq!(column(r#"Udp / "abc"#), 7);
+    // invalid flag
+    assert_eq!(column("Tcp(flags: SX)"), 12);
+}
+
+#[test]
+fn test_canonical_round_trip() {
+    for input in [
+        r#"Ether(src: 3c:ce:33:33:33:33) / Vlan(id: 5, prio: 3) / Ipv4(src: 10.0.0.1, dst: 10.0.0.2, ttl: 7, flags: D, options: 0x01010100) / Tcp(sport: 1, dport: 2, flags: PA, options: 0x020405b4) / "a \"quoted\" payload""#,
+        "Ether / Ipv6(src: fe80::1, dst: ff02::1, fl: 12345) / HopByHop / Udp(dport: 53) / Payload(load: 0x00ff)",
+        "Ether / Ipv4 / Icmp(type: 13, id: 1, seq: 2, ts_ori: 3)",
+        "Ether / Arp(op: 2, psrc: 10.0.0.1, hwsrc: 3c:ce:33:33:33:33)",
+    ] {
+        let bytes = LayerMut::parse(input).unwrap().build().unwrap();
+        let packet = LayerMut::Ether(EtherMut::from_buf(bytes.clone()).unwrap());
+        let text = packet.canonical();
+        let parsed = LayerMut::parse(&text).unwrap();
+        assert_eq!(parsed.canonical(), text);
+        assert_eq!(parsed.build().unwrap(), bytes, "{text}");
+    }
+}
+
+#[test]
+fn test_canonical_broken_checksum() {
+    let bytes = LayerMut::parse("Ether / Ipv4 / Udp(dport: 53) / \"query\"")
+        .unwrap()
+        .build()
+        .unwrap();
+    // break the UDP checksum behind the Ethernet, IPv4 and the first UDP fields
+    let mut broken = bytes.clone();
+    broken[14 + 20 + 6] ^= 0xff;
+    let packet = LayerMut::Ether(EtherMut::from_buf(broken).unwrap());
+    let text = packet.canonical();
+    assert!(!text.contains("chksum"), "{text}");
+    assert_eq!(LayerMut::parse(&text).unwrap().build().unwrap(), bytes);
+}
+
+#[test]
+fn test_canonical_icmp_error() {
+    for probe in [
+        "Ipv4(src: 10.0.0.1, dst: 10.0.0.2) / Tcp(sport: 1234, dport: 80, flags: S)",
+        "Ipv4(src: 10.0.0.1, dst: 10.0.0.2) / Udp(sport: 1234, dport: 53) / \"query\"",
+    ] {
+        let probe = LayerMut::parse(probe).unwrap().build().unwrap();
+        // the error quotes the IPv4 header and the first 8 bytes of its payload
+        let quote: String = probe[..28].iter().map(|b| format!("{b:02x}")).collect();
+        let bytes = LayerMut::parse(&format!(
+            "Ether / Ipv4(src: 10.0.0.2, dst: 10.0.0.1) / Icmp(type: 3, code: 3) / Payload(load: 0x{quote})"
+        ))
+        .unwrap()
+        .build()
+        .unwrap();
+        let packet = LayerMut::Ether(EtherMut::from_buf(bytes.clone()).unwrap());
+        let kinds: Vec<Layers> = packet.layers().map(LayerMut::kind).collect();
+        assert_eq!(kinds[..3], [Layers::Ipv4, Layers::Icmp, Layers::Ipv4]);
+        let text = packet.canonical();
+        // the total length of the quoted datagram is kept
+        assert!(text.contains(&format!("len: {}", probe.len())), "{text}");
+        let parsed = LayerMut::parse(&text).unwrap();
+        assert_eq!(parsed.canonical(), text);
+        assert_eq!(parsed.build().unwrap(), bytes, "{text}");
+    }
+}
@@ -0,0 +1,197 @@
+use pnet::util::MacAddr;
+use pnet_layers::registry::{self, Binding};
+use pnet_layers::{
+    EtherMut, Layer, LayerImmutable, LayerMut, LayerMutable, Layers, PayloadMut, Value,
+};
+use pnet_macros::packet;
+use pnet_macros_support::types::{u4, u16be};
+
+/// Shim header with options announcing an IP protocol
+#[packet]
+#[derive(Layer)]
+#[layer(
+    next = ip_proto(next),
+    length = len,
+    checksum = checksum,
+    bind(udp_port = 6081, ether_type = 0x88b8)
+)]
+pub struct Shim {
+    pub version: u4,
+    pub words: u4,
+    pub next: u8,
+    pub len: u16be,
+    pub checksum: u16be,
+    #[length_fn = "shim_options_length"]
+    pub options: Vec<u8>,
+    #[payload]
+    pub payload: Vec<u8>,
+}
+
+fn shim_options_length(shim: &ShimPacket) -> usize {
+    usize::from(shim.get_words()) * 4
+}
+
+/// Beacon without a next protocol, the payload is kept as `Payload`
+#[packet]
+#[derive(Layer)]
+#[layer(name = "Beacon", payload_length = size, bind(ether_type = 0x88b9))]
+pub struct Announce {
+    #[construct_with(u8, u8, u8, u8, u8, u8)]
+    pub mac: MacAddr,
+    pub size: u8,
+    #[payload]
+    pub payload: Vec<u8>,
+}
+
+fn kinds(packet: &LayerMut) -> Vec<Layers> {
+    std::iter::successors(Some(packet), |layer| layer.upper_layer())
+        .map(LayerMut::kind)
+        .collect()
+}
+
+#[test]
+fn test_derive_fields() {
+    assert_eq!(ShimLayer::NAME, "Shim");
+    let names: Vec<_> = ShimLayer::FIELDS.iter().map(|field| field.name).collect();
+    assert_eq!(
+        names,
+        ["version", "words", "next", "len", "checksum", "options"]
+    );
+    assert_eq!(ShimLayer::FIELDS[3].offset, 16);
+    assert_eq!(AnnounceLayer::NAME, "Beacon");
+    assert_eq!(
+        AnnounceLayer::FIELDS[0],
+        pnet_layers::Field::new("mac", 0, pnet_layers::FieldKind::Mac)
+    );
+}
+
+#[test]
+fn test_derive_round_trip() {
+    ShimLayer::register();
+    let packet = LayerMut::parse(
+        "Ether / Ipv4 / Udp(dport: 6081) / Shim(version: 1, words: 1, options: 0x01020304) / Ipv4 / Icmp",
+    )
+    .unwrap();
+    let bytes = packet.build().unwrap();
+
+    let ether = LayerMut::Ether(EtherMut::from_buf(bytes.clone()).unwrap());
+    assert_eq!(
+        kinds(&ether),
+        [
+            Layers::Ether,
+            Layers::Ipv4,
+            Layers::Udp,
+            Layers::Custom,
+            Layers::Ipv4,
+            Layers::Icmp
+        ]
+    );
+    let shim = ether
+        .upper_layer()
+        .and_then(LayerMut::upper_layer)
+        .and_then(LayerMut::upper_layer)
+        .unwrap();
+    assert_eq!(shim.header().len(), 10);
+    assert_eq!(shim.get_field("next"), Some(Value::Int(4)));
+    assert_eq!(shim.get_field("len"), Some(Value::Int(10 + 20 + 8)));
+    assert_eq!(
+        shim.get_field("options"),
+        Some(Value::Bytes(vec![1, 2, 3, 4]))
+    );
+    let header = ShimLayer::packet(shim).unwrap();
+    let mut data = shim.clone().build().unwrap();
+    data[4..6].copy_from_slice(&[0, 0]);
+    assert_eq!(
+        header.get_checksum(),
+        pnet::util::checksum(&data, usize::MAX)
+    );
+    assert!(
+        shim.to_string()
+            .starts_with("Shim (version: 1, words: 1, next: 4")
+    );
+
+    assert_eq!(ether.clone().build().unwrap(), bytes);
+    let parsed = LayerMut::parse(&ether.canonical()).unwrap();
+    assert_eq!(parsed.build().unwrap(), bytes);
+    let json = serde_json::to_string(&ether).unwrap();
+    let parsed: LayerMut = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed.build().unwrap(), bytes);
+}
+
+#[test]
+fn test_derive_layer() {
+    AnnounceLayer::register();
+    let mut beacon = AnnounceLayer::new_layer();
+    AnnounceLayer::modify(&mut beacon)
+        .unwrap()
+        .set_mac(MacAddr::broadcast());
+    let LayerMut::Custom(mut custom) = beacon else {
+        unreachable!()
+    };
+    custom
+        .add(LayerMut::Payload(pnet_layers::PayloadMut {
+            buf: vec![7; 3],
+        }))
+        .unwrap();
+    let mut ether = EtherMut::new();
+    ether.add(LayerMut::Custom(custom)).unwrap();
+    let bytes = ether.build().unwrap();
+    assert_eq!(
+        bytes[12..],
+        [0x88, 0xb9, 255, 255, 255, 255, 255, 255, 3, 7, 7, 7]
+    );
+
+    let beacon = AnnounceLayer::from_buf(bytes[14..].to_vec()).unwrap();
+    assert_eq!(
+        beacon.get_field("mac"),
+        Some(Value::Mac(MacAddr::broadcast()))
+    );
+    assert!(matches!(beacon.upper_layer(), Some(LayerMut::Payload(_))));
+    assert!(ShimLayer::packet(&beacon).is_none());
+    assert!(AnnounceLayer::from_buf(vec![0; 6]).is_err());
+
+    assert!(registry::unbind(Binding::EtherType(0x88b9)).is_some());
+    let ether = EtherMut::from_buf(bytes).unwrap();
+    assert!(matches!(
+        ether.upper_layer.as_deref(),
+        Some(LayerMut::Raw(_))
+    ));
+}
+
+#[test]
+fn test_derive_pair() {
+    ShimLayer::register();
+    let mut shim = ShimMut::new();
+    shim.modify().unwrap().set_version(2);
+    shim.add(LayerMut::parse("Ipv4 / Icmp").unwrap()).unwrap();
+    assert_eq!(shim.name(), "Shim");
+    assert!(shim.to_string().starts_with("Shim (version: 2"));
+    let mut ether = EtherMut::from_buf(
+        LayerMut::parse("Ether / Ipv4 / Udp(dport: 6081)")
+            .unwrap()
+            .build()
+            .unwrap(),
+    )
+    .unwrap();
+    ether.add(shim.into()).unwrap();
+    let bytes = ether.build().unwrap();
+
+    // the immutable layer walks the upper layers announced by the header
+    let view = ShimRef::new(&bytes[14 + 20 + 8..]);
+    assert_eq!(view.as_pnet().unwrap().get_next(), 4);
+    let kinds: Vec<Layers> = view.layers().map(|span| span.layer.kind()).collect();
+    assert_eq!(kinds, [Layers::Custom, Layers::Ipv4, Layers::Icmp]);
+    assert!(matches!(view.get_layer(Layers::Icmp), Some(Layer::Icmp(_))));
+
+    let mut shim = view.as_mut().unwrap();
+    assert!(matches!(shim.upper_layer(), Some(LayerMut::Ipv4(_))));
+    shim.set_payload(b"data");
+    assert!(
+        matches!(shim.upper_layer(), Some(LayerMut::Payload(PayloadMut { buf })) if buf == b"data")
+    );
+
+    let layer = LayerMut::from(shim);
+    let shim = ShimMut::try_from(layer).unwrap();
+    assert_eq!(shim.build().unwrap()[6..], *b"data");
+    assert!(ShimMut::try_from(LayerMut::new(Layers::Udp)).is_err());
+}
@@ -1,16 +1,134 @@
-use pnet_layers::EtherMut;
-use pnet_layers::LayerMutable;
+use pnet_layers::{
+    Ether, EtherMut, Ipv4, Ipv4Mut, Layer, LayerImmutable, LayerMut, LayerMutable, Layers,
+    TrailerMut,
+};
 
 #[test]
 fn test_layer_build() {
     let bytes = hex::decode("2222222222221111111111118100800a080045000028487640004006dbe10101010102020202b3ec9076015297b1dda5d28c5010020009c300000000").unwrap();
 
-    println!("{}", bytes.len());
-
-    let ether = EtherMut::from_buf(bytes).unwrap();
+    let ether = EtherMut::from_buf(bytes.clone()).unwrap();
+    assert_eq!(ether.trailer, Some(TrailerMut { buf: vec![0, 0] }));
 
+    // the padding is kept and written again, only the checksums are recalculated
     let builded = ether.build().unwrap();
-
-    let bytes = hex::decode("2222222222221111111111118100800a080045000028487640004006dbe10101010102020202b3ec9076015297b1dda5d28c5010020009c30000").unwrap();
     assert_eq!(builded.len(), bytes.len());
+    assert_eq!(builded[builded.len() - 2..], [0, 0]);
+}
+
+#[test]
+fn test_trailer() {
+    // ARP reply padded with non-zero bytes
+    let mut bytes = hex::decode(
+        "ffffffffffff3cce33333333080600010800060400023cce333333330a0000010000000000000a000002",
+    )
+    .unwrap();
+    let padding = vec![0xaa; 18];
+    bytes.extend_from_slice(&padding);
+
+    let ether = EtherMut::from_buf(bytes.clone()).unwrap();
+    assert_eq!(
+        ether.trailer.as_ref().map(|t| t.buf.clone()),
+        Some(padding.clone())
+    );
+    assert_eq!(
+        ether.upper_layer.as_ref().map(|arp| arp.header().len()),
+        Some(28)
+    );
+    assert!(matches!(
+        Ether::new(&bytes).get_layer(Layers::Trailer),
+        Some(Layer::Trailer(trailer)) if trailer.buf == padding
+    ));
+
+    // the trailer survives the canonical form and serde
+    let packet = LayerMut::Ether(ether);
+    let parsed = LayerMut::parse(&packet.canonical()).unwrap();
+    assert_eq!(
+        parsed.trailer().map(|t| t.buf.clone()),
+        Some(padding.clone())
+    );
+    let json = serde_json::to_string(&packet).unwrap();
+    let parsed: LayerMut = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed.build().unwrap(), bytes);
+    assert_eq!(packet.build().unwrap(), bytes);
+
+    // a trailer can be added to a crafted packet
+    let packet = LayerMut::parse("Ether / Ipv4 / Udp / Trailer(load: 0x0102)").unwrap();
+    let bytes = packet.build().unwrap();
+    assert_eq!(bytes.len(), 14 + 20 + 8 + 2);
+    assert_eq!(bytes[bytes.len() - 2..], [1, 2]);
+    assert!(LayerMut::parse("Ether / Ipv4 / Trailer / Udp").is_ok());
+    assert!(LayerMut::parse("Udp / Trailer").is_err());
+}
+
+#[test]
+fn test_padding() {
+    let LayerMut::Ether(mut ether) = LayerMut::parse("Ether / Ipv4 / Udp").unwrap() else {
+        unreachable!()
+    };
+    assert_eq!(ether.clone().build().unwrap().len(), 42);
+
+    ether.set_padding(true);
+    let bytes = ether.clone().build().unwrap();
+    assert_eq!(bytes.len(), EtherMut::MIN_LEN);
+    assert_eq!(bytes[42..], [0; 18]);
+    let parsed = EtherMut::from_buf(bytes.clone()).unwrap();
+    assert_eq!(parsed.trailer, Some(TrailerMut { buf: vec![0; 18] }));
+
+    // the padding follows the trailer
+    ether.trailer = Some(TrailerMut { buf: vec![0xff] });
+    let bytes = ether.clone().build().unwrap();
+    assert_eq!(bytes.len(), EtherMut::MIN_LEN);
+    assert_eq!(bytes[42], 0xff);
+
+    // long frames are not padded
+    ether
+        .add(LayerMut::Payload(pnet_layers::PayloadMut {
+            buf: vec![1; 100],
+        }))
+        .unwrap();
+    assert_eq!(ether.build().unwrap().len(), 42 + 100 + 1);
+}
+
+#[test]
+fn test_ip_trailer() {
+    // the bytes after the total length of a bare IPv4 packet are its trailer
+    let mut bytes = LayerMut::parse("Ipv4 / Udp / \"abc\"")
+        .unwrap()
+        .build()
+        .unwrap();
+    bytes.extend_from_slice(&[1, 2, 3]);
+    let ipv4 = Ipv4Mut::from_buf(bytes.clone()).unwrap();
+    assert_eq!(ipv4.trailer, Some(TrailerMut { buf: vec![1, 2, 3] }));
+    assert_eq!(
+        ipv4.upper_layer.as_ref().map(|udp| udp.header().len()),
+        Some(8)
+    );
+    let kinds: Vec<Layers> = Ipv4::new(&bytes)
+        .layers()
+        .map(|span| span.layer.kind())
+        .collect();
+    assert_eq!(
+        kinds,
+        [Layers::Ipv4, Layers::Udp, Layers::Payload, Layers::Trailer]
+    );
+
+    // a total length of zero is used by captures with segmentation offloading
+    let mut bytes = LayerMut::parse("Ether / Ipv4 / Udp / \"abc\"")
+        .unwrap()
+        .build()
+        .unwrap();
+    bytes[16..18].copy_from_slice(&[0, 0]);
+    let mut ether = EtherMut::from_buf(bytes.clone()).unwrap();
+    assert_eq!(ether.trailer, None);
+    let Some(LayerMut::Ipv4(ipv4)) = ether.upper_layer.as_deref_mut() else {
+        panic!("no ipv4 layer")
+    };
+    assert_eq!(ipv4.trailer, None);
+    assert_eq!(
+        ipv4.get_layer(&Layers::Payload)
+            .map(|payload| payload.header().to_vec()),
+        Some(b"abc".to_vec())
+    );
+    assert_eq!(Ether::new(&bytes).layers().count(), 4);
 }
@@ -0,0 +1,178 @@
+use pnet_layers::registry::{self, Binding, Protocol};
+use pnet_layers::{
+    Ether, EtherMut, Field, Ipv6, Layer, LayerImmutable, LayerIter, LayerMut, LayerMutable, Layers,
+    Result,
+};
+use std::sync::Arc;
+
+fn mut_kinds(bytes: &[u8]) -> Vec<Layers> {
+    let ether = LayerMut::Ether(EtherMut::from_buf(bytes.to_vec()).unwrap());
+    let mut kinds: Vec<Layers> = std::iter::successors(Some(&ether), |l| l.upper_layer())
+        .map(LayerMut::kind)
+        .collect();
+    if ether.trailer().is_some() {
+        kinds.push(Layers::Trailer);
+    }
+    kinds
+}
+
+#[test]
+fn test_iter_matches_from_buf() {
+    for stack in [
+        "Ether / Vlan / Vlan / Ipv4 / Udp / \"abc\"",
+        "Ether / Ipv4 / Tcp(options: 0x020405b4) / \"data\"",
+        "Ether / Ipv4 / Icmp(type: 3) / Ipv4 / Udp",
+        "Ether / Ipv6 / HopByHop / Routing / Fragment / DestOpts / Udp / \"abc\"",
+        "Ether / Ipv6 / Ah / Tcp",
+        "Ether / Ipv6 / Fragment(m: 1) / \"first fragment\"",
+        "Ether / Ipv4(proto: 47) / Raw(load: 0x00000800)",
+        "Ether / Arp / Trailer(load: 0x0000)",
+        "Ether / Ipv4 / Udp / Trailer(load: 0xaabb)",
+    ] {
+        let bytes = LayerMut::parse(stack).unwrap().build().unwrap();
+        let kinds: Vec<Layers> = Ether::new(&bytes)
+            .layers()
+            .map(|span| span.layer.kind())
+            .collect();
+        assert_eq!(kinds, mut_kinds(&bytes), "{stack}");
+    }
+}
+
+#[test]
+fn test_iter_truncated_quote() {
+    let probe = LayerMut::parse("Ipv4 / Tcp(sport: 1, dport: 2, flags: S)")
+        .unwrap()
+        .build()
+        .unwrap();
+    // the whole quote, the first 8 bytes of the TCP header and a truncated IPv4 header
+    for (quote, expected) in [
+        (&probe[..], Layers::Tcp),
+        (&probe[..28], Layers::Payload),
+        (&probe[..12], Layers::Payload),
+    ] {
+        let quote: String = quote.iter().map(|b| format!("{b:02x}")).collect();
+        let bytes = LayerMut::parse(&format!(
+            "Ether / Ipv4 / Icmp(type: 3, code: 3) / Payload(load: 0x{quote})"
+        ))
+        .unwrap()
+        .build()
+        .unwrap();
+        let kinds: Vec<Layers> = Ether::new(&bytes)
+            .layers()
+            .map(|span| span.layer.kind())
+            .collect();
+        assert_eq!(kinds, mut_kinds(&bytes), "{quote}");
+        assert_eq!(kinds.last(), Some(&expected), "{quote}");
+    }
+}
+
+#[test]
+fn test_iter_spans() {
+    let bytes = LayerMut::parse("Ether / Vlan / Vlan / Ipv4 / Udp / \"abc\" / Trailer(load: 0xff)")
+        .unwrap()
+        .build()
+        .unwrap();
+    let spans: Vec<(Layers, usize, usize)> = LayerIter::new(&bytes, Layers::Ether)
+        .map(|span| (span.layer.kind(), span.offset, span.len))
+        .collect();
+    assert_eq!(
+        spans,
+        [
+            (Layers::Ether, 0, 14),
+            (Layers::Vlan, 14, 4),
+            (Layers::Vlan, 18, 4),
+            (Layers::Ipv4, 22, 20),
+            (Layers::Udp, 42, 8),
+            (Layers::Payload, 50, 3),
+            (Layers::Trailer, 53, 1),
+        ]
+    );
+
+    // the buffer of a layer ends with its payload, the trailer is not part of it
+    let ether = Ether::new(&bytes);
+    let mut iter = ether.layers().skip(3);
+    let Some(span) = iter.next() else {
+        unreachable!()
+    };
+    assert!(matches!(span.layer, Layer::Ipv4(_)));
+    assert_eq!(span.layer.get_buf(), &bytes[22..53]);
+    let Some(span) = iter.last() else {
+        unreachable!()
+    };
+    assert!(matches!(span.layer, Layer::Trailer(trailer) if trailer.buf == [0xff]));
+}
+
+#[test]
+fn test_iter_upper_layer() {
+    let bytes = LayerMut::parse("Ipv6 / DestOpts / Udp")
+        .unwrap()
+        .build()
+        .unwrap();
+    let kinds: Vec<Layers> = Ipv6::new(&bytes)
+        .layers()
+        .map(|span| span.layer.kind())
+        .collect();
+    assert_eq!(kinds, [Layers::Ipv6, Layers::DestOpts, Layers::Udp]);
+
+    // the walk ends after a truncated layer
+    let bytes = LayerMut::parse("Ether / Ipv4 / Tcp")
+        .unwrap()
+        .build()
+        .unwrap();
+    let kinds: Vec<Layers> = LayerIter::new(&bytes[..40], Layers::Ether)
+        .map(|span| span.layer.kind())
+        .collect();
+    assert_eq!(kinds, [Layers::Ether, Layers::Ipv4]);
+    assert_eq!(LayerIter::new(&[0; 4], Layers::Ether).count(), 0);
+}
+
+/// Header with a 16 bit tag followed by an IPv6 packet
+struct Tag;
+
+impl Protocol for Tag {
+    fn name(&self) -> &'static str {
+        "Tag"
+    }
+    fn fields(&self) -> &'static [Field] {
+        const FIELDS: &[Field] = &[Field::int("tag", 0, 16)];
+        FIELDS
+    }
+    fn new_header(&self) -> Vec<u8> {
+        vec![0; 2]
+    }
+    fn header_len(&self, _buf: &[u8]) -> Result<usize> {
+        Ok(2)
+    }
+    fn next(&self, _header: &[u8]) -> Option<Binding> {
+        Some(Binding::EtherType(0x86dd))
+    }
+}
+
+#[test]
+fn test_iter_registry() {
+    registry::bind(Binding::UdpPort(47001), Arc::new(Tag));
+    let bytes = LayerMut::parse("Ether / Ipv4 / Udp(dport: 47001) / Tag / Ipv6 / Tcp")
+        .unwrap()
+        .build()
+        .unwrap();
+    // the iterator dispatches to the bound protocol and its upper layers like `from_buf`
+    let spans: Vec<(Layers, usize)> = LayerIter::new(&bytes, Layers::Ether)
+        .map(|span| (span.layer.kind(), span.offset))
+        .collect();
+    assert_eq!(
+        spans,
+        [
+            (Layers::Ether, 0),
+            (Layers::Ipv4, 14),
+            (Layers::Udp, 34),
+            (Layers::Custom, 42),
+            (Layers::Ipv6, 44),
+            (Layers::Tcp, 84),
+        ]
+    );
+    assert_eq!(
+        spans.iter().map(|span| span.0).collect::<Vec<_>>(),
+        mut_kinds(&bytes)
+    );
+    registry::unbind(Binding::UdpPort(47001));
+}
@@ -1,5 +1,7 @@
 use pnet::util::MacAddr;
-use pnet_layers::{EtherMut, Ipv4Mut, LayerMut, LayerMutable, Layers, TcpMut, VlanMut};
+use pnet_layers::{
+    Error, EtherMut, Ipv4Mut, LayerMut, LayerMutable, Layers, TcpMut, UdpMut, Value, VlanMut,
+};
 use std::net::Ipv4Addr;
 use std::str::FromStr;
 
@@ -48,16 +50,16 @@ fn test_layer_build() {
     }
 
     let mut ether_tcp = ether.clone();
-    assert!(ether_tcp.add(LayerMut::Vlan(vlan.clone())));
-    assert!(ether_tcp.add(LayerMut::Ipv4(ipv4.clone())));
-    assert!(ether_tcp.add(LayerMut::Tcp(tcp.clone())));
+    assert!(ether_tcp.add(LayerMut::Vlan(vlan.clone())).is_ok());
+    assert!(ether_tcp.add(LayerMut::Ipv4(ipv4.clone())).is_ok());
+    assert!(ether_tcp.add(LayerMut::Tcp(tcp.clone())).is_ok());
 
     {
         // adding VLAN after Ipv4 -> Should be replaced so that ether > vlan > ipv4 > tcp
         let mut ether_tcp_vlan = ether.clone();
-        assert!(ether_tcp_vlan.add(LayerMut::Ipv4(ipv4.clone())));
-        assert!(ether_tcp_vlan.add(LayerMut::Vlan(vlan.clone())));
-        assert!(ether_tcp_vlan.add(LayerMut::Tcp(tcp.clone())));
+        assert!(ether_tcp_vlan.add(LayerMut::Ipv4(ipv4.clone())).is_ok());
+        assert!(ether_tcp_vlan.add(LayerMut::Vlan(vlan.clone())).is_ok());
+        assert!(ether_tcp_vlan.add(LayerMut::Tcp(tcp.clone())).is_ok());
 
         assert_eq!(format!("{ether_tcp:?}"), format!("{ether_tcp_vlan:?}"));
         assert_eq!(
@@ -69,7 +71,13 @@ fn test_layer_build() {
     {
         let mut ether_tcp_vlan = ether.clone();
         // there is no IPv4 layer can not add Tcp
-        assert!(!ether_tcp_vlan.add(LayerMut::Tcp(tcp)));
+        assert!(matches!(
+            ether_tcp_vlan.add(LayerMut::Tcp(tcp)),
+            Err(Error::IllegalStacking {
+                layer: Layers::Tcp,
+                lower: Layers::Ether
+            })
+        ));
     }
 
     {
@@ -104,3 +112,173 @@ fn test_layer_build() {
         }
     }
 }
+
+#[test]
+fn test_layer_errors() {
+    // Ether > Vlan > Ipv4 > Tcp, but the TCP header is cut after 10 bytes
+    let bytes = hex::decode("2222222222221111111111118100800a080045000028487640004006dbe10101010102020202b3ec9076015297b1dda5").unwrap();
+    assert!(matches!(
+        EtherMut::from_buf(bytes),
+        Err(Error::Truncated {
+            layer: Layers::Tcp,
+            offset: 38
+        })
+    ));
+
+    let mut udp = UdpMut::new();
+    udp.set_payload(&[0; u16::MAX as usize]);
+    assert!(matches!(
+        udp.build(),
+        Err(Error::LengthOverflow {
+            layer: Layers::Udp,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn test_layer_nth() {
+    let bytes = LayerMut::parse("Ether / Vlan(id: 10) / Vlan(id: 20) / Ipv4 / Udp")
+        .unwrap()
+        .build()
+        .unwrap();
+    let mut ether = EtherMut::from_buf(bytes).unwrap();
+    assert_eq!(ether.count(&Layers::Vlan), 2);
+    assert_eq!(ether.count(&Layers::Tcp), 0);
+    let kinds: Vec<Layers> = ether.layers().map(LayerMut::kind).collect();
+    assert_eq!(
+        kinds,
+        [Layers::Vlan, Layers::Vlan, Layers::Ipv4, Layers::Udp]
+    );
+
+    // the inner tag is reachable without walking the upper layers by hand
+    let inner = ether.get_layer_nth(&Layers::Vlan, 1).unwrap();
+    assert_eq!(inner.get_field("id"), Some(Value::Int(20)));
+    inner.set_field("id", &Value::Int(30)).unwrap();
+    assert!(ether.get_layer_nth(&Layers::Vlan, 2).is_none());
+    assert_eq!(
+        ether
+            .get_layer_nth(&Layers::Vlan, 0)
+            .and_then(|vlan| vlan.get_field("id")),
+        Some(Value::Int(10))
+    );
+
+    let mut layers = ether.layers_mut();
+    while let Some(layer) = layers.next() {
+        if layer.kind() == Layers::Udp {
+            layer.set_field("dport", &Value::Int(53)).unwrap();
+        }
+    }
+    let ids: Vec<_> = ether
+        .layers()
+        .filter(|layer| layer.kind() == Layers::Vlan)
+        .filter_map(|vlan| vlan.get_field("id"))
+        .collect();
+    assert_eq!(ids, [Value::Int(10), Value::Int(30)]);
+
+    // a `LayerMut` counts its upper layers like the layer it holds
+    let vlans = ether.count(&Layers::Vlan);
+    let packet = LayerMut::Ether(ether);
+    assert_eq!(packet.layers().count(), 4);
+    assert_eq!(packet.count(&Layers::Vlan), vlans);
+    assert_eq!(packet.count(&Layers::Ether), 0);
+    assert_eq!(
+        packet
+            .layers()
+            .last()
+            .and_then(|udp| udp.get_field("dport")),
+        Some(Value::Int(53))
+    );
+}
+
+#[test]
+fn test_layer_restack() {
+    let kinds = |packet: &EtherMut| packet.layers().map(LayerMut::kind).collect::<Vec<_>>();
+    let bytes = LayerMut::parse("Ether / Vlan(id: 10) / Ipv4 / Tcp / \"data\"")
+        .unwrap()
+        .build()
+        .unwrap();
+    let mut ether = EtherMut::from_buf(bytes).unwrap();
+
+    // strip the VLAN tag
+    let vlan = ether.remove(&Layers::Vlan).unwrap();
+    assert_eq!(vlan.get_field("id"), Some(Value::Int(10)));
+    assert!(vlan.upper_layer().is_none());
+    assert_eq!(kinds(&ether), [Layers::Ipv4, Layers::Tcp, Layers::Payload]);
+
+    // swap TCP for UDP, the payload is kept
+    let tcp = ether
+        .replace(&Layers::Tcp, LayerMut::new(Layers::Udp))
+        .unwrap();
+    assert_eq!(tcp.kind(), Layers::Tcp);
+    assert_eq!(kinds(&ether), [Layers::Ipv4, Layers::Udp, Layers::Payload]);
+
+    // tag the frame again
+    ether
+        .insert_before(&Layers::Ipv4, LayerMut::new(Layers::Vlan))
+        .unwrap();
+    ether
+        .insert_after(&Layers::Vlan, LayerMut::new(Layers::Vlan))
+        .unwrap();
+    assert_eq!(ether.count(&Layers::Vlan), 2);
+
+    let bytes = ether.clone().build().unwrap();
+    let parsed = EtherMut::from_buf(bytes).unwrap();
+    assert_eq!(
+        kinds(&parsed),
+        [
+            Layers::Vlan,
+            Layers::Vlan,
+            Layers::Ipv4,
+            Layers::Udp,
+            Layers::Payload
+        ]
+    );
+    assert_eq!(
+        parsed
+            .layers()
+            .last()
+            .map(|payload| payload.header().to_vec()),
+        Some(b"data".to_vec())
+    );
+
+    let payload = ether.truncate_after(&Layers::Udp).unwrap();
+    assert_eq!(payload.map(|payload| payload.kind()), Some(Layers::Payload));
+    assert_eq!(ether.layers().last().map(LayerMut::kind), Some(Layers::Udp));
+}
+
+#[test]
+fn test_layer_restack_errors() {
+    let mut packet = LayerMut::parse("Ether / Ipv4 / Tcp / \"data\"").unwrap();
+    let before = packet.to_string();
+
+    // TCP is not allowed directly on Ethernet
+    assert!(matches!(
+        packet.remove(&Layers::Ipv4),
+        Err(Error::IllegalStacking {
+            layer: Layers::Tcp,
+            lower: Layers::Ether
+        })
+    ));
+    assert!(matches!(
+        packet.replace(&Layers::Ipv4, LayerMut::new(Layers::Arp)),
+        Err(Error::IllegalStacking {
+            layer: Layers::Tcp,
+            lower: Layers::Arp
+        })
+    ));
+    assert!(matches!(
+        packet.insert_after(&Layers::Ipv4, LayerMut::new(Layers::Vlan)),
+        Err(Error::IllegalStacking {
+            layer: Layers::Vlan,
+            lower: Layers::Ipv4
+        })
+    ));
+    assert!(matches!(
+        packet.remove(&Layers::Vlan),
+        Err(Error::MissingLayer {
+            layer: Layers::Vlan
+        })
+    ));
+    assert_eq!(packet.to_string(), before);
+}
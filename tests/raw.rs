@@ -0,0 +1,162 @@
+use pnet_layers::overrides::Overrides;
+use pnet_layers::registry::{Binding, Protocol};
+use pnet_layers::{
+    Ether, EtherMut, Field, Layer, LayerImmutable, LayerMut, LayerMutable, Layers, RawMut, Result,
+    Value,
+};
+use std::sync::Arc;
+
+fn raw(packet: &LayerMut) -> Option<&RawMut> {
+    let mut layer = Some(packet);
+    while let Some(current) = layer {
+        if let LayerMut::Raw(raw) = current {
+            return Some(raw);
+        }
+        layer = current.upper_layer();
+    }
+    None
+}
+
+#[test]
+fn test_raw_round_trip() {
+    // PTP sync message after the Ethernet header
+    let mut ptp = hex::decode("011b19000000001122334455").unwrap();
+    ptp.extend_from_slice(&[0x88, 0xf7]);
+    ptp.extend((0..44).map(|i| i as u8));
+
+    for (bytes, proto) in [
+        (ptp, 0x88f7),
+        // GRE in IPv4 and ESP in IPv6
+        (
+            LayerMut::parse("Ether / Ipv4(proto: 47) / Raw(load: 0x000008000102030405)")
+                .unwrap()
+                .build()
+                .unwrap(),
+            47,
+        ),
+        (
+            LayerMut::parse("Ether / Vlan / Ipv6(nh: 50) / Raw(load: 0xdeadbeef00000001)")
+                .unwrap()
+                .build()
+                .unwrap(),
+            50,
+        ),
+    ] {
+        let packet = LayerMut::Ether(EtherMut::from_buf(bytes.clone()).unwrap());
+        assert_eq!(raw(&packet).map(|raw| raw.proto), Some(proto));
+        assert_eq!(packet.clone().build().unwrap(), bytes);
+        let parsed = LayerMut::parse(&packet.canonical()).unwrap();
+        assert_eq!(parsed.build().unwrap(), bytes);
+        assert!(matches!(
+            Ether::new(&bytes).get_layer(Layers::Raw),
+            Some(Layer::Raw(raw)) if bytes.ends_with(raw.buf)
+        ));
+    }
+}
+
+#[test]
+fn test_round_trip_trailing_bytes() {
+    // bytes after the length of a bare IP packet, also in front of an unknown protocol
+    for input in [
+        r#"Ipv4 / Udp(dport: 53) / "query""#,
+        "Ipv4(proto: 47) / Raw(load: 0x000008000102030405)",
+        "Ipv6 / Udp",
+    ] {
+        let packet = LayerMut::parse(input).unwrap();
+        let kind = packet.kind();
+        let mut bytes = packet.build().unwrap();
+        bytes.extend_from_slice(&[0xaa, 0xbb, 0xcc]);
+        let parsed = LayerMut::from_buf(kind, bytes.clone()).unwrap();
+        assert_eq!(parsed.trailer().map(|t| t.buf.len()), Some(3));
+        assert_eq!(parsed.clone().build().unwrap(), bytes, "{input}");
+        let text = parsed.canonical();
+        assert_eq!(LayerMut::parse(&text).unwrap().build().unwrap(), bytes);
+    }
+}
+
+#[test]
+fn test_round_trip_zero_length() {
+    // a capture with segmentation offloading leaves the total length at zero
+    let mut bytes = LayerMut::parse(r#"Ether / Ipv4(id: 1) / Udp(dport: 53) / "query""#)
+        .unwrap()
+        .build()
+        .unwrap();
+    bytes[16..18].copy_from_slice(&[0, 0]);
+    let packet = LayerMut::from_buf(Layers::Ether, bytes.clone()).unwrap();
+
+    // no byte is lost, only the total length and the header checksum are filled in by build
+    let built = packet.clone().build().unwrap();
+    assert_eq!(built.len(), bytes.len());
+    assert_eq!(built[16..18], [0, 20 + 8 + 5]);
+    assert_eq!(built[..16], bytes[..16]);
+    assert_eq!(built[26..], bytes[26..]);
+
+    // keeping the fields builds the packet as it was captured
+    let mut overrides = Overrides::new();
+    overrides.keep(Layers::Ipv4);
+    assert_eq!(packet.build_with(&overrides).unwrap(), bytes);
+}
+
+#[test]
+fn test_raw_proto() {
+    let mut ether = EtherMut::new();
+    ether
+        .add(LayerMut::Raw(RawMut {
+            buf: vec![1, 2, 3],
+            proto: 0x88b5,
+        }))
+        .unwrap();
+    let bytes = ether.build().unwrap();
+    assert_eq!(bytes[12..], [0x88, 0xb5, 1, 2, 3]);
+
+    // the payload can be parsed again as a supported layer
+    let udp = LayerMut::parse("Udp(sport: 1, dport: 2)")
+        .unwrap()
+        .build()
+        .unwrap();
+    let raw = RawMut {
+        buf: udp,
+        proto: 17,
+    };
+    let layer = raw.reinterpret(Layers::Udp).unwrap();
+    assert_eq!(layer.get_field("dport"), Some(pnet_layers::Value::Int(2)));
+    assert!(raw.reinterpret(Layers::Ipv6).is_err());
+}
+
+/// Header with a 16 bit sequence number followed by an IPv4 packet
+struct Seq;
+
+impl Protocol for Seq {
+    fn name(&self) -> &'static str {
+        "Seq"
+    }
+    fn fields(&self) -> &'static [Field] {
+        const FIELDS: &[Field] = &[Field::int("seq", 0, 16)];
+        FIELDS
+    }
+    fn new_header(&self) -> Vec<u8> {
+        vec![0; 2]
+    }
+    fn header_len(&self, _buf: &[u8]) -> Result<usize> {
+        Ok(2)
+    }
+    fn next(&self, _header: &[u8]) -> Option<Binding> {
+        Some(Binding::EtherType(0x0800))
+    }
+}
+
+#[test]
+fn test_raw_reinterpret_as() {
+    let mut buf = vec![0, 7];
+    buf.extend(LayerMut::parse("Ipv4 / Udp").unwrap().build().unwrap());
+    let raw = RawMut { buf, proto: 0x88b5 };
+    // the protocol does not have to be bound in the registry
+    let layer = raw.reinterpret_as(Arc::new(Seq)).unwrap();
+    assert_eq!(layer.name(), "Seq");
+    assert_eq!(layer.get_field("seq"), Some(Value::Int(7)));
+    let kinds: Vec<Layers> = std::iter::successors(layer.upper_layer(), |l| l.upper_layer())
+        .map(LayerMut::kind)
+        .collect();
+    assert_eq!(kinds, [Layers::Ipv4, Layers::Udp]);
+    assert_eq!(layer.build().unwrap(), raw.buf);
+}
@@ -0,0 +1,63 @@
+use pnet_layers::{EtherMut, LayerMut, LayerMutable, TcpMut};
+
+#[test]
+fn test_serde_round_trip() {
+    let packet = LayerMut::parse(
+        r#"Ether(src: 3c:ce:33:33:33:33) / Vlan(id: 5) / Ipv4(id: 1, src: 10.0.0.1, dst: 10.0.0.2, options: 0x01010100) / Tcp(dport: 80, flags: SA) / "data""#,
+    )
+    .unwrap();
+    let bytes = packet.clone().build().unwrap();
+
+    let json = serde_json::to_value(&packet).unwrap();
+    let ipv4 = &json["Ether"]["upper_layer"]["Vlan"]["upper_layer"]["Ipv4"];
+    assert_eq!(ipv4["src"], "10.0.0.1");
+    assert_eq!(ipv4["options"], "0x01010100");
+    assert_eq!(ipv4["upper_layer"]["Tcp"]["dport"], 80);
+    assert_eq!(ipv4["upper_layer"]["Tcp"]["flags"], "SA");
+
+    let parsed: LayerMut = serde_json::from_value(json).unwrap();
+    assert_eq!(parsed.canonical(), packet.canonical());
+    assert_eq!(parsed.build().unwrap(), bytes);
+
+    // the layer types are serialized without the layer name
+    let ether = EtherMut::from_buf(bytes.clone()).unwrap();
+    let json = serde_json::to_string(&ether).unwrap();
+    assert!(json.starts_with(r#"{"dst":"00:00:00:00:00:00","src":"3c:ce:33:33:33:33""#));
+    let ether: EtherMut = serde_json::from_str(&json).unwrap();
+    assert_eq!(ether.build().unwrap(), bytes);
+}
+
+#[test]
+fn test_serde_partial() {
+    // missing fields keep their default value and flags can be given as number
+    let tcp: TcpMut = serde_json::from_str(r#"{"dport": 443, "flags": 2}"#).unwrap();
+    let tcp = LayerMut::Tcp(tcp);
+    assert_eq!(
+        tcp.canonical(),
+        LayerMut::parse("Tcp(dport: 443, flags: S)")
+            .unwrap()
+            .canonical()
+    );
+
+    assert!(serde_json::from_str::<TcpMut>(r#"{"foo": 1}"#).is_err());
+    assert!(serde_json::from_str::<TcpMut>(r#"{"dport": 65536}"#).is_err());
+    assert!(serde_json::from_str::<LayerMut>(r#"{"Foo": {}}"#).is_err());
+    assert!(
+        serde_json::from_str::<LayerMut>(r#"{"Udp": {"upper_layer": {"Ether": {}}}}"#).is_err()
+    );
+}
+
+#[test]
+fn test_serde_unordered() {
+    // hand-written maps may list the fields in any order, the ICMP type still sizes the header
+    let icmp: LayerMut =
+        serde_json::from_str(r#"{"Icmp": {"ts_rx": 5, "ts_ori": 4, "code": 0, "type": 13}}"#)
+            .unwrap();
+    assert_eq!(
+        icmp.canonical(),
+        LayerMut::parse("Icmp(type: 13, ts_ori: 4, ts_rx: 5)")
+            .unwrap()
+            .canonical()
+    );
+    assert_eq!(icmp.header().len(), 20);
+}